@@ -0,0 +1,171 @@
+//! The derive macro behind `comma`'s `derive` feature. See `comma::FromCommand` for how fields
+//! are mapped onto tokens; this crate is not meant to be used directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitChar, Type};
+
+/// Implements `comma::FromCommand` for a struct with named fields.
+#[proc_macro_derive(FromCommand, attributes(comma))]
+pub fn derive_from_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// How a field's type wraps the values it holds.
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Bool,
+    Option,
+    Vec,
+    Single,
+}
+
+/// What a field is read from.
+enum Role {
+    Flag,
+    Option,
+    Positional,
+}
+
+struct Field {
+    ident: syn::Ident,
+    name: String,
+    short: Option<char>,
+    shape: Shape,
+    role: Role,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(Error::new(Span::call_site(), "expected named fields")),
+        },
+        _ => {
+            let message = "FromCommand can only be derived for structs";
+            return Err(Error::new(Span::call_site(), message));
+        }
+    };
+
+    let fields = named.iter().map(field).collect::<syn::Result<Vec<_>>>()?;
+    let usage = fields.iter().map(usage).collect::<Vec<_>>().join(" ");
+
+    let specs = fields.iter().filter_map(|field| {
+        let (name, takes_value) = match field.role {
+            Role::Flag => (&field.name, false),
+            Role::Option => (&field.name, true),
+            Role::Positional => return None,
+        };
+        let short = match field.short {
+            Some(short) => quote!(::core::option::Option::Some(#short)),
+            None => quote!(::core::option::Option::None),
+        };
+        Some(quote!((#name, #short, #takes_value)))
+    });
+
+    let values = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let name = &field.name;
+        let method = match (&field.role, field.shape) {
+            (Role::Flag, _) => return quote!(#ident: args.flag(#name)),
+            (Role::Option, Shape::Option) => quote!(optional),
+            (Role::Option, Shape::Vec) => quote!(values),
+            (Role::Option, _) => quote!(required),
+            (Role::Positional, Shape::Option) => quote!(next_positional),
+            (Role::Positional, Shape::Vec) => quote!(remaining),
+            (Role::Positional, _) => quote!(positional),
+        };
+        quote!(#ident: args.#method(#name)?)
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::comma::FromCommand for #ident #ty_generics #where_clause {
+            const USAGE: &'static str = #usage;
+
+            fn from_tokens(
+                tokens: ::comma::__private::Vec<::comma::__private::String>,
+            ) -> ::core::result::Result<Self, ::comma::FromCommandError> {
+                let mut args = ::comma::__private::Args::new(tokens, &[#(#specs),*])?;
+                let value = Self { #(#values),* };
+                args.finish()?;
+                ::core::result::Result::Ok(value)
+            }
+        }
+    })
+}
+
+fn field(field: &syn::Field) -> syn::Result<Field> {
+    let ident = field.ident.clone().expect("named fields have identifiers");
+    let name = ident.to_string();
+    let name = name.trim_start_matches("r#").replace('_', "-");
+    let shape = shape(&field.ty);
+
+    let mut long = false;
+    let mut short = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("comma"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("long") {
+                long = true;
+                Ok(())
+            } else if meta.path.is_ident("short") {
+                short = Some(meta.value()?.parse::<LitChar>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `long` or `short = 'c'`"))
+            }
+        })?;
+    }
+
+    let role = match shape {
+        Shape::Bool => Role::Flag,
+        _ if long || short.is_some() => Role::Option,
+        _ => Role::Positional,
+    };
+    Ok(Field {
+        ident,
+        name,
+        short,
+        shape,
+        role,
+    })
+}
+
+fn shape(ty: &Type) -> Shape {
+    let last = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    };
+    match last {
+        Some(segment) if segment.ident == "bool" => Shape::Bool,
+        Some(segment) if segment.ident == "Option" => Shape::Option,
+        Some(segment) if segment.ident == "Vec" => Shape::Vec,
+        _ => Shape::Single,
+    }
+}
+
+fn usage(field: &Field) -> String {
+    let name = &field.name;
+    let flag = match field.short {
+        Some(short) => format!("-{}|--{}", short, name),
+        None => format!("--{}", name),
+    };
+    match (&field.role, field.shape) {
+        (Role::Flag, _) => format!("[{}]", flag),
+        (Role::Option, Shape::Option) => format!("[{} <{}>]", flag, name),
+        (Role::Option, Shape::Vec) => format!("[{} <{}>]...", flag, name),
+        (Role::Option, _) => format!("{} <{}>", flag, name),
+        (Role::Positional, Shape::Option) => format!("[<{}>]", name),
+        (Role::Positional, Shape::Vec) => format!("[<{}>...]", name),
+        (Role::Positional, _) => format!("<{}>", name),
+    }
+}
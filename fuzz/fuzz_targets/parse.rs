@@ -0,0 +1,73 @@
+//! Checks that no parser configuration panics on arbitrary input, and that every token and error
+//! points back into the input correctly.
+
+#![no_main]
+
+use comma::{ControlCharacters, Escapes, Newlines, Parser, TrailingBackslash};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (options, input) = match data.split_first() {
+        Some((&options, input)) => (options, input),
+        None => return,
+    };
+    let input = match std::str::from_utf8(input) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+
+    let parser = Parser::new()
+        .keep_quotes(options & 1 != 0)
+        .split_long_options(options & 2 != 0)
+        .strict_escapes(options & 4 != 0)
+        .percent_decode(options & 8 != 0)
+        .strip_bom(options & 16 != 0)
+        .auto_close_quotes(options & 32 != 0)
+        .comment("#")
+        .trailing_backslash(match options % 3 {
+            0 => TrailingBackslash::Error,
+            1 => TrailingBackslash::Literal,
+            _ => TrailingBackslash::Continue,
+        })
+        .escapes(match options % 5 {
+            0 => Escapes::Never,
+            1 => Escapes::DoubleQuoted,
+            _ => Escapes::Anywhere,
+        })
+        .newlines(match options % 7 {
+            0 => Newlines::Terminate,
+            1 => Newlines::Reject,
+            _ => Newlines::Separate,
+        })
+        .control_characters(match options % 11 {
+            0 => ControlCharacters::Strip,
+            1 => ControlCharacters::Reject,
+            _ => ControlCharacters::Allow,
+        });
+
+    match parser.try_parse(input) {
+        Ok(_) => assert!(parser.diagnose(input).is_empty()),
+        Err(error) => {
+            assert!(error.span().start <= error.span().end && error.span().end <= input.len());
+            assert!(input.is_char_boundary(error.offset()));
+            error.render(input);
+            assert!(!parser.diagnose(input).is_empty());
+        }
+    }
+
+    if let Some(tokens) = parser.parse_tokens(input) {
+        for token in &tokens {
+            token.position(input);
+            token.key_value();
+        }
+    }
+
+    parser.parse_lossy(input);
+    let _ = parser.parse_with_warnings(input);
+    parser.parse_line(input);
+    parser.parse_script(input);
+    parser.parse_ast(input);
+    parser.parse_classified(input);
+    parser.parse_plus_commands(input);
+    comma::parse_command(input);
+});
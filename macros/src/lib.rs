@@ -0,0 +1,45 @@
+//! Compile-time parsing of constant commands with `comma`.
+//!
+//! The [`comma!`] macro parses a string literal while the crate using it is compiled, so that a
+//! quoting mistake in a constant command is a build error rather than a runtime failure, and the
+//! parsed tokens cost nothing to produce when the program runs.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Error, LitStr};
+
+/// Parses a string literal as a command, like `comma::Parser::new().try_parse`, expanding to
+/// its tokens as a `&'static [&'static str]`. Malformed input fails to compile.
+///
+/// ```
+/// use comma_macros::comma;
+///
+/// const COMMIT: &[&str] = comma!("git commit -m 'initial commit'");
+/// assert_eq!(COMMIT, ["git", "commit", "-m", "initial commit"]);
+/// assert!(comma!("  ").is_empty());
+/// ```
+///
+/// ```compile_fail
+/// let tokens = comma_macros::comma!("echo 'unterminated");
+/// ```
+#[proc_macro]
+pub fn comma(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let tokens = match comma::Parser::new().try_parse(&literal.value()) {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            let message = format!("malformed command: {}", error);
+            return Error::new(literal.span(), message)
+                .into_compile_error()
+                .into();
+        }
+    };
+
+    let expanded = quote! {
+        {
+            const TOKENS: &[&str] = &[#(#tokens),*];
+            TOKENS
+        }
+    };
+    expanded.into()
+}
@@ -0,0 +1,96 @@
+//! Expansion of command aliases.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::Parser;
+use crate::{ErrorKind, Limit, ParseError};
+use core::ops::Range;
+
+pub(crate) fn parse<F>(
+    parser: &Parser,
+    input: &str,
+    mut resolve: F,
+) -> Result<Vec<String>, ParseError>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut line = input.to_string();
+    // Aliases already expanded, which are not expanded again to prevent loops.
+    let mut expanded: Vec<String> = Vec::new();
+    // The number of bytes of replacement text, counted against `Parser::max_expansion`.
+    let mut size = 0;
+    // The span of the first word as written in the input, which later rewrites of `line` move.
+    let mut origin: Option<Range<usize>> = None;
+
+    while let Some(first) = Lexer::new(parser, &line).next() {
+        let first = first?;
+
+        // As in shells, quoting or escaping any part of a word prevents alias expansion.
+        if !first.plain || expanded.contains(&first.value) {
+            break;
+        }
+
+        let span = origin.get_or_insert_with(|| first.span.clone()).clone();
+        match resolve(&first.value) {
+            Some(replacement) => {
+                size += replacement.len();
+                if parser.max_expansion.map_or(false, |max| size > max) {
+                    let kind = ErrorKind::LimitsExceeded(Limit::ExpansionSize);
+                    return Err(ParseError::new(kind, span));
+                }
+                line = format!("{}{}", replacement, &line[first.span.end..]);
+                expanded.push(first.value);
+            }
+            None => break,
+        }
+    }
+
+    Lexer::new(parser, &line)
+        .map(|token| token.map(|token| token.value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_with_aliases, ErrorKind, Limit, Parser};
+
+    fn aliases(name: &str) -> Option<String> {
+        match name {
+            "ll" => Some(String::from("ls -l")),
+            "ls" => Some(String::from("ls --color 'a b'")),
+            "loop" => Some(String::from("again x")),
+            "again" => Some(String::from("loop y")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn alias_chain() {
+        let result = parse_with_aliases("ll  /tmp", aliases).unwrap();
+        assert_eq!(result, vec!["ls", "--color", "a b", "-l", "/tmp"]);
+
+        let result = parse_with_aliases("'ll' /tmp", aliases).unwrap();
+        assert_eq!(result, vec!["ll", "/tmp"]);
+    }
+
+    #[test]
+    fn alias_loop() {
+        let result = parse_with_aliases("loop z", aliases).unwrap();
+        assert_eq!(result, vec!["loop", "y", "x", "z"]);
+    }
+
+    #[test]
+    fn expansion_limit() {
+        // `ll` expands within the limit, but the `ls` it expands to does not.
+        let parser = Parser::new().max_expansion(Some(10));
+        let error = super::parse(&parser, "  ll /tmp", aliases).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::LimitsExceeded(Limit::ExpansionSize)
+        );
+        assert_eq!(error.span(), 2..4);
+
+        let parser = Parser::new().max_expansion(Some(21));
+        assert!(super::parse(&parser, "  ll /tmp", aliases).is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+//! Helpers for interpreting an already-parsed argument list.
+
+use crate::prelude::*;
+use crate::Token;
+
+/// An argument list divided at its end-of-options marker. See [`split_options`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SplitArgs {
+    /// The arguments preceding the `--` marker.
+    pub options: Vec<String>,
+    /// The arguments following the `--` marker.
+    pub positional: Vec<String>,
+    /// Whether a `--` marker was present at all.
+    pub has_marker: bool,
+}
+
+/// Splits a list of arguments at the first standalone `--`, which is consumed.
+/// Should no marker be present, every argument is placed in [`SplitArgs::options`].
+///
+/// ```
+/// use comma::{parse_command, split_options};
+///
+/// let split = split_options(parse_command("rm -f -- -file --").unwrap());
+/// assert_eq!(split.options, vec!["rm", "-f"]);
+/// assert_eq!(split.positional, vec!["-file", "--"]);
+/// assert!(split.has_marker);
+/// ```
+pub fn split_options(mut args: Vec<String>) -> SplitArgs {
+    match args.iter().position(|arg| arg == "--") {
+        Some(marker) => {
+            let positional = args.split_off(marker + 1);
+            args.pop();
+            SplitArgs {
+                options: args,
+                positional,
+                has_marker: true,
+            }
+        }
+        None => SplitArgs {
+            options: args,
+            positional: Vec::new(),
+            has_marker: false,
+        },
+    }
+}
+
+/// Expands combined short flags, so that `-abc` becomes `-a -b -c`. Flags listed in
+/// `takes_value` consume the rest of their argument as a separate value, so with `o` taking a
+/// value `-vofile` becomes `-v -o file`; should such a flag end its argument, the following
+/// argument is its value and is left untouched. Arguments after a `--` are never expanded.
+///
+/// ```
+/// use comma::{parse_command, split_short_flags};
+///
+/// let args = parse_command("tar -xzf archive.tgz -C -dir -- -rf").unwrap();
+/// assert_eq!(
+///     split_short_flags(args, &['f', 'C']),
+///     vec!["tar", "-x", "-z", "-f", "archive.tgz", "-C", "-dir", "--", "-rf"]
+/// );
+/// ```
+pub fn split_short_flags(args: Vec<String>, takes_value: &[char]) -> Vec<String> {
+    let mut output = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            output.push(arg);
+            output.extend(args);
+            break;
+        }
+
+        let flags = match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && !flags.starts_with('-') => flags,
+            _ => {
+                output.push(arg);
+                continue;
+            }
+        };
+
+        for (at, flag) in flags.char_indices() {
+            output.push(format!("-{}", flag));
+            if !takes_value.contains(&flag) {
+                continue;
+            }
+
+            let value = &flags[at + flag.len_utf8()..];
+            if !value.is_empty() {
+                output.push(value.to_string());
+            } else {
+                output.extend(args.next());
+            }
+            break;
+        }
+    }
+
+    output
+}
+
+/// The role a token plays in a command line. See [`parse_classified`](crate::parse_classified).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// A flag such as `-x` or `-abc`.
+    ShortFlag,
+    /// A flag such as `--name` or `--name=value`.
+    LongFlag,
+    /// A standalone `--`, after which every argument is positional.
+    EndOfOptions,
+    /// An unquoted shell operator such as `|`, `&&`, `;` or `>`.
+    Operator,
+    /// Any other argument, including `-` and every argument following `--`.
+    Positional,
+}
+
+/// Shell operators recognized when they appear as a complete, unquoted token.
+pub(crate) const OPERATORS: &[&str] = &[
+    "|", "||", "|&", "&", "&&", ";", ";;", "<", "<<", "<&", ">", ">>", ">&", "(", ")",
+];
+
+/// Classifies each token. Flags following a `--` are treated as positionals until the next
+/// operator begins a new command.
+pub(crate) fn classify(tokens: &[Token]) -> Vec<TokenKind> {
+    let mut options_ended = false;
+
+    tokens
+        .iter()
+        .map(|token| {
+            let text = token.as_str();
+            if token.plain && OPERATORS.contains(&text) {
+                options_ended = false;
+                TokenKind::Operator
+            } else if options_ended {
+                TokenKind::Positional
+            } else if text == "--" {
+                options_ended = true;
+                TokenKind::EndOfOptions
+            } else if text.starts_with("--") {
+                TokenKind::LongFlag
+            } else if text.starts_with('-') && text.len() > 1 {
+                TokenKind::ShortFlag
+            } else {
+                TokenKind::Positional
+            }
+        })
+        .collect()
+}
+
+/// Groups tokens into game-console commands, each beginning at a plain word starting with `+`,
+/// which is removed. Tokens before the first such word form a leading command of their own.
+pub(crate) fn chain(tokens: Vec<Token>) -> Vec<Vec<String>> {
+    let mut commands: Vec<Vec<String>> = Vec::new();
+
+    for token in tokens {
+        let name = match token.value.strip_prefix('+') {
+            Some(name) if token.plain && !name.is_empty() => Some(name.to_string()),
+            _ => None,
+        };
+
+        match (name, commands.last_mut()) {
+            (Some(name), _) => commands.push(vec![name]),
+            (None, Some(command)) => command.push(token.value),
+            (None, None) => commands.push(vec![token.value]),
+        }
+    }
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_classified, parse_command, parse_plus_commands};
+    use crate::{split_options, split_short_flags, TokenKind};
+
+    #[test]
+    fn split_without_marker() {
+        let split = split_options(parse_command("ls -la dir").unwrap());
+        assert_eq!(split.options, vec!["ls", "-la", "dir"]);
+        assert!(split.positional.is_empty());
+        assert!(!split.has_marker);
+    }
+
+    #[test]
+    fn split_trailing_marker() {
+        let split = split_options(parse_command("git log --").unwrap());
+        assert_eq!(split.options, vec!["git", "log"]);
+        assert!(split.positional.is_empty());
+        assert!(split.has_marker);
+    }
+
+    #[test]
+    fn classification() {
+        use TokenKind::*;
+
+        let result = parse_classified("grep -i --color -- -v '|' - | wc -l").unwrap();
+        let kinds: Vec<TokenKind> = result.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Positional,
+                ShortFlag,
+                LongFlag,
+                EndOfOptions,
+                Positional,
+                Positional,
+                Positional,
+                Operator,
+                Positional,
+                ShortFlag
+            ]
+        );
+        assert_eq!(result[5].1, "|");
+    }
+
+    #[test]
+    fn short_flags() {
+        let args = parse_command("ls -laé - --all -n5").unwrap();
+        assert_eq!(
+            split_short_flags(args, &['n']),
+            vec!["ls", "-l", "-a", "-é", "-", "--all", "-n", "5"]
+        );
+    }
+
+    #[test]
+    fn plus_commands() {
+        let commands = parse_plus_commands("quake -game ctf +set 'sv name' '+1' + +map").unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                vec!["quake", "-game", "ctf"],
+                vec!["set", "sv name", "+1", "+"],
+                vec!["map"],
+            ]
+        );
+        assert!(parse_plus_commands("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,896 @@
+//! Structured parsing of compound command lines.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::quote::quote_with;
+use crate::{ErrorKind, ParseError};
+use crate::{Parser, Token};
+use core::fmt;
+use core::iter;
+use core::ops::Range;
+
+/// A node of a parsed command list. See [`parse_ast`](crate::parse_ast).
+///
+/// Nodes are a simpler view of a [`List`], keeping only its commands' words and how the commands
+/// are grouped; a list converts into them with [`From`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// A simple command and its arguments.
+    Command(Vec<String>),
+    /// A list of commands enclosed in parentheses, e.g. `(cd /tmp; ls)`.
+    Subshell(Vec<Node>),
+    /// A list of commands enclosed in braces, e.g. `{ ls; pwd; }`.
+    Group(Vec<Node>),
+}
+
+/// A list of commands separated by `;`, `&` or newlines. See
+/// [`parse_shell`](crate::parse_shell).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct List {
+    /// The list's items, in order.
+    pub items: Vec<AndOr>,
+}
+
+/// A chain of pipelines joined by `&&` and `||`, e.g. `make && make install || echo failed`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AndOr {
+    /// The first pipeline of the chain.
+    pub first: Pipeline,
+    /// The pipelines following the first, each with the operator preceding it.
+    pub rest: Vec<(Connector, Pipeline)>,
+    /// Whether the chain was ended by `&`, to be run in the background.
+    pub background: bool,
+}
+
+/// An operator joining two pipelines of an [`AndOr`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Connector {
+    /// `&&`, which runs the next pipeline only if the previous one succeeded.
+    And,
+    /// `||`, which runs the next pipeline only if the previous one failed.
+    Or,
+}
+
+/// Commands joined by `|`, each reading the output of the one before.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    /// The commands of the pipeline, in order.
+    pub commands: Vec<Command>,
+}
+
+/// One command of a [`Pipeline`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// A simple command, e.g. `make -j4 >log`.
+    Simple(SimpleCommand),
+    /// A list enclosed in parentheses, to be run in a subshell, e.g. `(cd /tmp; ls)`.
+    Subshell(List),
+    /// A list enclosed in braces, to be run in the current shell, e.g. `{ ls; pwd; }`.
+    Group(List),
+}
+
+impl From<List> for Vec<Node> {
+    /// Converts each command of the list into a node, in order. Simple commands keep only their
+    /// words, dropping their assignments and redirections, and the operators joining commands
+    /// are dropped too.
+    fn from(list: List) -> Self {
+        let pipelines = list.items.into_iter().flat_map(|and_or| {
+            iter::once(and_or.first).chain(and_or.rest.into_iter().map(|(_, pipeline)| pipeline))
+        });
+        pipelines
+            .flat_map(|pipeline| pipeline.commands)
+            .map(|command| match command {
+                Command::Simple(command) => Node::Command(command.words),
+                Command::Subshell(list) => Node::Subshell(list.into()),
+                Command::Group(list) => Node::Group(list.into()),
+            })
+            .collect()
+    }
+}
+
+/// A command with its arguments, preceded by any variable assignments, and with any
+/// redirections, which may be written anywhere among its words.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// The leading `NAME=value` words, as name and value.
+    pub assignments: Vec<(String, String)>,
+    /// The command's name and arguments.
+    pub words: Vec<String>,
+    /// The command's redirections, in order.
+    pub redirects: Vec<Redirect>,
+}
+
+/// A redirection of one of a command's file descriptors, e.g. `2>>errors.log`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirect {
+    /// The file descriptor written before the operator, if any.
+    pub fd: Option<u32>,
+    /// The operator.
+    pub kind: RedirectKind,
+    /// The file, or for duplications the file descriptor, that the operator is applied to.
+    pub target: String,
+}
+
+/// The operator of a [`Redirect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RedirectKind {
+    /// `<`, which reads input from a file.
+    Input,
+    /// `>`, which writes output to a file, replacing its contents.
+    Output,
+    /// `>>`, which appends output to a file.
+    Append,
+    /// `<&`, which duplicates an input file descriptor.
+    DuplicateInput,
+    /// `>&`, which duplicates an output file descriptor.
+    DuplicateOutput,
+}
+
+/// The characters that are operators in the shell grammar.
+const OPERATORS: &[char] = &['\n', ';', '|', '&', '<', '>', '(', ')'];
+
+/// Quotes `word` so that [`parse_shell`](crate::parse_shell) reads it as a single word.
+fn word(word: &str) -> String {
+    quote_with(word, |ch| OPERATORS.contains(&ch))
+}
+
+impl fmt::Display for List {
+    /// Writes the list as [`parse_shell`](crate::parse_shell) would read it, with every word
+    /// quoted only if necessary, and items separated by `; `, or by ` & ` for those run in the
+    /// background.
+    ///
+    /// ```
+    /// use comma::parse_shell;
+    ///
+    /// use comma::ast::Command;
+    ///
+    /// let mut list = parse_shell("ls src|grep 'a b'   && echo done").unwrap();
+    /// for command in &mut list.items[0].first.commands {
+    ///     if let Command::Simple(command) = command {
+    ///         command.words.insert(1, String::from("--color=never"));
+    ///     }
+    /// }
+    /// assert_eq!(
+    ///     list.to_string(),
+    ///     "ls --color=never src | grep --color=never 'a b' && echo done"
+    /// );
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, and_or) in self.items.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", and_or)?;
+            if and_or.background {
+                f.write_str(" &")?;
+            } else if index + 1 < self.items.len() {
+                f.write_str(";")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AndOr {
+    /// Writes the chain, without any `&` ending it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (connector, pipeline) in &self.rest {
+            let operator = match connector {
+                Connector::And => "&&",
+                Connector::Or => "||",
+            };
+            write!(f, " {} {}", operator, pipeline)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, command) in self.commands.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{}", command)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Command {
+    /// Writes a group with its last item terminated, as in `{ ls; pwd; }`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Simple(command) => write!(f, "{}", command),
+            Command::Subshell(list) => write!(f, "({})", list),
+            Command::Group(list) => {
+                let background = list.items.last().map_or(false, |and_or| and_or.background);
+                let terminator = if background { "" } else { ";" };
+                write!(f, "{{ {}{} }}", list, terminator)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    /// Writes the assignments, then the words, then the redirections of the command.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let assignments = self
+            .assignments
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, word(value)));
+        let words = self.words.iter().enumerate().map(|(index, text)| {
+            // A first word that looks like an assignment or a brace must be quoted to stay a word.
+            let first = index == 0 && self.assignments.is_empty();
+            if first && (text == "{" || text == "}") {
+                quote_with(text, |_| true)
+            } else if first && text.contains('=') {
+                quote_with(text, |ch| ch == '=')
+            } else {
+                word(text)
+            }
+        });
+        let redirects = self.redirects.iter().map(ToString::to_string);
+
+        let parts: Vec<String> = assignments.chain(words).chain(redirects).collect();
+        f.write_str(&parts.join(" "))
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(fd) = self.fd {
+            write!(f, "{}", fd)?;
+        }
+        let operator = match self.kind {
+            RedirectKind::Input => "<",
+            RedirectKind::Output => ">",
+            RedirectKind::Append => ">>",
+            RedirectKind::DuplicateInput => "<&",
+            RedirectKind::DuplicateOutput => ">&",
+        };
+        write!(f, "{}{}", operator, word(&self.target))
+    }
+}
+
+/// A traversal of a [`List`]. Each method is called on reaching a node of its type, and by
+/// default walks into the node's children with the matching `walk_` function, so implementations
+/// need only override the methods for the nodes they care about. An overriding method can call
+/// the `walk_` function itself to continue into the children, or not call it to skip them.
+///
+/// Command substitutions are not modelled. The shell grammar rejects an unquoted `$(date)`, and
+/// a quoted one, or one written with backquotes, is visited only as part of the word holding it.
+///
+/// ```
+/// use comma::ast::{Redirect, RedirectKind, Visitor};
+/// use comma::parse_shell;
+///
+/// #[derive(Default)]
+/// struct Files(Vec<String>);
+///
+/// impl Visitor for Files {
+///     fn visit_redirect(&mut self, redirect: &Redirect) {
+///         if !matches!(redirect.kind, RedirectKind::DuplicateInput | RedirectKind::DuplicateOutput) {
+///             self.0.push(redirect.target.clone());
+///         }
+///     }
+/// }
+///
+/// let list = parse_shell("sort <in.txt | uniq >out.txt 2>&1; wc -l out.txt >>counts").unwrap();
+/// let mut files = Files::default();
+/// files.visit_list(&list);
+/// assert_eq!(files.0, vec!["in.txt", "out.txt", "counts"]);
+/// ```
+pub trait Visitor {
+    /// Visits a list.
+    fn visit_list(&mut self, list: &List) {
+        walk_list(self, list);
+    }
+
+    /// Visits a chain of pipelines.
+    fn visit_and_or(&mut self, and_or: &AndOr) {
+        walk_and_or(self, and_or);
+    }
+
+    /// Visits a pipeline.
+    fn visit_pipeline(&mut self, pipeline: &Pipeline) {
+        walk_pipeline(self, pipeline);
+    }
+
+    /// Visits a command of a pipeline.
+    fn visit_command(&mut self, command: &Command) {
+        walk_command(self, command);
+    }
+
+    /// Visits the list of a subshell, e.g. `(cd /tmp; ls)`.
+    fn visit_subshell(&mut self, list: &List) {
+        self.visit_list(list);
+    }
+
+    /// Visits the list of a brace group, e.g. `{ ls; pwd; }`.
+    fn visit_group(&mut self, list: &List) {
+        self.visit_list(list);
+    }
+
+    /// Visits a simple command.
+    fn visit_simple_command(&mut self, command: &SimpleCommand) {
+        walk_simple_command(self, command);
+    }
+
+    /// Visits a variable assignment preceding a command.
+    fn visit_assignment(&mut self, _name: &str, _value: &str) {}
+
+    /// Visits a command's name or one of its arguments.
+    fn visit_word(&mut self, _word: &str) {}
+
+    /// Visits a redirection.
+    fn visit_redirect(&mut self, _redirect: &Redirect) {}
+}
+
+/// Visits each item of `list`.
+pub fn walk_list<V: Visitor + ?Sized>(visitor: &mut V, list: &List) {
+    for and_or in &list.items {
+        visitor.visit_and_or(and_or);
+    }
+}
+
+/// Visits each pipeline of `and_or`.
+pub fn walk_and_or<V: Visitor + ?Sized>(visitor: &mut V, and_or: &AndOr) {
+    visitor.visit_pipeline(&and_or.first);
+    for (_, pipeline) in &and_or.rest {
+        visitor.visit_pipeline(pipeline);
+    }
+}
+
+/// Visits each command of `pipeline`.
+pub fn walk_pipeline<V: Visitor + ?Sized>(visitor: &mut V, pipeline: &Pipeline) {
+    for command in &pipeline.commands {
+        visitor.visit_command(command);
+    }
+}
+
+/// Visits `command` as a simple command, a subshell or a group.
+pub fn walk_command<V: Visitor + ?Sized>(visitor: &mut V, command: &Command) {
+    match command {
+        Command::Simple(command) => visitor.visit_simple_command(command),
+        Command::Subshell(list) => visitor.visit_subshell(list),
+        Command::Group(list) => visitor.visit_group(list),
+    }
+}
+
+/// Visits the assignments, then the words, then the redirections of `command`.
+pub fn walk_simple_command<V: Visitor + ?Sized>(visitor: &mut V, command: &SimpleCommand) {
+    for (name, value) in &command.assignments {
+        visitor.visit_assignment(name, value);
+    }
+    for word in &command.words {
+        visitor.visit_word(word);
+    }
+    for redirect in &command.redirects {
+        visitor.visit_redirect(redirect);
+    }
+}
+
+/// An operator of the shell grammar.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Separator,
+    Background,
+    Pipe,
+    Connector(Connector),
+    Redirect(RedirectKind),
+    Open,
+    Close,
+}
+
+/// The delimiter that ends a nested list.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Delimiter {
+    Paren,
+    Brace,
+}
+
+/// One lexical element of the shell grammar.
+enum Lexeme {
+    Word(Token),
+    Operator(Operator, Range<usize>),
+}
+
+impl Lexeme {
+    fn span(&self) -> Range<usize> {
+        match self {
+            Lexeme::Word(token) => token.span.clone(),
+            Lexeme::Operator(_, span) => span.clone(),
+        }
+    }
+}
+
+/// Reads a [`List`], with one lexeme of lookahead.
+struct ShellParser<'p, 'a> {
+    input: &'a str,
+    lexer: Lexer<'p, 'a>,
+    peeked: Option<Lexeme>,
+    /// Whether leading `NAME=value` words are read as assignments.
+    assignments: bool,
+}
+
+impl ShellParser<'_, '_> {
+    fn lexeme(&mut self) -> Result<Option<Lexeme>, ParseError> {
+        if let Some(lexeme) = self.peeked.take() {
+            return Ok(Some(lexeme));
+        }
+
+        loop {
+            self.lexer.skip_separators();
+            let at = self.lexer.offset();
+            if let Some(ch) = self.lexer.punctuation() {
+                let next = self.input[at + ch.len_utf8()..].chars().next();
+                let (operator, long) = match (ch, next) {
+                    ('|', Some('|')) => (Operator::Connector(Connector::Or), true),
+                    ('&', Some('&')) => (Operator::Connector(Connector::And), true),
+                    ('>', Some('>')) => (Operator::Redirect(RedirectKind::Append), true),
+                    ('>', Some('&')) => (Operator::Redirect(RedirectKind::DuplicateOutput), true),
+                    ('<', Some('&')) => (Operator::Redirect(RedirectKind::DuplicateInput), true),
+                    ('|', _) => (Operator::Pipe, false),
+                    ('&', _) => (Operator::Background, false),
+                    ('>', _) => (Operator::Redirect(RedirectKind::Output), false),
+                    ('<', _) => (Operator::Redirect(RedirectKind::Input), false),
+                    ('(', _) => (Operator::Open, false),
+                    (')', _) => (Operator::Close, false),
+                    _ => (Operator::Separator, false),
+                };
+                if long {
+                    self.lexer.punctuation();
+                }
+                let end = self.lexer.offset();
+                return Ok(Some(Lexeme::Operator(operator, at..end)));
+            }
+
+            match self.lexer.next().transpose()? {
+                Some(token) => return Ok(Some(Lexeme::Word(token))),
+                // A comment stops the lexer short of the newline ending it.
+                None if self.lexer.offset() < self.input.len() => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Lexeme>, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexeme()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    /// Whether the next lexeme is the given operator, which is consumed if so.
+    fn accept(&mut self, operator: Operator) -> Result<bool, ParseError> {
+        match self.peek()? {
+            Some(Lexeme::Operator(next, _)) if *next == operator => {
+                self.peeked = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Reads items until the delimiter ending the list, or until the end of input if there is
+    /// none. The delimiter is given with the byte offset of the one that opened the list.
+    fn list(&mut self, close: Option<(Delimiter, usize)>) -> Result<List, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            if self.accept(Operator::Separator)? {
+                continue;
+            }
+            if self.peek()?.is_none() {
+                return match close {
+                    None => Ok(List { items }),
+                    Some((_, open)) => {
+                        let span = open..self.input.len();
+                        Err(ParseError::new(ErrorKind::UnclosedGroup, span))
+                    }
+                };
+            }
+            if self.closes(close)? {
+                self.peeked = None;
+                return Ok(List { items });
+            }
+
+            let mut and_or = self.and_or()?;
+            match self.peek()? {
+                Some(Lexeme::Operator(Operator::Background, _)) => {
+                    self.peeked = None;
+                    and_or.background = true;
+                }
+                Some(Lexeme::Operator(Operator::Separator, _)) => self.peeked = None,
+                None => {}
+                Some(lexeme) => {
+                    let span = lexeme.span();
+                    if !self.closes(close)? {
+                        return Err(unexpected(span));
+                    }
+                }
+            }
+            items.push(and_or);
+        }
+    }
+
+    /// Whether the next lexeme is the delimiter ending the list, as given to [`list`](Self::list).
+    fn closes(&mut self, close: Option<(Delimiter, usize)>) -> Result<bool, ParseError> {
+        Ok(match (close, self.peek()?) {
+            (Some((Delimiter::Paren, _)), Some(Lexeme::Operator(Operator::Close, _))) => true,
+            (Some((Delimiter::Brace, _)), Some(Lexeme::Word(token))) => is_reserved(token, "}"),
+            _ => false,
+        })
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let mut and_or = AndOr {
+            first: self.pipeline()?,
+            ..AndOr::default()
+        };
+        loop {
+            let connector = match self.peek()? {
+                Some(Lexeme::Operator(Operator::Connector(connector), _)) => *connector,
+                _ => return Ok(and_or),
+            };
+            self.peeked = None;
+            while self.accept(Operator::Separator)? {}
+            and_or.rest.push((connector, self.pipeline()?));
+        }
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut commands = vec![self.command()?];
+        while self.accept(Operator::Pipe)? {
+            while self.accept(Operator::Separator)? {}
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline { commands })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        let open = match self.peek()? {
+            Some(Lexeme::Operator(Operator::Open, span)) => (Delimiter::Paren, span.start),
+            Some(Lexeme::Word(token)) if is_reserved(token, "{") => {
+                (Delimiter::Brace, token.span.start)
+            }
+            Some(Lexeme::Word(token)) if is_reserved(token, "}") => {
+                return Err(unexpected(token.span.clone()))
+            }
+            _ => return self.simple_command().map(Command::Simple),
+        };
+        self.peeked = None;
+        let list = self.list(Some(open))?;
+        Ok(match open.0 {
+            Delimiter::Paren => Command::Subshell(list),
+            Delimiter::Brace => Command::Group(list),
+        })
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+        loop {
+            self.peek()?;
+            let token = match self.peeked.take() {
+                Some(Lexeme::Word(token)) => token,
+                Some(lexeme @ Lexeme::Operator(Operator::Redirect(_), _)) => {
+                    self.peeked = Some(lexeme);
+                    command.redirects.push(self.redirect(None)?);
+                    continue;
+                }
+                lexeme => {
+                    let end = self.input.len();
+                    let span = lexeme.as_ref().map_or(end..end, Lexeme::span);
+                    self.peeked = lexeme;
+                    if command == SimpleCommand::default() {
+                        return Err(unexpected(span));
+                    }
+                    return Ok(command);
+                }
+            };
+
+            if let Some(fd) = self.fd(&token)? {
+                command.redirects.push(self.redirect(Some(fd))?);
+                continue;
+            }
+            match self.assignment(&token) {
+                Some(pair) if self.assignments && command.words.is_empty() => {
+                    command.assignments.push(pair)
+                }
+                _ => command.words.push(token.into_string()),
+            }
+        }
+    }
+
+    /// Reads a redirection operator, which must be next, and its target.
+    fn redirect(&mut self, fd: Option<u32>) -> Result<Redirect, ParseError> {
+        let (kind, span) = match self.lexeme()? {
+            Some(Lexeme::Operator(Operator::Redirect(kind), span)) => (kind, span),
+            _ => unreachable!("redirections begin with an operator"),
+        };
+        match self.lexeme()? {
+            Some(Lexeme::Word(token)) => Ok(Redirect {
+                fd,
+                kind,
+                target: token.into_string(),
+            }),
+            Some(lexeme) => Err(unexpected(lexeme.span())),
+            None => Err(unexpected(span)),
+        }
+    }
+
+    /// The file descriptor written by `token`, if it is a number immediately followed by a
+    /// redirection operator.
+    fn fd(&mut self, token: &Token) -> Result<Option<u32>, ParseError> {
+        let number = token.plain && token.as_str().bytes().all(|b| b.is_ascii_digit());
+        match self.peek()? {
+            Some(Lexeme::Operator(Operator::Redirect(_), span))
+                if number && span.start == token.span.end =>
+            {
+                Ok(token.as_str().parse().ok())
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The name and value assigned by `token`, if it is an assignment of the form `NAME=value`
+    /// with the name written without quotes or escapes.
+    fn assignment(&self, token: &Token) -> Option<(String, String)> {
+        let (name, value) = token.key_value()?;
+        let valid = !name.starts_with(|ch: char| ch.is_ascii_digit())
+            && name
+                .chars()
+                .all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+            && self.input[token.span.clone()].starts_with(name);
+        if valid {
+            Some((name.to_string(), value.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+fn unexpected(span: Range<usize>) -> ParseError {
+    ParseError::new(ErrorKind::UnexpectedToken, span)
+}
+
+/// Whether a token is the given reserved word, written without quotes or escapes.
+fn is_reserved(token: &Token, word: &str) -> bool {
+    token.plain && token.as_str() == word
+}
+
+pub(crate) fn parse_shell(parser: &Parser, input: &str) -> Result<List, ParseError> {
+    let lexer = Lexer::new(parser, input).with_punctuation(OPERATORS);
+    let mut shell = ShellParser {
+        input,
+        lexer,
+        peeked: None,
+        assignments: true,
+    };
+    shell.list(None)
+}
+
+/// Parses `input` with the shell grammar reduced to lists and groups, so that pipes,
+/// redirections and assignments are read as ordinary words.
+pub(crate) fn parse(parser: &Parser, input: &str) -> Result<Vec<Node>, ParseError> {
+    let lexer = Lexer::new(parser, input).with_punctuation(&['\n', ';', '(', ')']);
+    let mut shell = ShellParser {
+        input,
+        lexer,
+        peeked: None,
+        assignments: false,
+    };
+    shell.list(None).map(Vec::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Visitor;
+    use crate::ast::{Command, Connector, List, Node, Redirect, RedirectKind, SimpleCommand};
+    use crate::{parse_ast, parse_shell, Parser};
+
+    fn command(words: &[&str]) -> Node {
+        Node::Command(words.iter().map(|word| word.to_string()).collect())
+    }
+
+    fn simple(command: &Command) -> &SimpleCommand {
+        match command {
+            Command::Simple(command) => command,
+            _ => panic!("not a simple command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn groups() {
+        let result = parse_ast("(cd /tmp; ls); { echo '(a)'; (pwd) } ; x\\(y").unwrap();
+        assert_eq!(
+            result,
+            vec![
+                Node::Subshell(vec![command(&["cd", "/tmp"]), command(&["ls"])]),
+                Node::Group(vec![
+                    command(&["echo", "(a)"]),
+                    Node::Subshell(vec![command(&["pwd"])])
+                ]),
+                command(&["x(y"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_errors() {
+        assert_eq!(parse_ast("(echo a"), None);
+        assert_eq!(parse_ast("echo a)"), None);
+        assert_eq!(parse_ast("{ echo a }"), None);
+        assert_eq!(parse_ast("(a) b"), None);
+        assert_eq!(
+            parse_ast("a | b > c"),
+            Some(vec![command(&["a", "|", "b", ">", "c"])])
+        );
+
+        let list = parse_shell("A=1 a | b && (c >x)").unwrap();
+        assert_eq!(
+            Vec::<Node>::from(list),
+            vec![
+                command(&["a"]),
+                command(&["b"]),
+                Node::Subshell(vec![command(&["c"])])
+            ]
+        );
+    }
+
+    #[test]
+    fn shell_grammar() {
+        let list =
+            parse_shell("A=1 B='x y' make -j4 2>&1 >>log | tee out && echo ok & ls").unwrap();
+        let words =
+            |words: &[&str]| -> Vec<String> { words.iter().map(|w| w.to_string()).collect() };
+        assert_eq!(list.items.len(), 2);
+
+        let first = &list.items[0];
+        assert!(first.background && !list.items[1].background);
+        let make = simple(&first.first.commands[0]);
+        assert_eq!(
+            make.assignments,
+            vec![("A".into(), "1".into()), ("B".into(), "x y".into())]
+        );
+        assert_eq!(make.words, words(&["make", "-j4"]));
+        assert_eq!(
+            make.redirects,
+            vec![
+                Redirect {
+                    fd: Some(2),
+                    kind: RedirectKind::DuplicateOutput,
+                    target: "1".into()
+                },
+                Redirect {
+                    fd: None,
+                    kind: RedirectKind::Append,
+                    target: "log".into()
+                },
+            ]
+        );
+        assert_eq!(
+            simple(&first.first.commands[1]).words,
+            words(&["tee", "out"])
+        );
+        assert_eq!(first.rest[0].0, Connector::And);
+        assert_eq!(
+            simple(&list.items[1].first.commands[0]).words,
+            words(&["ls"])
+        );
+
+        let quoted = parse_shell("echo 'a|b' \\& 2 >x '1'>y c=d").unwrap();
+        let echo = simple(&quoted.items[0].first.commands[0]);
+        assert_eq!(echo.words, words(&["echo", "a|b", "&", "2", "1", "c=d"]));
+        assert_eq!(echo.redirects[0].fd, None);
+
+        let commented = Parser::new()
+            .comment("#")
+            .parse_shell("a # b | c\nd")
+            .unwrap();
+        assert_eq!(commented.items.len(), 2);
+    }
+
+    #[test]
+    fn shell_groups() {
+        let list = parse_shell("(cd /tmp; ls) | wc && { A=1 make & }").unwrap();
+        let and_or = &list.items[0];
+        let subshell = match &and_or.first.commands[0] {
+            Command::Subshell(subshell) => subshell,
+            command => panic!("not a subshell: {:?}", command),
+        };
+        assert_eq!(subshell.items.len(), 2);
+        assert_eq!(
+            simple(&subshell.items[0].first.commands[0]).words,
+            ["cd", "/tmp"]
+        );
+        assert_eq!(simple(&and_or.first.commands[1]).words, ["wc"]);
+
+        let group = match &and_or.rest[0].1.commands[0] {
+            Command::Group(group) => group,
+            command => panic!("not a group: {:?}", command),
+        };
+        assert!(group.items[0].background);
+        assert_eq!(
+            simple(&group.items[0].first.commands[0]).assignments.len(),
+            1
+        );
+
+        for input in [
+            "(a",
+            "a)",
+            "{ a }",
+            "(a) b",
+            "}",
+            "a && }",
+            "( | a)",
+            "echo $(date)",
+        ] {
+            assert_eq!(parse_shell(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn shell_errors() {
+        assert_eq!(parse_shell(";\n").unwrap(), List::default());
+        for input in ["| a", "a |", "a && || b", "a >", "a > ;", "& a", "a 'b"] {
+            assert_eq!(parse_shell(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        #[derive(Default)]
+        struct Names(Vec<String>);
+
+        impl Visitor for Names {
+            fn visit_simple_command(&mut self, command: &SimpleCommand) {
+                self.0.extend(command.words.first().cloned());
+            }
+            fn visit_assignment(&mut self, name: &str, _value: &str) {
+                self.0.push(format!("${}", name));
+            }
+        }
+
+        let list = parse_shell("A=1 make || B=2 echo x | tee y; ls &").unwrap();
+        let mut names = Names::default();
+        names.visit_list(&list);
+        assert_eq!(names.0, vec!["make", "echo", "tee", "ls"]);
+
+        let list = parse_shell("(cd /tmp; ls) | { wc; (pwd) }").unwrap();
+        let mut names = Names::default();
+        names.visit_list(&list);
+        assert_eq!(names.0, vec!["cd", "ls", "wc", "pwd"]);
+
+        #[derive(Default)]
+        struct Subshells(usize);
+
+        impl Visitor for Subshells {
+            fn visit_subshell(&mut self, _list: &List) {
+                self.0 += 1;
+            }
+        }
+
+        // The nested `(b)` is skipped, as the outer subshell is not walked into.
+        let list = parse_shell("(a; (b)) | { (c) }").unwrap();
+        let mut subshells = Subshells::default();
+        subshells.visit_list(&list);
+        assert_eq!(subshells.0, 2);
+    }
+
+    #[test]
+    fn shell_round_trip() {
+        for input in [
+            "A='' B='x y' make '-j 4' 2>&1 >>'my log' | tee '>out' && echo '|' '' &",
+            "'A=1' b=2; x <&3 || 2 >y &",
+            "(cd /tmp; ls '(a)') | wc && { '{' a; b & } || { ; }",
+            "'}'; ()",
+            "",
+        ] {
+            let list = parse_shell(input).unwrap();
+            assert_eq!(list.to_string(), input);
+            assert_eq!(parse_shell(&list.to_string()).unwrap(), list);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Parsing of commands read asynchronously from a [`tokio::io::AsyncBufRead`].
+
+use crate::reader::{Lines, ReadError};
+use crate::Parser;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::Stream;
+use tokio::io::AsyncBufRead;
+
+/// A [`Stream`] of the commands read from a [`tokio::io::AsyncBufRead`], such as a TCP control
+/// connection, one per logical line. Lines are joined and parsed exactly as by
+/// [`ReadCommands`](crate::ReadCommands), keeping the unfinished command between reads, and
+/// [`Parser::max_token_length`] bounds the input buffered in the same way. See
+/// [`Parser::commands_from_async_reader`].
+///
+/// The stream ends after an error reading the input, but continues after a malformed command.
+pub struct AsyncCommands<R> {
+    reader: R,
+    lines: Lines,
+    done: bool,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncCommands<R> {
+    pub(crate) fn new(parser: Parser, reader: R) -> Self {
+        Self {
+            reader,
+            lines: Lines::new(parser),
+            done: false,
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncCommands<R> {
+    type Item = Result<Vec<String>, ReadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while !this.done {
+            let data = match Pin::new(&mut this.reader).poll_fill_buf(cx) {
+                Poll::Ready(Ok(data)) => data,
+                Poll::Ready(Err(error)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(ReadError::Io(error))));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            if data.is_empty() {
+                this.done = true;
+                return Poll::Ready(this.lines.finish());
+            }
+
+            let (read, command) = this.lines.feed(data);
+            Pin::new(&mut this.reader).consume(read);
+            if let Some(command) = command {
+                this.done = matches!(command, Err(ReadError::Io(_)));
+                return Poll::Ready(Some(command));
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+    use core::pin::Pin;
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use futures_core::Stream;
+
+    fn waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(core::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(clone(core::ptr::null())) }
+    }
+
+    #[test]
+    fn streams_commands() {
+        let input: &[u8] = b"open 'a\r\nb'\n\nclose \"x";
+        let parser = Parser::new();
+        let mut commands = parser.commands_from_async_reader(input);
+        let waker = waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut next = || match Pin::new(&mut commands).poll_next(&mut cx) {
+            Poll::Ready(command) => command,
+            Poll::Pending => panic!("reading a slice never blocks"),
+        };
+
+        assert_eq!(next().unwrap().unwrap(), vec!["open", "a\nb"]);
+        assert_eq!(
+            next().unwrap().unwrap_err().to_string(),
+            "line 4: unterminated \" quote at byte 6"
+        );
+        assert!(next().is_none());
+    }
+}
@@ -0,0 +1,129 @@
+//! Detection of shell metacharacters within parsed tokens.
+
+use crate::prelude::*;
+use crate::Token;
+use core::fmt;
+use core::ops::Range;
+
+/// A shell construct that a token could trigger if it were later passed to a real shell without
+/// being quoted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HazardKind {
+    /// A `;` or newline, which ends one command and begins another.
+    CommandSeparator,
+    /// A `|`, which pipes output into another command.
+    Pipe,
+    /// A `&`, which runs a command in the background or chains commands with `&&`.
+    Background,
+    /// A `<` or `>`, which redirects input or output, possibly overwriting files.
+    Redirect,
+    /// A backtick or `$(`, which runs a command and substitutes its output.
+    CommandSubstitution,
+}
+
+impl fmt::Display for HazardKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HazardKind::CommandSeparator => "command separator",
+            HazardKind::Pipe => "pipe",
+            HazardKind::Background => "background operator",
+            HazardKind::Redirect => "redirection",
+            HazardKind::CommandSubstitution => "command substitution",
+        })
+    }
+}
+
+/// A shell metacharacter found within a token. See [`Parser::audit`](crate::Parser::audit).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Hazard {
+    kind: HazardKind,
+    token: usize,
+    span: Range<usize>,
+}
+
+impl Hazard {
+    /// The construct the metacharacter could trigger.
+    pub fn kind(&self) -> HazardKind {
+        self.kind
+    }
+
+    /// The index of the token containing the metacharacter.
+    pub fn token(&self) -> usize {
+        self.token
+    }
+
+    /// The byte range of the metacharacter in the input.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} in token {} at byte {}",
+            self.kind, self.token, self.span.start
+        )
+    }
+}
+
+/// Finds the metacharacters written within each token, whether quoted, escaped or bare.
+pub(crate) fn audit(input: &str, tokens: &[Token]) -> Vec<Hazard> {
+    let mut hazards = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        let start = token.span.start;
+        let text = &input[token.span.clone()];
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((at, ch)) = chars.next() {
+            let (kind, len) = match ch {
+                ';' | '\n' => (HazardKind::CommandSeparator, 1),
+                '|' => (HazardKind::Pipe, 1),
+                '&' => (HazardKind::Background, 1),
+                '<' | '>' => (HazardKind::Redirect, 1),
+                '`' => (HazardKind::CommandSubstitution, 1),
+                '$' if matches!(chars.peek(), Some((_, '('))) => {
+                    chars.next();
+                    (HazardKind::CommandSubstitution, 2)
+                }
+                _ => continue,
+            };
+            hazards.push(Hazard {
+                kind,
+                token: index,
+                span: start + at..start + at + len,
+            });
+        }
+    }
+
+    hazards
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{audit_command, HazardKind};
+
+    #[test]
+    fn hazards_in_tokens() {
+        let hazards = audit_command("echo 'a;b' x\\|y `id` \"$(rm)\" $HOME >").unwrap();
+        let found: Vec<_> = hazards
+            .iter()
+            .map(|hazard| (hazard.kind(), hazard.token(), hazard.span()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (HazardKind::CommandSeparator, 1, 7..8),
+                (HazardKind::Pipe, 2, 13..14),
+                (HazardKind::CommandSubstitution, 3, 16..17),
+                (HazardKind::CommandSubstitution, 3, 19..20),
+                (HazardKind::CommandSubstitution, 4, 22..24),
+                (HazardKind::Redirect, 6, 35..36),
+            ]
+        );
+        assert!(audit_command("ls -la 'My Files'").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! Parsing of interactive input with existing [`clap`] definitions.
+
+use crate::{ParseError, Parser};
+use std::error::Error;
+use std::fmt;
+
+/// Where the program name, which clap expects as the first argument, comes from. See
+/// [`Parser::try_parse_clap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgramName<'a> {
+    /// The name of the clap command is inserted before the parsed tokens. This suits REPLs, whose
+    /// input names a subcommand or arguments, but not the program itself.
+    CommandName,
+    /// The given name is inserted before the parsed tokens.
+    Custom(&'a str),
+    /// The first token of the input is the program name, as in a full command line.
+    FirstToken,
+}
+
+impl Default for ProgramName<'_> {
+    fn default() -> Self {
+        ProgramName::CommandName
+    }
+}
+
+/// An error parsing input with clap: either the input was malformed, or clap rejected its
+/// tokens.
+#[derive(Debug)]
+pub enum ClapError {
+    /// The input was not a well-formed command.
+    Parse(ParseError),
+    /// Clap rejected the tokens, or was asked for help or a version. Its message, ready to show
+    /// to the user, is the error's [`Display`](fmt::Display) output.
+    Clap(clap::Error),
+}
+
+impl fmt::Display for ClapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClapError::Parse(error) => error.fmt(f),
+            ClapError::Clap(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for ClapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClapError::Parse(error) => Some(error),
+            ClapError::Clap(error) => Some(error),
+        }
+    }
+}
+
+impl From<ParseError> for ClapError {
+    fn from(error: ParseError) -> Self {
+        ClapError::Parse(error)
+    }
+}
+
+impl From<clap::Error> for ClapError {
+    fn from(error: clap::Error) -> Self {
+        ClapError::Clap(error)
+    }
+}
+
+/// The tokens of `input`, preceded by the program name unless the input supplies it.
+/// `command_name` is only called for [`ProgramName::CommandName`].
+fn args(
+    parser: &Parser,
+    input: &str,
+    program: ProgramName,
+    command_name: impl FnOnce() -> String,
+) -> Result<Vec<String>, ParseError> {
+    let tokens = parser.try_parse(input)?;
+    let name = match program {
+        ProgramName::CommandName => Some(command_name()),
+        ProgramName::Custom(name) => Some(name.to_string()),
+        ProgramName::FirstToken => None,
+    };
+    Ok(name.into_iter().chain(tokens).collect())
+}
+
+pub(crate) fn parse<T: clap::Parser>(
+    parser: &Parser,
+    input: &str,
+    program: ProgramName,
+) -> Result<T, ClapError> {
+    let args = args(parser, input, program, || {
+        T::command().get_name().to_string()
+    })?;
+    Ok(T::try_parse_from(args)?)
+}
+
+pub(crate) fn matches(
+    parser: &Parser,
+    command: clap::Command,
+    input: &str,
+    program: ProgramName,
+) -> Result<clap::ArgMatches, ClapError> {
+    let args = args(parser, input, program, || command.get_name().to_string())?;
+    Ok(command.try_get_matches_from(args)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClapError, ProgramName};
+    use crate::Parser;
+    use clap::{Arg, Command};
+
+    #[test]
+    fn feeds_clap() {
+        let command = Command::new("repl")
+            .subcommand(Command::new("open").arg(Arg::new("path").required(true)))
+            .subcommand(Command::new("quit"));
+        let parser = Parser::new();
+        let matches = |input, program| parser.clap_matches(command.clone(), input, program);
+
+        let open = matches("open 'My File.txt'", ProgramName::CommandName).unwrap();
+        let (name, args) = open.subcommand().unwrap();
+        assert_eq!(name, "open");
+        assert_eq!(args.get_one::<String>("path").unwrap(), "My File.txt");
+
+        let quit = matches("shell quit", ProgramName::FirstToken).unwrap();
+        assert_eq!(quit.subcommand_name(), Some("quit"));
+        assert!(matches("quit", ProgramName::Custom("x")).is_ok());
+
+        assert!(matches!(
+            matches("open", ProgramName::default()),
+            Err(ClapError::Clap(_))
+        ));
+        assert!(matches!(
+            matches("open 'a", ProgramName::default()),
+            Err(ClapError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,258 @@
+//! A parsed command as a value type.
+
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::ErrorKind;
+use crate::{quote, ParseError, Parser};
+use core::convert::TryFrom;
+use core::fmt;
+use core::slice;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::process;
+
+/// The tokens of a command, parsed by a default [`Parser`]. Blank input produces a command with
+/// no tokens.
+///
+/// ```
+/// use comma::Command;
+///
+/// let command: Command = "grep -i 'hello world' notes.txt".parse()?;
+/// assert_eq!(command.tokens(), ["grep", "-i", "hello world", "notes.txt"]);
+/// assert!("grep 'unterminated".parse::<Command>().is_err());
+/// # Ok::<(), comma::ParseError>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Command {
+    tokens: Vec<String>,
+}
+
+impl Command {
+    /// The command's tokens.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// Consumes the command, returning its tokens.
+    pub fn into_tokens(self) -> Vec<String> {
+        self.tokens
+    }
+
+    /// The program to run, which is the first token, or None if the command is empty.
+    ///
+    /// ```
+    /// use comma::Command;
+    ///
+    /// let command: Command = "cargo build --release".parse()?;
+    /// assert_eq!(command.program(), Some("cargo"));
+    /// assert_eq!(command.args(), ["build", "--release"]);
+    /// assert_eq!(command.get(2), Some("--release"));
+    ///
+    /// let empty: Command = "".parse()?;
+    /// assert_eq!((empty.program(), empty.args().len()), (None, 0));
+    /// # Ok::<(), comma::ParseError>(())
+    /// ```
+    pub fn program(&self) -> Option<&str> {
+        self.get(0)
+    }
+
+    /// The arguments to the program: every token but the first. Empty if the command is.
+    pub fn args(&self) -> &[String] {
+        self.tokens.get(1..).unwrap_or_default()
+    }
+
+    /// The token at `index`, where the program is at index 0.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.tokens.get(index).map(String::as_str)
+    }
+
+    /// The number of tokens, including the program.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Whether the command has no tokens at all.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// An iterator over the tokens, beginning with the program.
+    pub fn iter(&self) -> slice::Iter<'_, String> {
+        self.tokens.iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Command {
+    /// Builds a [`std::process::Command`] that runs the program with the command's arguments.
+    /// Fails with [`ErrorKind::EmptyCommand`] if the command has no tokens.
+    ///
+    /// ```no_run
+    /// use comma::Command;
+    ///
+    /// let command: Command = "git log --oneline -n 5".parse()?;
+    /// let output = command.to_process_command()?.output()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_process_command(&self) -> Result<process::Command, ParseError> {
+        let program = self
+            .program()
+            .ok_or_else(|| ParseError::new(ErrorKind::EmptyCommand, 0..0))?;
+        let mut command = process::Command::new(program);
+        command.args(self.args());
+        Ok(command)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&Command> for process::Command {
+    type Error = ParseError;
+
+    fn try_from(command: &Command) -> Result<Self, Self::Error> {
+        command.to_process_command()
+    }
+}
+
+/// Adds arguments written as a command string to a [`std::process::Command`].
+#[cfg(feature = "std")]
+pub trait CommandArgsExt {
+    /// Parses `input` with a default [`Parser`], and appends every token it contains as an
+    /// argument. Blank input appends nothing. Fails, appending nothing, if `input` is malformed.
+    ///
+    /// ```
+    /// use comma::CommandArgsExt;
+    /// use std::process::Command;
+    ///
+    /// let flags = "-C opt-level=3 --cfg 'feature=\"fast\"'";
+    /// let mut rustc = Command::new("rustc");
+    /// rustc.arg("main.rs").args_parsed(flags)?.arg("-v");
+    ///
+    /// let args: Vec<_> = rustc.get_args().collect();
+    /// assert_eq!(args, ["main.rs", "-C", "opt-level=3", "--cfg", "feature=\"fast\"", "-v"]);
+    /// # Ok::<(), comma::ParseError>(())
+    /// ```
+    fn args_parsed(&mut self, input: &str) -> Result<&mut Self, ParseError>;
+}
+
+#[cfg(feature = "std")]
+impl CommandArgsExt for process::Command {
+    fn args_parsed(&mut self, input: &str) -> Result<&mut Self, ParseError> {
+        let tokens = Parser::new().try_parse(input)?;
+        Ok(self.args(tokens))
+    }
+}
+
+impl fmt::Display for Command {
+    /// Writes the tokens separated by spaces, each quoted by [`quote`] if necessary, so that the
+    /// result parses back to the same command.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, token) in self.tokens.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(&quote(token))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'c> IntoIterator for &'c Command {
+    type Item = &'c String;
+    type IntoIter = slice::Iter<'c, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Command {
+    type Item = String;
+    type IntoIter = alloc::vec::IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.into_iter()
+    }
+}
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let tokens = Parser::new().try_parse(input)?;
+        Ok(Self { tokens })
+    }
+}
+
+impl TryFrom<&str> for Command {
+    type Error = ParseError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        input.parse()
+    }
+}
+
+impl From<Vec<String>> for Command {
+    fn from(tokens: Vec<String>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl From<Command> for Vec<String> {
+    fn from(command: Command) -> Self {
+        command.tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, ErrorKind};
+    use core::convert::TryFrom;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Command::try_from("  ").unwrap(), Command::default());
+        let command = Command::try_from("a\\ b c").unwrap();
+        assert_eq!(Vec::from(command), vec!["a b", "c"]);
+        let error = Command::try_from("a \"b").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+    }
+
+    #[test]
+    fn accessors() {
+        let command = Command::try_from("say 'it''s' \"a b\"").unwrap();
+        assert_eq!(command.len(), 3);
+        assert_eq!(command.get(3), None);
+        assert_eq!(command.to_string(), "say its 'a b'");
+        let parsed: Vec<&String> = command.iter().collect();
+        assert_eq!(command.to_string().parse::<Command>().unwrap(), command);
+        assert_eq!(parsed, command.tokens().iter().collect::<Vec<_>>());
+
+        let single = Command::from(vec![String::from("ls")]);
+        assert!(single.args().is_empty() && !single.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn process_command() {
+        use crate::CommandArgsExt;
+
+        let command = Command::try_from("echo 'a b'").unwrap();
+        let process = std::process::Command::try_from(&command).unwrap();
+        assert_eq!(process.get_program(), "echo");
+        assert_eq!(process.get_args().collect::<Vec<_>>(), vec!["a b"]);
+
+        let error = Command::default().to_process_command().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::EmptyCommand);
+
+        let mut process = std::process::Command::new("x");
+        assert!(process.args_parsed("a 'b").is_err());
+        process
+            .args_parsed(" ")
+            .unwrap()
+            .args_parsed("c\\ d")
+            .unwrap();
+        assert_eq!(process.get_args().collect::<Vec<_>>(), vec!["c d"]);
+    }
+}
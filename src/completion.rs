@@ -0,0 +1,138 @@
+//! Support for tab completion of partially typed commands.
+
+use crate::parser::Lexer;
+use crate::position::char_boundary;
+use crate::prelude::*;
+use crate::{ErrorKind, Parser, Token};
+use core::ops::Range;
+
+/// What lies at a cursor within a command. See [`Parser::cursor`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    /// The index of the token containing the cursor. Should the cursor not touch any token, this
+    /// is the index that a token typed at the cursor would take.
+    pub token: usize,
+    /// The byte range of the token containing the cursor, or an empty range at the cursor if it
+    /// does not touch any token.
+    pub span: Range<usize>,
+    /// The quotation mark left open before the cursor, if the cursor is within quotes.
+    pub quote: Option<char>,
+    /// The text of the token preceding the cursor, with quotes and escapes resolved. This is the
+    /// partial word to be completed.
+    pub prefix: String,
+}
+
+impl Cursor {
+    /// Escapes `candidate`, a completion for the token at the cursor, so that inserting it at the
+    /// cursor yields exactly its text. Within quotes, only the open quotation mark and backslashes
+    /// are escaped; otherwise, whitespace and quotation marks are escaped too. This assumes the
+    /// default [`Escapes::Anywhere`](crate::Escapes::Anywhere).
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let bare = parser.cursor("cat My", 6);
+    /// assert_eq!(bare.escape("My Files/it's"), r"My\ Files/it\'s");
+    ///
+    /// let quoted = parser.cursor("cat 'My", 7);
+    /// assert_eq!(quoted.escape("My Files/it's"), r"My Files/it\'s");
+    /// ```
+    pub fn escape(&self, candidate: &str) -> String {
+        let mut escaped = String::with_capacity(candidate.len());
+        for ch in candidate.chars() {
+            let special = match self.quote {
+                Some(quote) => ch == quote || ch == '\\',
+                None => matches!(ch, '\\' | '"' | '\'') || ch.is_whitespace(),
+            };
+            if special {
+                escaped.push('\\');
+            }
+            escaped.push(ch);
+        }
+        escaped
+    }
+}
+
+pub(crate) fn locate(parser: &Parser, input: &str, offset: usize) -> Cursor {
+    let offset = char_boundary(input, offset);
+    let before = &input[..offset];
+
+    let mut lexer = Lexer::new(parser, before).with_recovery();
+    let mut tokens: Vec<Token> = lexer.by_ref().filter_map(Result::ok).collect();
+    let quote = lexer
+        .errors()
+        .into_iter()
+        .find_map(|error| match error.kind() {
+            ErrorKind::UnterminatedQuote(quote) => Some(quote),
+            _ => None,
+        });
+
+    // The token ending at the cursor is the one being typed; otherwise a new one begins there.
+    let (index, start, prefix) = match tokens.pop() {
+        Some(token) if token.span.end == offset => (tokens.len(), token.span.start, token.value),
+        Some(_) => (tokens.len() + 1, offset, String::new()),
+        None => (0, offset, String::new()),
+    };
+
+    // The rest of the token may follow the cursor.
+    let end = Lexer::new(parser, input)
+        .with_recovery()
+        .filter_map(Result::ok)
+        .find(|token| token.span.start == start)
+        .map_or(offset, |token| token.span.end.max(offset));
+
+    Cursor {
+        token: index,
+        span: start..end,
+        quote,
+        prefix,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cursor, Parser};
+
+    #[test]
+    fn cursor_positions() {
+        let parser = Parser::new();
+        let input = "cp 'My Doc' b";
+        let cursor = |offset| parser.cursor(input, offset);
+
+        assert_eq!(
+            cursor(0),
+            Cursor {
+                token: 0,
+                span: 0..2,
+                quote: None,
+                prefix: String::new()
+            }
+        );
+        assert_eq!(cursor(2).prefix, "cp");
+        assert_eq!(cursor(2).span, 0..2);
+
+        let inside = cursor(6);
+        assert_eq!((inside.token, inside.span), (1, 3..11));
+        assert_eq!((inside.quote, inside.prefix.as_str()), (Some('\''), "My"));
+
+        assert_eq!(cursor(11).quote, None);
+        assert_eq!(cursor(11).prefix, "My Doc");
+        assert_eq!(cursor(12).token, 2);
+        assert_eq!(cursor(12).span, 12..13);
+        assert_eq!(cursor(99).prefix, "b");
+
+        let cursor = parser.cursor("ls  ", 3);
+        assert_eq!((cursor.token, cursor.span), (1, 3..3));
+    }
+
+    #[test]
+    fn escape_candidates() {
+        let parser = Parser::new();
+        let candidate = "a \"b\"\\c'\td";
+        let input = format!("x \"{}\"", parser.cursor("x \"", 3).escape(candidate));
+        assert_eq!(parser.parse(&input).unwrap()[1], candidate);
+        let input = format!("x {}", parser.cursor("x ", 2).escape(candidate));
+        assert_eq!(parser.parse(&input).unwrap()[1], candidate);
+    }
+}
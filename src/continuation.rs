@@ -0,0 +1,177 @@
+//! Detection of input that an interactive shell should continue on another line.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::{ErrorKind, Parser, TrailingBackslash};
+
+/// Whether input forms a whole command, or more lines must be read. See [`Parser::classify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Completeness {
+    /// The input can be parsed as it is, although it may still be malformed.
+    Complete,
+    /// The input is unfinished, for the given reason.
+    NeedsMore(Continuation),
+}
+
+/// Why input is unfinished. See [`Completeness::NeedsMore`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Continuation {
+    /// The given quotation mark was opened but never closed.
+    Quote(char),
+    /// The input ends in a backslash under [`TrailingBackslash::Continue`].
+    TrailingBackslash,
+    /// A heredoc, introduced by `<<` or `<<-`, has not yet been ended by its delimiter, given
+    /// here.
+    Heredoc(String),
+}
+
+impl Completeness {
+    /// Whether the input is complete.
+    pub fn is_complete(&self) -> bool {
+        *self == Completeness::Complete
+    }
+}
+
+/// A heredoc whose body follows the line of its command.
+struct Heredoc {
+    delimiter: String,
+    /// Whether leading tabs are stripped from its lines, as written `<<-`.
+    strip_tabs: bool,
+}
+
+pub(crate) fn classify(parser: &Parser, input: &str) -> Completeness {
+    let mut start = 0;
+    loop {
+        let mut heredocs = Vec::new();
+        let mut strip_tabs = None;
+        let mut line_end = None;
+
+        for token in Lexer::starting_at(parser, input, start) {
+            let token = match token {
+                Ok(token) => token,
+                Err(error) if line_end.map_or(false, |end| error.span().start > end) => break,
+                Err(error) => {
+                    return match error.kind() {
+                        ErrorKind::UnterminatedQuote(quote) => {
+                            Completeness::NeedsMore(Continuation::Quote(quote))
+                        }
+                        ErrorKind::TrailingBackslash
+                            if parser.trailing_backslash == TrailingBackslash::Continue =>
+                        {
+                            Completeness::NeedsMore(Continuation::TrailingBackslash)
+                        }
+                        _ => Completeness::Complete,
+                    };
+                }
+            };
+            if line_end.map_or(false, |end| token.span.start > end) {
+                break;
+            }
+
+            let end = token.span.end;
+            let text = token.as_str();
+            if let Some(strip_tabs) = strip_tabs.take() {
+                heredocs.push(Heredoc {
+                    delimiter: token.into_string(),
+                    strip_tabs,
+                });
+            } else if !token.plain || !text.starts_with("<<") || text.starts_with("<<<") {
+                continue;
+            } else if text == "<<" || text == "<<-" {
+                strip_tabs = Some(text == "<<-");
+                continue;
+            } else {
+                let delimiter = text.strip_prefix("<<-").unwrap_or(&text[2..]);
+                heredocs.push(Heredoc {
+                    delimiter: delimiter.to_string(),
+                    strip_tabs: text.starts_with("<<-"),
+                });
+            }
+
+            // The bodies of heredocs begin on the line after the command that introduces them.
+            if line_end.is_none() {
+                let newline = input[end..].find('\n');
+                line_end = Some(newline.map_or(input.len(), |newline| end + newline));
+            }
+        }
+
+        let line_end = match line_end {
+            Some(line_end) => line_end,
+            None => return Completeness::Complete,
+        };
+        start = line_end + 1;
+        for heredoc in heredocs {
+            start = match body_end(input, start, &heredoc) {
+                Some(end) => end,
+                None => return Completeness::NeedsMore(Continuation::Heredoc(heredoc.delimiter)),
+            };
+        }
+        if start >= input.len() {
+            return Completeness::Complete;
+        }
+    }
+}
+
+/// The offset just past the line ending `heredoc`, whose body starts at `start`, if there is one.
+fn body_end(input: &str, start: usize, heredoc: &Heredoc) -> Option<usize> {
+    let mut offset = start;
+    for line in input.get(start..)?.split_inclusive('\n') {
+        offset += line.len();
+        let mut line = line.trim_end_matches('\n').trim_end_matches('\r');
+        if heredoc.strip_tabs {
+            line = line.trim_start_matches('\t');
+        }
+        if line == heredoc.delimiter {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Completeness, Continuation};
+    use crate::{Parser, TrailingBackslash};
+
+    #[test]
+    fn classifies_input() {
+        let parser = Parser::new();
+        let more = Completeness::NeedsMore;
+
+        assert_eq!(parser.classify("echo 'a b'"), Completeness::Complete);
+        assert_eq!(
+            parser.classify("echo 'a\nb"),
+            more(Continuation::Quote('\''))
+        );
+        assert_eq!(parser.classify("echo \"a"), more(Continuation::Quote('"')));
+        assert_eq!(parser.classify("echo a\\"), Completeness::Complete);
+        let continued = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+        assert_eq!(
+            continued.classify("echo a\\"),
+            more(Continuation::TrailingBackslash)
+        );
+    }
+
+    #[test]
+    fn classifies_heredocs() {
+        let parser = Parser::new();
+        let heredoc =
+            |delimiter: &str| Completeness::NeedsMore(Continuation::Heredoc(delimiter.to_string()));
+
+        assert_eq!(parser.classify("cat <<EOF"), heredoc("EOF"));
+        assert_eq!(parser.classify("cat << 'END'\nit's\n"), heredoc("END"));
+        assert_eq!(
+            parser.classify("cat <<END\nit's\nEND"),
+            Completeness::Complete
+        );
+        assert_eq!(parser.classify("cat <<-X\n\tX\n"), Completeness::Complete);
+        assert_eq!(parser.classify("a <<A <<B\nA\n"), heredoc("B"));
+        assert_eq!(
+            parser.classify("a <<A <<B\nA\nB\necho 'x"),
+            Completeness::NeedsMore(Continuation::Quote('\''))
+        );
+        assert_eq!(parser.classify("a <<A <<B\nA\nB\ncat <<C\n"), heredoc("C"));
+        assert_eq!(parser.classify("cat '<<EOF' <<<x"), Completeness::Complete);
+    }
+}
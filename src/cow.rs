@@ -0,0 +1,168 @@
+//! Parsing into tokens that borrow from the input wherever they can.
+//!
+//! The lexer runs without building token text to find each token. A token holding no quotes or
+//! escapes is then the very text it was read from, so it is borrowed; only the others are lexed
+//! once more to build their values.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::{ParseError, Parser};
+use alloc::borrow::Cow;
+use core::ops::Range;
+
+/// A token as written in the input, before its quotes and escapes are resolved. See
+/// [`Parser::raw_tokens`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawToken<'a> {
+    text: &'a str,
+    span: Range<usize>,
+    escaped: bool,
+}
+
+impl<'a> RawToken<'a> {
+    /// The text of the input the token was read from, including any quotes and escapes.
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// The half-open byte range of the input from which the token was read.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Whether the token's value differs from its [`text`](RawToken::text), so that it must be
+    /// [unescaped](RawToken::unescape) before use. Otherwise, the text is the value.
+    pub fn needs_unescape(&self) -> bool {
+        self.escaped
+    }
+
+    /// The token's value, with its quotes and escapes resolved by `parser`, which should be the
+    /// parser that read it. A token that needs no unescaping is borrowed from the input, and
+    /// text that `parser` cannot read as one token is returned unchanged.
+    pub fn unescape(&self, parser: &Parser) -> Cow<'a, str> {
+        if !self.escaped {
+            return Cow::Borrowed(self.text);
+        }
+        match Lexer::new(parser, self.text)
+            .with_options_ended(true)
+            .next()
+        {
+            Some(Ok(token)) => Cow::Owned(token.into_string()),
+            _ => Cow::Borrowed(self.text),
+        }
+    }
+}
+
+/// An iterator over the [`RawToken`]s of a command, read as they are requested. See
+/// [`Parser::raw_tokens`].
+pub struct RawTokens<'p, 'a> {
+    parser: &'p Parser,
+    input: &'a str,
+    lexer: Lexer<'p, 'a>,
+}
+
+impl<'p, 'a> RawTokens<'p, 'a> {
+    pub(crate) fn new(parser: &'p Parser, input: &'a str) -> Self {
+        Self {
+            parser,
+            input,
+            lexer: Lexer::new(parser, input).with_discard(),
+        }
+    }
+}
+
+impl<'a> Iterator for RawTokens<'_, 'a> {
+    type Item = Result<RawToken<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = match self.lexer.next()? {
+            Ok(token) => token,
+            Err(error) => return Some(Err(error)),
+        };
+        let escaped = !token.plain && !self.parser.keep_quotes;
+        Some(Ok(RawToken {
+            text: &self.input[token.span.clone()],
+            span: token.span,
+            escaped: escaped || self.parser.rewrites_plain_tokens(),
+        }))
+    }
+}
+
+/// Parses `input`, passing each token to `each` as it is read, borrowed from the input where
+/// possible.
+pub(crate) fn parse<'a, F>(parser: &Parser, input: &'a str, mut each: F) -> Result<(), ParseError>
+where
+    F: FnMut(Cow<'a, str>),
+{
+    if parser.rewrites_plain_tokens() || parser.split_long_options {
+        for token in Lexer::new(parser, input) {
+            each(Cow::Owned(token?.into_string()));
+        }
+        return Ok(());
+    }
+
+    for token in Lexer::new(parser, input).with_discard() {
+        let token = token?;
+        if token.plain || parser.keep_quotes {
+            each(Cow::Borrowed(&input[token.span]));
+            continue;
+        }
+        let value = match Lexer::starting_at(parser, input, token.span.start).next() {
+            Some(token) => token?.into_string(),
+            None => String::new(),
+        };
+        each(Cow::Owned(value));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+    use alloc::borrow::Cow;
+
+    #[test]
+    fn borrows_plain_tokens() {
+        let parser = Parser::new().comment("#");
+        let input = "cp -r 'My Files' a\\ b dest/ # copy";
+        let tokens = parser.try_parse_cow(input).unwrap();
+        assert_eq!(tokens, parser.try_parse(input).unwrap());
+        let borrowed: Vec<bool> = tokens
+            .iter()
+            .map(|token| matches!(token, Cow::Borrowed(_)))
+            .collect();
+        assert_eq!(borrowed, [true, true, false, false, true]);
+
+        let parser = Parser::new().split_long_options(true);
+        let tokens = parser.try_parse_cow("--to=all").unwrap();
+        assert_eq!(tokens, ["--to", "all"]);
+    }
+
+    #[test]
+    fn collects_string_types() {
+        let parser = Parser::new();
+        let tokens: Vec<Box<str>> = parser.try_parse_as("mv 'a b' c\\ d").unwrap();
+        assert_eq!(tokens, ["mv".into(), "a b".into(), "c d".into()]);
+        let error = parser.try_parse_as::<String>("mv 'a").unwrap_err();
+        assert_eq!(error.offset(), 3);
+    }
+
+    #[test]
+    fn reads_raw_tokens() {
+        let parser = Parser::new().split_long_options(true);
+        let input = "say --to='a b' \\\"hi\\\" there";
+        let tokens: Vec<_> = parser.raw_tokens(input).map(Result::unwrap).collect();
+        let texts: Vec<&str> = tokens.iter().map(|token| token.text()).collect();
+        assert_eq!(texts, ["say", "--to='a b'", "\\\"hi\\\"", "there"]);
+        assert!(tokens[1].needs_unescape() && !tokens[3].needs_unescape());
+        assert_eq!(tokens[1].unescape(&parser), "--to=a b");
+        assert_eq!(tokens[2].unescape(&parser), "\"hi\"");
+        assert_eq!(tokens[2].span(), 15..21);
+
+        let parser = Parser::new().percent_decode(true);
+        let token = parser.raw_tokens("a%20b").next().unwrap().unwrap();
+        assert!(token.needs_unescape());
+        assert_eq!(token.unescape(&parser), "a b");
+        assert!(parser.raw_tokens("'a").next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,307 @@
+//! Lossless syntax trees, which preserve every byte of their input.
+//!
+//! A [`SyntaxTree`] records how each token was written, along with the whitespace and comments
+//! between tokens, so that formatters and refactoring tools can inspect a command and write it
+//! back out exactly. Its [`Display`](std::fmt::Display) implementation reproduces the input.
+//!
+//! ```
+//! use comma::cst::Node;
+//! use comma::Parser;
+//!
+//! let input = "echo  'hello world'\\! # greet";
+//! let tree = Parser::new().comment("#").parse_cst(input);
+//! assert_eq!(tree.to_string(), input);
+//!
+//! let words: Vec<&str> = tree.words().map(|word| word.value.as_str()).collect();
+//! assert_eq!(words, vec!["echo", "hello world!"]);
+//! assert_eq!(tree.nodes[1], Node::Whitespace(String::from("  ")));
+//! assert_eq!(tree.nodes[4], Node::Comment(String::from("# greet")));
+//! ```
+
+use crate::highlight::{paint, runs};
+use crate::prelude::*;
+use crate::{quote, Highlight, Parser};
+use core::fmt;
+
+/// A lossless syntax tree of a command. See the [module documentation](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyntaxTree {
+    /// The nodes making up the command, in the order they were written.
+    pub nodes: Vec<Node>,
+}
+
+impl SyntaxTree {
+    /// The words of the command, in order, skipping whitespace and comments.
+    pub fn words(&self) -> impl Iterator<Item = &Word> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Word(word) => Some(word),
+            _ => None,
+        })
+    }
+
+    /// The index within [`SyntaxTree::nodes`] of each word.
+    fn word_nodes(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|&at| matches!(self.nodes[at], Node::Word(_)))
+            .collect()
+    }
+
+    /// Replaces the word at `index` with a word for `value`, quoted if necessary by
+    /// [`quote`](crate::quote), returning the word replaced. Returns None, leaving the tree
+    /// unchanged, if there is no word at `index`.
+    ///
+    /// ```
+    /// use comma::parse_cst;
+    ///
+    /// let mut tree = parse_cst("cp  \"a.txt\"   b.txt");
+    /// tree.replace_word(2, "my file.txt");
+    /// assert_eq!(tree.to_string(), "cp  \"a.txt\"   'my file.txt'");
+    /// ```
+    pub fn replace_word(&mut self, index: usize, value: &str) -> Option<Word> {
+        let at = *self.word_nodes().get(index)?;
+        let node = core::mem::replace(&mut self.nodes[at], Node::Word(word(value)));
+        match node {
+            Node::Word(replaced) => Some(replaced),
+            _ => None,
+        }
+    }
+
+    /// Inserts a word for `value`, quoted if necessary by [`quote`](crate::quote), so that it
+    /// becomes the word at `index`. A single space separates it from its neighbours. Returns
+    /// false, leaving the tree unchanged, if `index` is greater than the number of words.
+    ///
+    /// ```
+    /// use comma::parse_cst;
+    ///
+    /// let mut tree = parse_cst("git  commit -m 'wip'");
+    /// tree.insert_word(2, "--amend");
+    /// tree.insert_word(5, "--no-edit");
+    /// assert_eq!(tree.to_string(), "git  commit --amend -m 'wip' --no-edit");
+    /// ```
+    pub fn insert_word(&mut self, index: usize, value: &str) -> bool {
+        let words = self.word_nodes();
+        let space = || Node::Whitespace(String::from(" "));
+        let new = Node::Word(word(value));
+
+        match (words.get(index), words.last()) {
+            (Some(&at), _) => {
+                self.nodes.splice(at..at, vec![new, space()]);
+            }
+            (None, Some(&last)) if index == words.len() => {
+                self.nodes.splice(last + 1..last + 1, vec![space(), new]);
+            }
+            (None, None) if index == 0 => {
+                let spaced = matches!(self.nodes.first(), Some(node) if !is_whitespace(node));
+                self.nodes.insert(0, new);
+                if spaced {
+                    self.nodes.insert(1, space());
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Removes the word at `index`, along with the whitespace separating it from the next word,
+    /// or from the previous word if it is the last of several. Returns the word removed, or None,
+    /// leaving the tree unchanged, if there is no word at `index`.
+    ///
+    /// ```
+    /// use comma::parse_cst;
+    ///
+    /// let mut tree = parse_cst("rm  -rf 'build dir'");
+    /// tree.remove_word(1);
+    /// assert_eq!(tree.to_string(), "rm  'build dir'");
+    /// ```
+    pub fn remove_word(&mut self, index: usize) -> Option<Word> {
+        let at = *self.word_nodes().get(index)?;
+        let removed = self.nodes.remove(at);
+
+        // Indentation before the first word is kept, as is anything after the last.
+        let is_word = |node: &Node| matches!(node, Node::Word(_));
+        let last = !self.nodes[at..].iter().any(is_word);
+        if last && self.nodes[..at].iter().any(is_word) {
+            if is_whitespace(&self.nodes[at - 1]) {
+                self.nodes.remove(at - 1);
+            }
+        } else if self.nodes.get(at).map_or(false, is_whitespace) {
+            self.nodes.remove(at);
+        }
+
+        match removed {
+            Node::Word(removed) => Some(removed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.nodes.iter().try_for_each(|node| node.fmt(f))
+    }
+}
+
+/// A node of a [`SyntaxTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// A run of whitespace, or of any other text outside of a word.
+    Whitespace(String),
+    /// A comment, including its marker.
+    Comment(String),
+    /// A word, which parses to a single token.
+    Word(Word),
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Whitespace(text) | Node::Comment(text) => f.write_str(text),
+            Node::Word(word) => word.fmt(f),
+        }
+    }
+}
+
+/// A word of a [`SyntaxTree`], and how it was written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Word {
+    /// The token the word parses to, with quotes and escapes resolved.
+    pub value: String,
+    /// The pieces the word was written as, in order. Their text joins to form the word exactly as
+    /// written.
+    pub pieces: Vec<Piece>,
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.pieces
+            .iter()
+            .try_for_each(|piece| f.write_str(&piece.text))
+    }
+}
+
+/// A piece of a [`Word`]: a quotation mark, some quoted or unquoted text, or an escape sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Piece {
+    /// The role of the piece. Pieces of a word are never [`Highlight::Whitespace`] or
+    /// [`Highlight::Comment`].
+    pub role: Highlight,
+    /// The piece's text, exactly as written.
+    pub text: String,
+}
+
+fn is_whitespace(node: &Node) -> bool {
+    matches!(node, Node::Whitespace(_))
+}
+
+/// A word for `value`, written as [`quote`] writes it.
+fn word(value: &str) -> Word {
+    let tree = parse(&Parser::new(), &quote(value));
+    match tree.nodes.into_iter().next() {
+        Some(Node::Word(word)) => word,
+        _ => Word {
+            value: value.to_string(),
+            pieces: Vec::new(),
+        },
+    }
+}
+
+pub(crate) fn parse(parser: &Parser, input: &str) -> SyntaxTree {
+    let (tokens, roles) = paint(parser, input);
+    let mut nodes = Vec::new();
+    let mut copied = 0;
+
+    for token in tokens {
+        if copied < token.span.start {
+            nodes.push(Node::Whitespace(
+                input[copied..token.span.start].to_string(),
+            ));
+        }
+        copied = token.span.end;
+
+        if token.comment {
+            nodes.push(Node::Comment(token.value));
+            continue;
+        }
+        let pieces = runs(&roles, token.span.clone())
+            .into_iter()
+            .map(|(span, role)| Piece {
+                role,
+                text: input[span].to_string(),
+            })
+            .collect();
+        nodes.push(Node::Word(Word {
+            value: token.value,
+            pieces,
+        }));
+    }
+
+    if copied < input.len() {
+        nodes.push(Node::Whitespace(input[copied..].to_string()));
+    }
+    SyntaxTree { nodes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Node, Piece};
+    use crate::{parse_cst, Highlight, Parser};
+
+    #[test]
+    fn round_trips() {
+        let parser = Parser::new().comment("#");
+        for input in [
+            "",
+            " a\\ b \"c\\\"d\" | é # note\n'x",
+            "a\\",
+            "\u{7}x  \r\n# c",
+        ] {
+            assert_eq!(parser.parse_cst(input).to_string(), input);
+        }
+    }
+
+    #[test]
+    fn edits() {
+        let parser = Parser::new().comment("#");
+        let mut tree = parser.parse_cst("  a\tb # c");
+        assert!(tree.remove_word(2).is_none() && !tree.insert_word(3, "x"));
+        assert_eq!(tree.remove_word(1).unwrap().value, "b");
+        assert_eq!(tree.to_string(), "  a # c");
+        tree.insert_word(1, "it's");
+        assert_eq!(tree.to_string(), "  a \"it's\" # c");
+        tree.remove_word(0);
+        tree.remove_word(0);
+        assert_eq!(tree.to_string(), "  # c");
+        tree.insert_word(0, "");
+        assert_eq!(tree.to_string(), "''  # c");
+
+        let mut tree = parser.parse_cst("# c");
+        tree.insert_word(0, "x");
+        assert_eq!(tree.to_string(), "x # c");
+        assert_eq!(tree.words().next().unwrap().value, "x");
+    }
+
+    #[test]
+    fn word_pieces() {
+        let tree = parse_cst("a'b'\\ ");
+        let piece = |role, text: &str| Piece {
+            role,
+            text: text.to_string(),
+        };
+        match &tree.nodes[..] {
+            [Node::Word(word)] => {
+                assert_eq!(word.value, "ab ");
+                assert_eq!(
+                    word.pieces,
+                    vec![
+                        piece(Highlight::Word, "a"),
+                        piece(Highlight::QuoteDelimiter, "'"),
+                        piece(Highlight::Quoted, "b"),
+                        piece(Highlight::QuoteDelimiter, "'"),
+                        piece(Highlight::Escape, "\\ "),
+                    ]
+                );
+            }
+            nodes => panic!("unexpected nodes {:?}", nodes),
+        }
+    }
+}
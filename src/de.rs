@@ -0,0 +1,340 @@
+//! Deserialization of command strings into Rust values with [`serde`].
+//!
+//! A struct is read from a command's tokens in two parts: bare tokens fill the struct's fields in
+//! the order they are declared, skipping any field named elsewhere, and `key=value` tokens set the
+//! field named by their key. Quote a bare token containing `=` to keep it positional. Each value
+//! is read from its text: numbers and booleans are parsed, an empty value is `None` for an
+//! `Option`, an enum takes the name of a unit variant, and a sequence is split at commas.
+//!
+//! ```
+//! use serde::Deserialize;
+//!
+//! #[derive(Debug, Deserialize, PartialEq)]
+//! struct Backup {
+//!     source: String,
+//!     target: String,
+//!     retries: u32,
+//!     verbose: bool,
+//!     tags: Vec<String>,
+//!     limit: Option<u64>,
+//! }
+//!
+//! let backup: Backup =
+//!     comma::de::from_str("'My Files' retries=3 /mnt/backup verbose=true tags=daily,offsite limit=")?;
+//! assert_eq!(
+//!     backup,
+//!     Backup {
+//!         source: String::from("My Files"),
+//!         target: String::from("/mnt/backup"),
+//!         retries: 3,
+//!         verbose: true,
+//!         tags: vec![String::from("daily"), String::from("offsite")],
+//!         limit: None,
+//!     }
+//! );
+//! # Ok::<(), comma::de::Error>(())
+//! ```
+//!
+//! Sequences and tuples take one bare token per element, and maps take the `key=value` tokens.
+
+use crate::prelude::*;
+use crate::{ParseError, Parser, Token};
+use core::fmt;
+use core::str::FromStr;
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// Deserializes a `T` from the tokens of a command string. See the [module documentation](self).
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, Error> {
+    let tokens = Parser::new()
+        .try_tokens(input)
+        .collect::<Result<Vec<Token>, ParseError>>()?;
+    T::deserialize(Deserializer { tokens })
+}
+
+/// An error deserializing a command string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The input was not a well-formed command.
+    Parse(ParseError),
+    /// The tokens did not fit the type being deserialized.
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(error) => error.fmt(f),
+            Error::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+impl de::StdError for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Message(message.to_string())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::Parse(error)
+    }
+}
+
+/// Deserializes a whole command from its tokens.
+struct Deserializer {
+    tokens: Vec<Token>,
+}
+
+impl Deserializer {
+    /// Splits the tokens into bare values and `key=value` pairs.
+    fn split(self) -> (Vec<Value>, Vec<(String, Value)>) {
+        let mut bare = Vec::new();
+        let mut pairs = Vec::new();
+        for token in self.tokens {
+            match token.key_value() {
+                Some((key, value)) => pairs.push((key.to_string(), Value(value.to_string()))),
+                None => bare.push(Value(token.into_string())),
+            }
+        }
+        (bare, pairs)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let values = self
+            .tokens
+            .into_iter()
+            .map(|token| Value(token.into_string()));
+        let mut seq = SeqDeserializer::new(values);
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let (bare, pairs) = self.split();
+        if let Some(Value(value)) = bare.into_iter().next() {
+            return Err(Error::Message(format!("unexpected bare token {:?}", value)));
+        }
+        let mut map = MapDeserializer::new(pairs.into_iter());
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (bare, mut pairs) = self.split();
+        let open: Vec<&str> = fields
+            .iter()
+            .copied()
+            .filter(|field| !pairs.iter().any(|(key, _)| key == field))
+            .collect();
+        let mut open = open.into_iter();
+        for Value(value) in bare {
+            match open.next() {
+                Some(field) => pairs.push((field.to_string(), Value(value))),
+                None => return Err(Error::Message(format!("unexpected bare token {:?}", value))),
+            }
+        }
+
+        let mut map = MapDeserializer::new(pairs.into_iter());
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct seq tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+/// Deserializes a single value from its text.
+struct Value(String);
+
+impl Value {
+    fn parse<T: FromStr>(&self, expected: &dyn de::Expected) -> Result<T, Error> {
+        self.0
+            .parse()
+            .map_err(|_| de::Error::invalid_value(Unexpected::Str(&self.0), expected))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let value = self.parse(&visitor)?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let items: Vec<Value> = match self.0.as_str() {
+            "" => Vec::new(),
+            text => text
+                .split(',')
+                .map(|item| Value(item.to_string()))
+                .collect(),
+        };
+        let mut seq = SeqDeserializer::new(items.into_iter());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_str, Error};
+    use crate::ErrorKind;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Job {
+        name: String,
+        level: Level,
+        #[serde(default)]
+        size: (u8, char),
+    }
+
+    #[test]
+    fn structs() {
+        let job: Job = from_str("level=high 'nightly=yes' size=3,x").unwrap();
+        assert_eq!(job.name, "nightly=yes");
+        assert_eq!((job.level, job.size), (Level::High, (3, 'x')));
+
+        let error = from_str::<Job>("a low 1,x b").unwrap_err();
+        assert_eq!(
+            error,
+            Error::Message(String::from("unexpected bare token \"b\""))
+        );
+        assert!(matches!(
+            from_str::<Job>("a level=x"),
+            Err(Error::Message(_))
+        ));
+        assert!(matches!(
+            from_str::<Job>("a low size=1,2,3"),
+            Err(Error::Message(_))
+        ));
+
+        let error = from_str::<Job>("a 'low").unwrap_err();
+        assert!(
+            matches!(error, Error::Parse(error) if error.kind() == ErrorKind::UnterminatedQuote('\''))
+        );
+    }
+
+    #[test]
+    fn collections() {
+        let words: Vec<String> = from_str("a 'b c' d=e").unwrap();
+        assert_eq!(words, vec!["a", "b c", "d=e"]);
+        let pair: (u16, bool) = from_str("8080 false").unwrap();
+        assert_eq!(pair, (8080, false));
+
+        let map: BTreeMap<String, Option<i32>> = from_str("x=-1 y=").unwrap();
+        assert_eq!(map.get("x"), Some(&Some(-1)));
+        assert_eq!(map.get("y"), Some(&None));
+        assert!(from_str::<BTreeMap<String, String>>("k=v bare").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Parsing of the `Exec` key of Freedesktop Desktop Entry files.
+//!
+//! The rules implemented here follow the
+//! [Desktop Entry Specification](https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html):
+//! arguments are separated by spaces, may be enclosed in double quotes, and may contain field
+//! codes such as `%f` or `%U` that are replaced with values supplied by the caller.
+
+use crate::prelude::*;
+use core::iter::Peekable;
+use core::str::Chars;
+
+/// Characters that must be quoted to appear within an argument.
+const RESERVED: &[char] = &[
+    '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')', '`',
+];
+
+/// Characters that must be escaped with a backslash inside a quoted argument.
+const ESCAPABLE: &[char] = &['"', '`', '$', '\\'];
+
+/// Codes that are deprecated by the specification and expand to nothing.
+const DEPRECATED: &[char] = &['d', 'D', 'n', 'N', 'v', 'm'];
+
+/// Values substituted for the field codes of an `Exec` key. See [`parse_exec`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fields {
+    /// Files to open, substituted for `%F`, or whose first entry is substituted for `%f`.
+    pub files: Vec<String>,
+    /// URLs to open, substituted for `%U`, or whose first entry is substituted for `%u`.
+    pub urls: Vec<String>,
+    /// The entry's `Icon` key; `%i` expands to `--icon` followed by this value.
+    pub icon: Option<String>,
+    /// The entry's translated `Name` key, substituted for `%c`.
+    pub name: Option<String>,
+    /// The location of the desktop file, substituted for `%k`.
+    pub location: Option<String>,
+}
+
+/// One piece of an unexpanded argument.
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Text(char),
+    Code(char),
+}
+
+/// Parses the value of an `Exec` key, after the desktop file's own string escapes (such as `\s`
+/// and `\\`) have been decoded, and expands its field codes using `fields`.
+///
+/// Returns None if the value is malformed: if a quote is mismatched, a reserved character appears
+/// unquoted, a quoted argument contains a field code, an unknown field code is used, or a
+/// list code (`%F`, `%U` or `%i`) does not form an argument by itself.
+///
+/// ```
+/// use comma::desktop::{parse_exec, Fields};
+///
+/// let fields = Fields {
+///     files: vec![String::from("a.txt"), String::from("b.txt")],
+///     icon: Some(String::from("editor")),
+///     ..Fields::default()
+/// };
+/// let result = parse_exec("\"/opt/my editor/bin\" %i --new-window %F", &fields).unwrap();
+/// assert_eq!(
+///     result,
+///     vec!["/opt/my editor/bin", "--icon", "editor", "--new-window", "a.txt", "b.txt"]
+/// );
+/// ```
+pub fn parse_exec(exec: &str, fields: &Fields) -> Option<Vec<String>> {
+    let mut chars = exec.chars().peekable();
+    let mut output = Vec::new();
+
+    loop {
+        while chars.next_if_eq(&' ').is_some() {}
+        if chars.peek().is_none() {
+            return Some(output);
+        }
+
+        if chars.next_if_eq(&'"').is_some() {
+            output.push(quoted(&mut chars)?);
+        } else {
+            expand(&unquoted(&mut chars)?, fields, &mut output)?;
+        }
+    }
+}
+
+/// Reads the remainder of a quoted argument, which must be followed by a space or the end of
+/// the value.
+fn quoted(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut arg = String::new();
+
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => arg.push(chars.next().filter(|ch| ESCAPABLE.contains(ch))?),
+            '%' => {
+                chars.next_if_eq(&'%')?;
+                arg.push('%');
+            }
+            ch => arg.push(ch),
+        }
+    }
+
+    match chars.peek() {
+        None | Some(' ') => Some(arg),
+        Some(_) => None,
+    }
+}
+
+fn unquoted(chars: &mut Peekable<Chars>) -> Option<Vec<Piece>> {
+    let mut pieces = Vec::new();
+
+    while let Some(ch) = chars.next_if(|&ch| ch != ' ') {
+        pieces.push(match ch {
+            '%' => match chars.next()? {
+                '%' => Piece::Text('%'),
+                code => Piece::Code(code),
+            },
+            ch if RESERVED.contains(&ch) => return None,
+            ch => Piece::Text(ch),
+        });
+    }
+
+    Some(pieces)
+}
+
+/// Expands the field codes of an unquoted argument, appending the result to `output`.
+fn expand(pieces: &[Piece], fields: &Fields, output: &mut Vec<String>) -> Option<()> {
+    match pieces {
+        [Piece::Code('F')] => output.extend(fields.files.iter().cloned()),
+        [Piece::Code('U')] => output.extend(fields.urls.iter().cloned()),
+        [Piece::Code('i')] => {
+            if let Some(icon) = &fields.icon {
+                output.push(String::from("--icon"));
+                output.push(icon.clone());
+            }
+        }
+        pieces => {
+            let mut arg = String::new();
+            for piece in pieces {
+                match *piece {
+                    Piece::Text(ch) => arg.push(ch),
+                    Piece::Code('f') => arg.extend(fields.files.first().map(String::as_str)),
+                    Piece::Code('u') => arg.extend(fields.urls.first().map(String::as_str)),
+                    Piece::Code('c') => arg.extend(fields.name.as_deref()),
+                    Piece::Code('k') => arg.extend(fields.location.as_deref()),
+                    Piece::Code(code) if DEPRECATED.contains(&code) => {}
+                    Piece::Code(_) => return None,
+                }
+            }
+
+            // An argument made up only of field codes that expanded to nothing is removed.
+            let only_codes = pieces.iter().all(|piece| matches!(piece, Piece::Code(_)));
+            if !(only_codes && arg.is_empty()) {
+                output.push(arg);
+            }
+        }
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::desktop::{parse_exec, Fields};
+
+    #[test]
+    fn field_codes() {
+        let fields = Fields {
+            urls: vec![String::from("https://example.com")],
+            name: Some(String::from("Browser")),
+            ..Fields::default()
+        };
+        let result = parse_exec("browser %f --title=%c %u 100%% \"%%\\$HOME\" %m", &fields);
+        assert_eq!(
+            result.unwrap(),
+            vec![
+                "browser",
+                "--title=Browser",
+                "https://example.com",
+                "100%",
+                "%$HOME"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_exec() {
+        let fields = Fields::default();
+        assert_eq!(parse_exec("sh -c echo;reboot", &fields), None);
+        assert_eq!(parse_exec("app \"%f\"", &fields), None);
+        assert_eq!(parse_exec("app --files=%F", &fields), None);
+        assert_eq!(parse_exec("app %z", &fields), None);
+        assert_eq!(parse_exec("app \"a\"b", &fields), None);
+        assert_eq!(parse_exec("app \"\\n\"", &fields), None);
+    }
+}
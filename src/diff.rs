@@ -0,0 +1,155 @@
+//! Token-level comparison of commands.
+
+use crate::prelude::*;
+use crate::{ParseError, Token};
+use core::fmt;
+
+/// A difference between two commands, found by [`Parser::diff`](crate::Parser::diff). Each
+/// token carries its span within the command it came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A token of the new command, absent from the old one.
+    Added(Token),
+    /// A token of the old command, absent from the new one.
+    Removed(Token),
+    /// A token of the old command, replaced by a different token of the new one.
+    Changed(Token, Token),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(token) => write!(f, "+ {:?}", token.as_str()),
+            Change::Removed(token) => write!(f, "- {:?}", token.as_str()),
+            Change::Changed(old, new) => write!(f, "~ {:?} -> {:?}", old.as_str(), new.as_str()),
+        }
+    }
+}
+
+/// The first difference between the tokens of two commands. See
+/// [`Parser::compare`](crate::Parser::compare).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mismatch {
+    /// The index of the first token that differs.
+    pub index: usize,
+    /// The first command's token at `index`, or None if the first command has no more tokens.
+    pub left: Option<String>,
+    /// The second command's token at `index`, or None if the second command has no more tokens.
+    pub right: Option<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => {
+                write!(f, "token {} differs: {:?} != {:?}", self.index, left, right)
+            }
+            (Some(left), None) => write!(f, "unexpected token {} {:?}", self.index, left),
+            (None, Some(right)) => write!(f, "missing token {} {:?}", self.index, right),
+            (None, None) => write!(f, "token {} differs", self.index),
+        }
+    }
+}
+
+/// Compares two token streams in lockstep, stopping at the first error or difference.
+pub(crate) fn compare<A, B>(left: A, right: B) -> Result<Option<Mismatch>, ParseError>
+where
+    A: Iterator<Item = Result<Token, ParseError>>,
+    B: Iterator<Item = Result<Token, ParseError>>,
+{
+    let (mut left, mut right) = (left.fuse(), right.fuse());
+    for index in 0.. {
+        let pair = (left.next().transpose()?, right.next().transpose()?);
+        match pair {
+            (None, None) => break,
+            (Some(a), Some(b)) if a.value == b.value => continue,
+            (a, b) => {
+                return Ok(Some(Mismatch {
+                    index,
+                    left: a.map(Token::into_string),
+                    right: b.map(Token::into_string),
+                }))
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Finds a shortest edit script turning `old` into `new`, keeping their longest common
+/// subsequence of tokens. A removal at the same place as an addition is reported as a change.
+pub(crate) fn diff(old: Vec<Token>, new: Vec<Token>) -> Vec<Change> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i].value == new[j].value {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let mut old = old.into_iter().enumerate().peekable();
+    let mut new = new.into_iter().enumerate().peekable();
+    loop {
+        let change = match (old.peek(), new.peek()) {
+            (Some((_, a)), Some((_, b))) if a.value == b.value => {
+                old.next();
+                new.next();
+                continue;
+            }
+            (Some(&(i, _)), Some(&(j, _))) if common[i][j] == common[i + 1][j + 1] => {
+                let (_, a) = old.next().unwrap();
+                let (_, b) = new.next().unwrap();
+                Change::Changed(a, b)
+            }
+            (Some(&(i, _)), Some(&(j, _))) if common[i + 1][j] >= common[i][j + 1] => {
+                Change::Removed(old.next().unwrap().1)
+            }
+            (Some(_), None) => Change::Removed(old.next().unwrap().1),
+            (_, Some(_)) => Change::Added(new.next().unwrap().1),
+            (None, None) => break,
+        };
+        changes.push(change);
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Change, Parser};
+
+    #[test]
+    fn edit_script() {
+        let parser = Parser::new();
+        let changes = parser
+            .diff("cc -O2 -c 'a b.c' -o out", "cc -g -c 'a b.c' -Wall")
+            .unwrap();
+        let summary: Vec<String> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            summary,
+            vec!["~ \"-O2\" -> \"-g\"", "~ \"-o\" -> \"-Wall\"", "- \"out\""]
+        );
+        match &changes[0] {
+            Change::Changed(old, new) => assert_eq!((old.span(), new.span()), (3..6, 3..5)),
+            change => panic!("unexpected change {:?}", change),
+        }
+
+        assert_eq!(parser.diff("a b", "a b").unwrap(), vec![]);
+        let changes = parser.diff("", "x y").unwrap();
+        assert!(matches!(&changes[..], [Change::Added(_), Change::Added(_)]));
+        assert!(parser.diff("a", "'").is_err());
+    }
+
+    #[test]
+    fn mismatches() {
+        let parser = Parser::new();
+        assert_eq!(parser.compare("a 'b c'", "a  b\\ c").unwrap(), None);
+        let mismatch = parser.compare("a b", "a b c").unwrap().unwrap();
+        assert_eq!((mismatch.index, mismatch.left.as_deref()), (2, None));
+        assert_eq!(mismatch.to_string(), "missing token 2 \"c\"");
+        assert!(parser.compare("a b", "a '").is_err() && parser.compare("a b", "x '").is_ok());
+    }
+}
@@ -0,0 +1,389 @@
+//! Errors produced while parsing.
+
+use crate::position::char_boundary;
+use crate::prelude::*;
+use crate::Position;
+use core::fmt;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+/// The reason input could not be parsed. Every error produced by the parser carries one of
+/// these kinds, along with its position, in a [`ParseError`]. More kinds may be added in future
+/// releases, so matches must include a wildcard arm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The given quotation mark has no terminating counterpart.
+    UnterminatedQuote(char),
+    /// The input ends with a backslash that escapes nothing.
+    TrailingBackslash,
+    /// A subshell or brace group was never closed.
+    UnclosedGroup,
+    /// An operator or reserved word appeared where it is not allowed.
+    UnexpectedToken,
+    /// A control character was found while the parser rejects them.
+    ControlCharacter(char),
+    /// A backslash escaped a character that strict escaping does not allow.
+    UnknownEscape(char),
+    /// A newline was found while the parser rejects them.
+    UnexpectedNewline,
+    /// A byte order mark was found after the start of the input.
+    ByteOrderMark,
+    /// A character forbidden by [`Parser::forbid`](crate::Parser::forbid) was found.
+    ForbiddenCharacter(char),
+    /// The input contained no tokens, where a command to run was needed.
+    EmptyCommand,
+    /// A caller-provided buffer had no room for a token. See
+    /// [`Parser::parse_fixed`](crate::Parser::parse_fixed).
+    CapacityExceeded,
+    /// A token grew longer than the given number of bytes, the maximum set with
+    /// [`Parser::max_token_length`](crate::Parser::max_token_length).
+    TokenTooLong(usize),
+    /// The input passed one of the limits set to bound the work done parsing it, such as
+    /// [`Parser::max_input_length`](crate::Parser::max_input_length). Parsing stops as soon as
+    /// the limit is passed, even when recovering from errors.
+    LimitsExceeded(Limit),
+}
+
+/// A limit on the resources parsing may use, which the input can exceed. See
+/// [`ErrorKind::LimitsExceeded`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Limit {
+    /// The length of the input, set with
+    /// [`Parser::max_input_length`](crate::Parser::max_input_length).
+    InputLength,
+    /// The number of tokens, set with [`Parser::max_tokens`](crate::Parser::max_tokens).
+    TokenCount,
+    /// The size of the text produced by expansions, set with
+    /// [`Parser::max_expansion`](crate::Parser::max_expansion).
+    ExpansionSize,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Limit::InputLength => "input length",
+            Limit::TokenCount => "token count",
+            Limit::ExpansionSize => "expansion size",
+        })
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnterminatedQuote(quote) => write!(f, "unterminated {} quote", quote),
+            ErrorKind::TrailingBackslash => f.write_str("trailing backslash"),
+            ErrorKind::UnclosedGroup => f.write_str("unclosed group"),
+            ErrorKind::UnexpectedToken => f.write_str("unexpected token"),
+            ErrorKind::ControlCharacter(ch) => write!(f, "control character {:?}", ch),
+            ErrorKind::UnknownEscape(ch) => write!(f, "unknown escape sequence \\{}", ch),
+            ErrorKind::UnexpectedNewline => f.write_str("unexpected newline"),
+            ErrorKind::ByteOrderMark => f.write_str("unexpected byte order mark"),
+            ErrorKind::ForbiddenCharacter(ch) => write!(f, "forbidden character {:?}", ch),
+            ErrorKind::EmptyCommand => f.write_str("empty command"),
+            ErrorKind::CapacityExceeded => f.write_str("capacity exceeded"),
+            ErrorKind::TokenTooLong(max) => write!(f, "token longer than {} bytes", max),
+            ErrorKind::LimitsExceeded(limit) => write!(f, "{} limit exceeded", limit),
+        }
+    }
+}
+
+/// A stable, machine-readable identifier for each category of [`ErrorKind`], without the
+/// details some kinds carry. Codes, and their string forms, do not change between releases,
+/// although new codes may be added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// See [`ErrorKind::UnterminatedQuote`].
+    UnterminatedQuote,
+    /// See [`ErrorKind::TrailingBackslash`].
+    TrailingBackslash,
+    /// See [`ErrorKind::UnclosedGroup`].
+    UnclosedGroup,
+    /// See [`ErrorKind::UnexpectedToken`].
+    UnexpectedToken,
+    /// See [`ErrorKind::ControlCharacter`].
+    ControlCharacter,
+    /// See [`ErrorKind::UnknownEscape`].
+    UnknownEscape,
+    /// See [`ErrorKind::UnexpectedNewline`].
+    UnexpectedNewline,
+    /// See [`ErrorKind::ByteOrderMark`].
+    ByteOrderMark,
+    /// See [`ErrorKind::ForbiddenCharacter`].
+    ForbiddenCharacter,
+    /// See [`ErrorKind::EmptyCommand`].
+    EmptyCommand,
+    /// See [`ErrorKind::CapacityExceeded`].
+    CapacityExceeded,
+    /// See [`ErrorKind::TokenTooLong`].
+    TokenTooLong,
+    /// See [`ErrorKind::LimitsExceeded`].
+    LimitsExceeded,
+}
+
+impl ErrorCode {
+    /// The code as a `snake_case` string, such as `"unterminated_quote"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnterminatedQuote => "unterminated_quote",
+            ErrorCode::TrailingBackslash => "trailing_backslash",
+            ErrorCode::UnclosedGroup => "unclosed_group",
+            ErrorCode::UnexpectedToken => "unexpected_token",
+            ErrorCode::ControlCharacter => "control_character",
+            ErrorCode::UnknownEscape => "unknown_escape",
+            ErrorCode::UnexpectedNewline => "unexpected_newline",
+            ErrorCode::ByteOrderMark => "byte_order_mark",
+            ErrorCode::ForbiddenCharacter => "forbidden_character",
+            ErrorCode::EmptyCommand => "empty_command",
+            ErrorCode::CapacityExceeded => "capacity_exceeded",
+            ErrorCode::TokenTooLong => "token_too_long",
+            ErrorCode::LimitsExceeded => "limits_exceeded",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ErrorKind {
+    /// The stable code identifying this kind of error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ErrorKind::UnterminatedQuote(_) => ErrorCode::UnterminatedQuote,
+            ErrorKind::TrailingBackslash => ErrorCode::TrailingBackslash,
+            ErrorKind::UnclosedGroup => ErrorCode::UnclosedGroup,
+            ErrorKind::UnexpectedToken => ErrorCode::UnexpectedToken,
+            ErrorKind::ControlCharacter(_) => ErrorCode::ControlCharacter,
+            ErrorKind::UnknownEscape(_) => ErrorCode::UnknownEscape,
+            ErrorKind::UnexpectedNewline => ErrorCode::UnexpectedNewline,
+            ErrorKind::ByteOrderMark => ErrorCode::ByteOrderMark,
+            ErrorKind::ForbiddenCharacter(_) => ErrorCode::ForbiddenCharacter,
+            ErrorKind::EmptyCommand => ErrorCode::EmptyCommand,
+            ErrorKind::CapacityExceeded => ErrorCode::CapacityExceeded,
+            ErrorKind::TokenTooLong(_) => ErrorCode::TokenTooLong,
+            ErrorKind::LimitsExceeded(_) => ErrorCode::LimitsExceeded,
+        }
+    }
+}
+
+/// An error describing why, and where, input could not be parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseError {
+    kind: ErrorKind,
+    offset: usize,
+    end: usize,
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self {
+            kind,
+            offset: span.start,
+            end: span.end,
+        }
+    }
+
+    /// The same error, found in input that begins `by` bytes into some larger input.
+    pub(crate) fn shifted(self, by: usize) -> Self {
+        Self {
+            offset: self.offset + by,
+            end: self.end + by,
+            ..self
+        }
+    }
+
+    /// The kind of problem encountered.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The line and column in `input`, which must be the input that failed to parse, at which the
+    /// problem was found.
+    ///
+    /// ```
+    /// use comma::{Parser, Position};
+    ///
+    /// let error = Parser::new().try_parse("a\nb 'c").unwrap_err();
+    /// assert_eq!(error.position("a\nb 'c"), Position { line: 2, column: 3 });
+    /// ```
+    pub fn position(&self, input: &str) -> Position {
+        Position::of(input, self.offset)
+    }
+
+    /// The stable code identifying the kind of problem, for matching across releases.
+    ///
+    /// ```
+    /// use comma::{try_parse_command, ErrorCode};
+    ///
+    /// let error = try_parse_command("say \"hi").unwrap_err();
+    /// assert_eq!(error.code(), ErrorCode::UnterminatedQuote);
+    /// assert_eq!(error.code().as_str(), "unterminated_quote");
+    /// ```
+    pub fn code(&self) -> ErrorCode {
+        self.kind.code()
+    }
+
+    /// The byte offset in the input at which the problem was found.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The byte range of the input involved in the problem. This is the offending character or
+    /// token, except for an unterminated quote, where it runs from the opening quotation mark to
+    /// the end of the input, and an unclosed group, where it runs from the opening delimiter to
+    /// the end of the input.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let error = Parser::new().try_parse("say \"hi 'there'").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+    /// assert_eq!(error.span(), 4..15);
+    /// ```
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.end
+    }
+
+    /// Formats the error as a snippet of `input`, which must be the input that failed to parse,
+    /// with carets marking the problem, ready to be printed by command-line tools.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let input = "cd /tmp\necho 'hi";
+    /// let error = Parser::new().try_parse(input).unwrap_err();
+    /// assert_eq!(
+    ///     error.render(input),
+    ///     "error: unterminated ' quote\n --> line 2, column 6\n  |\n2 | echo 'hi\n  |      ^^^\n"
+    /// );
+    /// ```
+    pub fn render(&self, input: &str) -> String {
+        // Should `input` not be what was parsed, the offset may not lie on a character boundary.
+        let offset = char_boundary(input, self.offset);
+        let position = Position::of(input, offset);
+        let line_start = input[..offset].rfind('\n').map_or(0, |at| at + 1);
+        let line_end = input[offset..]
+            .find('\n')
+            .map_or(input.len(), |at| offset + at);
+        let line = input[line_start..line_end].trim_end_matches('\r');
+        let number = position.line;
+
+        // Tabs are kept in the padding so that the carets line up however they are displayed.
+        let before = &input[line_start..offset];
+        let padding: String = before
+            .chars()
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end = self.end.max(offset).min(line_start + line.len());
+        let carets = input
+            .get(offset..end)
+            .map_or(0, |marked| marked.chars().count());
+
+        let gutter = " ".repeat(number.to_string().len());
+        format!(
+            "error: {}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}{}\n",
+            self.kind,
+            gutter,
+            number,
+            position.column,
+            gutter,
+            number,
+            line,
+            gutter,
+            padding,
+            "^".repeat(carets.max(1)),
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ParseError {}
+
+/// Reports each error with a code, a label marking its span, and advice on fixing it. Attach the
+/// parsed input with [`miette::Report::with_source_code`] to see the labelled snippet.
+///
+/// ```
+/// use comma::Parser;
+/// use miette::{Diagnostic, Report};
+///
+/// let input = "echo 'hi";
+/// let error = Parser::new().try_parse(input).unwrap_err();
+/// let code = Diagnostic::code(&error).unwrap();
+/// assert_eq!(code.to_string(), "comma::unterminated_quote");
+///
+/// let report = Report::new(error).with_source_code(input);
+/// println!("{:?}", report);
+/// ```
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for ParseError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(format!("comma::{}", ParseError::code(self))))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = match self.kind {
+            ErrorKind::UnterminatedQuote(quote) => {
+                format!("add a closing {} or escape the opening one", quote)
+            }
+            ErrorKind::TrailingBackslash => String::from("remove the backslash or escape it"),
+            ErrorKind::UnclosedGroup => String::from("close the group"),
+            ErrorKind::UnexpectedToken => String::from("quote the token to use it literally"),
+            ErrorKind::ControlCharacter(_) => String::from("remove the control character"),
+            ErrorKind::UnknownEscape(ch) => format!("remove the backslash, or write \\\\{}", ch),
+            ErrorKind::UnexpectedNewline => String::from("quote or escape the newline"),
+            ErrorKind::ByteOrderMark => String::from("remove the byte order mark"),
+            ErrorKind::ForbiddenCharacter(_) => String::from("remove the forbidden character"),
+            ErrorKind::EmptyCommand => String::from("name a program to run"),
+            ErrorKind::CapacityExceeded => String::from("provide larger buffers"),
+            ErrorKind::TokenTooLong(_) => String::from("shorten the token or close its quote"),
+            ErrorKind::LimitsExceeded(Limit::InputLength) => String::from("shorten the input"),
+            ErrorKind::LimitsExceeded(Limit::TokenCount) => String::from("pass fewer arguments"),
+            ErrorKind::LimitsExceeded(_) => String::from("use fewer or shorter expansions"),
+        };
+        Some(Box::new(help))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let span = miette::SourceSpan::from(self.span());
+        let label = miette::LabeledSpan::new_with_span(Some(self.kind.to_string()), span);
+        Some(Box::new(core::iter::once(label)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+
+    #[test]
+    fn render_at_end() {
+        let parser = Parser::new();
+        let error = parser.try_parse("a\tb \\").unwrap_err();
+        assert_eq!(
+            error.render("a\tb \\"),
+            "error: trailing backslash\n --> line 1, column 5\n  |\n1 | a\tb \\\n  |  \t  ^\n"
+        );
+
+        assert!(error.render("aaaé").contains("column 4"));
+
+        let input = "x\n".repeat(9) + "'\r\n";
+        let error = parser.try_parse(&input).unwrap_err();
+        assert!(error.render(&input).ends_with("10 | '\n   | ^\n"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Parsing into caller-provided buffers, for targets that cannot allocate.
+//!
+//! The lexer runs without building token text to find each token, then once more over each
+//! token, writing its text straight into the caller's buffer.
+
+use crate::parser::Lexer;
+use crate::{ErrorKind, ParseError, Parser};
+use core::fmt::{self, Write};
+use core::str;
+
+/// Writes text into the front of a byte buffer, remembering whether any of it did not fit.
+struct Writer<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+    full: bool,
+}
+
+impl Write for Writer<'_> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let end = self.len + text.len();
+        match self.buffer.get_mut(self.len..end) {
+            Some(slot) => {
+                slot.copy_from_slice(text.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+            None => {
+                self.full = true;
+                Err(fmt::Error)
+            }
+        }
+    }
+}
+
+pub(crate) fn parse<'b>(
+    parser: &Parser,
+    input: &str,
+    mut buffer: &'b mut [u8],
+    tokens: &mut [&'b str],
+) -> Result<usize, ParseError> {
+    let mut count = 0;
+
+    for token in Lexer::new(parser, input).with_discard() {
+        let span = token?.span;
+        let overflow = || ParseError::new(ErrorKind::CapacityExceeded, span.clone());
+        let slot = tokens.get_mut(count).ok_or_else(overflow)?;
+
+        let mut writer = Writer {
+            buffer,
+            len: 0,
+            full: false,
+        };
+        if parser.keep_quotes {
+            writer.write_str(&input[span.clone()]).ok();
+        } else {
+            Lexer::starting_at(parser, input, span.start)
+                .with_output(&mut writer)
+                .next();
+        }
+
+        let Writer {
+            buffer: written,
+            len,
+            full,
+        } = writer;
+        if full {
+            return Err(overflow());
+        }
+        let (text, rest) = written.split_at_mut(len);
+        *slot = str::from_utf8(text).unwrap_or_default();
+        buffer = rest;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, Parser};
+
+    #[test]
+    fn fills_buffers() {
+        let parser = Parser::new().comment("#");
+        let mut buffer = [0; 16];
+        let mut tokens = [""; 4];
+        let input = "cp 'a b' \\\"c\\tx # d";
+        let count = parser.parse_fixed(input, &mut buffer, &mut tokens).unwrap();
+        assert_eq!(tokens[..count], ["cp", "a b", "\"c\tx"]);
+
+        let mut tokens = [""; 2];
+        let error = parser.parse_fixed(input, &mut buffer, &mut tokens);
+        assert_eq!(error.unwrap_err().span(), 9..15);
+
+        let mut buffer = [0; 6];
+        let error = Parser::new()
+            .parse_fixed("ab cd efg", &mut buffer, &mut [""; 4])
+            .unwrap_err();
+        assert_eq!(
+            (error.kind(), error.span()),
+            (ErrorKind::CapacityExceeded, 6..9)
+        );
+
+        let quoted = Parser::new().keep_quotes(true);
+        let (mut buffer, mut tokens) = ([0; 5], [""; 1]);
+        quoted
+            .parse_fixed("'a b'", &mut buffer, &mut tokens)
+            .unwrap();
+        assert_eq!(tokens, ["'a b'"]);
+    }
+}
@@ -0,0 +1,107 @@
+//! Pretty-printing of commands across several lines.
+
+use crate::prelude::*;
+use crate::{quote, ParseError, Parser};
+
+/// Writes commands with each token quoted as necessary, wrapping long commands across several
+/// lines joined by backslash continuations. The result can be read by POSIX shells, and by a
+/// [`Parser`] with [`TrailingBackslash::Continue`](crate::TrailingBackslash::Continue).
+///
+/// ```
+/// use comma::Formatter;
+///
+/// let formatter = Formatter::new().width(30).indent(2);
+/// let tokens = ["docker", "run", "--rm", "-v", "/srv/my data:/data", "-p", "8080:80", "nginx"];
+/// assert_eq!(
+///     formatter.format(&tokens),
+///     "docker run --rm -v \\\n  '/srv/my data:/data' -p \\\n  8080:80 nginx"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Formatter {
+    width: usize,
+    indent: usize,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 4,
+        }
+    }
+}
+
+impl Formatter {
+    /// Creates a formatter wrapping lines at 80 characters and indenting continuation lines by
+    /// four spaces.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of characters, including any trailing backslash, that lines should not
+    /// exceed. A token too long to fit is placed on a line of its own.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets the number of spaces with which continuation lines are indented.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Formats a list of tokens as a command.
+    pub fn format<S: AsRef<str>>(&self, tokens: &[S]) -> String {
+        let mut output = String::new();
+        // The number of characters on the current line, or None before the first token.
+        let mut line: Option<usize> = None;
+
+        for token in tokens {
+            let word = quote(token.as_ref());
+            let length = word.chars().count();
+            line = Some(match line {
+                None => length,
+                // Leave room for the continuation's ` \` on the line being extended.
+                Some(used) if used + 1 + length + 2 <= self.width => {
+                    output.push(' ');
+                    used + 1 + length
+                }
+                Some(_) => {
+                    output.push_str(" \\\n");
+                    output.extend(core::iter::repeat(' ').take(self.indent));
+                    self.indent + length
+                }
+            });
+            output.push_str(&word);
+        }
+
+        output
+    }
+
+    /// Parses `input` with a default [`Parser`], and formats the result.
+    pub fn format_command(&self, input: &str) -> Result<String, ParseError> {
+        Ok(self.format(&Parser::new().try_parse(input)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Formatter, Parser, TrailingBackslash};
+
+    #[test]
+    fn wraps_and_round_trips() {
+        let formatter = Formatter::new().width(12).indent(1);
+        let input = "a 'b c' averyveryverylongword d\\'e f";
+        let formatted = formatter.format_command(input).unwrap();
+        assert_eq!(
+            formatted,
+            "a 'b c' \\\n averyveryverylongword \\\n \"d'e\" f"
+        );
+
+        let parser = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+        assert_eq!(parser.parse(&formatted), Parser::new().parse(input));
+        assert_eq!(formatter.format::<&str>(&[]), "");
+    }
+}
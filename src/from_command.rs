@@ -0,0 +1,286 @@
+//! Mapping of a command's tokens onto the fields of a struct.
+
+use crate::prelude::*;
+use crate::{ParseError, Parser};
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::str::FromStr;
+
+/// A type that can be built from the tokens of a command, for small command languages that do
+/// not warrant a full argument parser.
+///
+/// With the `derive` feature, `#[derive(FromCommand)]` implements this for structs with named
+/// fields. Each field becomes one of:
+///
+/// - a flag, written `--name`, if it is a `bool`;
+/// - an option, written `--name value` or `--name=value`, if it is marked `#[comma(long)]` or
+///   `#[comma(short = 'n')]`, which also allows `-n value` and `-nvalue`;
+/// - otherwise, a positional argument, filled in declaration order.
+///
+/// Options and positional arguments are required, unless their type is an `Option`, or a `Vec`
+/// that collects every occurrence or every remaining argument. Values are converted with
+/// [`FromStr`]. Field names have underscores replaced by hyphens, and a `--` token makes every
+/// following token positional.
+///
+#[cfg_attr(feature = "derive", doc = "```")]
+#[cfg_attr(not(feature = "derive"), doc = "```ignore")]
+/// use comma::FromCommand;
+///
+/// #[derive(FromCommand)]
+/// struct Deploy {
+///     #[comma(short = 'f')]
+///     force: bool,
+///     #[comma(long)]
+///     retries: Option<u32>,
+///     service: String,
+///     hosts: Vec<String>,
+/// }
+///
+/// assert_eq!(Deploy::USAGE, "[-f|--force] [--retries <retries>] <service> [<hosts>...]");
+/// let deploy = Deploy::from_command("-f api --retries=3 'web 1' web2")?;
+/// assert!(deploy.force && deploy.retries == Some(3));
+/// assert_eq!(deploy.hosts, vec!["web 1", "web2"]);
+/// # Ok::<(), comma::FromCommandError>(())
+/// ```
+pub trait FromCommand: Sized {
+    /// A one-line description of the tokens expected, suitable for a usage message.
+    const USAGE: &'static str;
+
+    /// Builds a value from tokens that have already been parsed.
+    fn from_tokens(tokens: Vec<String>) -> Result<Self, FromCommandError>;
+
+    /// Parses `input` with a default [`Parser`] and builds a value from its tokens.
+    fn from_command(input: &str) -> Result<Self, FromCommandError> {
+        Self::from_tokens(Parser::new().try_parse(input)?)
+    }
+}
+
+/// An error building a value with [`FromCommand`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FromCommandError {
+    /// The input was not a well-formed command.
+    Parse(ParseError),
+    /// A required positional argument or option, named here, was not given.
+    Missing(&'static str),
+    /// An argument was given that no field accepts.
+    Unexpected(String),
+    /// An option was given that no field declares.
+    UnknownOption(String),
+    /// The named option was given without a value.
+    MissingValue(&'static str),
+    /// The value given for the named field could not be converted.
+    InvalidValue {
+        /// The field's name.
+        name: &'static str,
+        /// The value given.
+        value: String,
+    },
+}
+
+impl fmt::Display for FromCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromCommandError::Parse(error) => error.fmt(f),
+            FromCommandError::Missing(name) => write!(f, "missing {}", name),
+            FromCommandError::Unexpected(arg) => write!(f, "unexpected argument {:?}", arg),
+            FromCommandError::UnknownOption(arg) => write!(f, "unknown option {}", arg),
+            FromCommandError::MissingValue(name) => write!(f, "option --{} needs a value", name),
+            FromCommandError::InvalidValue { name, value } => {
+                write!(f, "invalid value {:?} for {}", value, name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FromCommandError {}
+
+impl From<ParseError> for FromCommandError {
+    fn from(error: ParseError) -> Self {
+        FromCommandError::Parse(error)
+    }
+}
+
+/// Tokens sorted into options and positional arguments, from which derived implementations of
+/// [`FromCommand`] take each field's value.
+#[doc(hidden)]
+pub struct Args {
+    options: Vec<(&'static str, String)>,
+    positional: VecDeque<String>,
+}
+
+/// An option a derived implementation accepts: its long name, short name, and whether it takes
+/// a value.
+#[doc(hidden)]
+pub type OptionSpec = (&'static str, Option<char>, bool);
+
+impl Args {
+    pub fn new(tokens: Vec<String>, specs: &[OptionSpec]) -> Result<Self, FromCommandError> {
+        let mut args = Args {
+            options: Vec::new(),
+            positional: VecDeque::new(),
+        };
+        let mut tokens = tokens.into_iter();
+
+        while let Some(token) = tokens.next() {
+            if token == "--" {
+                args.positional.extend(tokens.by_ref());
+            } else if let Some(long) = token.strip_prefix("--") {
+                let (name, inline) = match long.find('=') {
+                    Some(at) => (&long[..at], Some(long[at + 1..].to_string())),
+                    None => (long, None),
+                };
+                let spec = specs.iter().find(|spec| spec.0 == name);
+                match spec {
+                    Some(&(name, _, true)) => {
+                        let value = inline.or_else(|| tokens.next());
+                        let value = value.ok_or(FromCommandError::MissingValue(name))?;
+                        args.options.push((name, value));
+                    }
+                    Some(&(name, _, false)) if inline.is_none() => {
+                        args.options.push((name, String::new()))
+                    }
+                    _ => return Err(FromCommandError::UnknownOption(token)),
+                }
+            } else if token.len() > 1 && token.starts_with('-') {
+                args.short(&token, specs, &mut tokens)?;
+            } else {
+                args.positional.push_back(token);
+            }
+        }
+        Ok(args)
+    }
+
+    /// Records the short options combined in `token`, such as `-vf` or `-ofile`.
+    fn short(
+        &mut self,
+        token: &str,
+        specs: &[OptionSpec],
+        tokens: &mut impl Iterator<Item = String>,
+    ) -> Result<(), FromCommandError> {
+        for (at, ch) in token.char_indices().skip(1) {
+            let spec = specs.iter().find(|spec| spec.1 == Some(ch));
+            match spec {
+                Some(&(name, _, true)) => {
+                    let rest = &token[at + ch.len_utf8()..];
+                    let value = match rest {
+                        "" => tokens.next().ok_or(FromCommandError::MissingValue(name))?,
+                        rest => rest.to_string(),
+                    };
+                    self.options.push((name, value));
+                    return Ok(());
+                }
+                Some(&(name, _, false)) => self.options.push((name, String::new())),
+                None => return Err(FromCommandError::UnknownOption(format!("-{}", ch))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the named flag was given.
+    pub fn flag(&mut self, name: &'static str) -> bool {
+        self.options.iter().any(|option| option.0 == name)
+    }
+
+    /// The value of the named option, which must be given.
+    pub fn required<T: FromStr>(&mut self, name: &'static str) -> Result<T, FromCommandError> {
+        self.optional(name)?.ok_or(FromCommandError::Missing(name))
+    }
+
+    /// The value of the named option, if given. Should it be given more than once, the last
+    /// value wins.
+    pub fn optional<T: FromStr>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<Option<T>, FromCommandError> {
+        let value = self.options.iter().rev().find(|option| option.0 == name);
+        value.map(|option| convert(name, &option.1)).transpose()
+    }
+
+    /// Every value given for the named option.
+    pub fn values<T: FromStr>(&mut self, name: &'static str) -> Result<Vec<T>, FromCommandError> {
+        let values = self.options.iter().filter(|option| option.0 == name);
+        values.map(|option| convert(name, &option.1)).collect()
+    }
+
+    /// The next positional argument, which must be given.
+    pub fn positional<T: FromStr>(&mut self, name: &'static str) -> Result<T, FromCommandError> {
+        self.next_positional(name)?
+            .ok_or(FromCommandError::Missing(name))
+    }
+
+    /// The next positional argument, if there is one.
+    pub fn next_positional<T: FromStr>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<Option<T>, FromCommandError> {
+        let value = self.positional.pop_front();
+        value.map(|value| convert(name, &value)).transpose()
+    }
+
+    /// Every remaining positional argument.
+    pub fn remaining<T: FromStr>(
+        &mut self,
+        name: &'static str,
+    ) -> Result<Vec<T>, FromCommandError> {
+        let values = core::mem::take(&mut self.positional);
+        values.iter().map(|value| convert(name, value)).collect()
+    }
+
+    /// Fails if any positional argument was left unused.
+    pub fn finish(mut self) -> Result<(), FromCommandError> {
+        match self.positional.pop_front() {
+            Some(arg) => Err(FromCommandError::Unexpected(arg)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn convert<T: FromStr>(name: &'static str, value: &str) -> Result<T, FromCommandError> {
+    value.parse().map_err(|_| FromCommandError::InvalidValue {
+        name,
+        value: value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Args, FromCommandError};
+    use crate::parse_command;
+
+    #[test]
+    fn sorts_tokens() {
+        let specs = [("verbose", Some('v'), false), ("out", Some('o'), true)];
+        let args = |input| Args::new(parse_command(input).unwrap(), &specs);
+
+        let mut parsed = args("a -vofile --out=x -- -b").unwrap();
+        assert!(parsed.flag("verbose"));
+        assert_eq!(parsed.values::<String>("out").unwrap(), vec!["file", "x"]);
+        assert_eq!(parsed.required::<String>("out").unwrap(), "x");
+        assert_eq!(parsed.positional::<String>("first").unwrap(), "a");
+        assert_eq!(parsed.remaining::<String>("rest").unwrap(), vec!["-b"]);
+        assert!(parsed.finish().is_ok());
+
+        assert_eq!(
+            args("--out").err(),
+            Some(FromCommandError::MissingValue("out"))
+        );
+        assert_eq!(
+            args("-vx").err(),
+            Some(FromCommandError::UnknownOption(String::from("-x")))
+        );
+        assert!(args("--verbose=yes").is_err());
+
+        let mut parsed = args("x y").unwrap();
+        let error = parsed.positional::<u8>("count").unwrap_err();
+        assert_eq!(error.to_string(), "invalid value \"x\" for count");
+        assert_eq!(
+            parsed.required::<u8>("out"),
+            Err(FromCommandError::Missing("out"))
+        );
+        assert_eq!(
+            parsed.finish(),
+            Err(FromCommandError::Unexpected(String::from("y")))
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Classification of input for syntax highlighting.
+
+use crate::args::OPERATORS;
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::{Parser, Token};
+use core::ops::Range;
+
+/// The role of a span of input, for syntax highlighting. See
+/// [`Parser::highlight`](crate::Parser::highlight).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Highlight {
+    /// Whitespace, or anything else outside of a token.
+    Whitespace,
+    /// Unquoted text within a token.
+    Word,
+    /// Text enclosed in quotation marks.
+    Quoted,
+    /// A quotation mark opening or closing quoted text.
+    QuoteDelimiter,
+    /// An escape sequence, including its backslash.
+    Escape,
+    /// An unquoted shell operator, such as `|` or `&&`, standing as a token of its own.
+    Operator,
+    /// A comment, including its marker.
+    Comment,
+}
+
+/// The token types of the Language Server Protocol's semantic tokens legend, as indexed by
+/// [`Parser::semantic_tokens`](crate::Parser::semantic_tokens).
+pub const SEMANTIC_TOKEN_TYPES: &[&str] = &[
+    "parameter",
+    "string",
+    "escapeSequence",
+    "operator",
+    "comment",
+];
+
+/// The token modifiers of the Language Server Protocol's semantic tokens legend, as used by
+/// [`Parser::semantic_tokens`](crate::Parser::semantic_tokens). Quotation marks are strings with
+/// the `delimiter` modifier.
+pub const SEMANTIC_TOKEN_MODIFIERS: &[&str] = &["delimiter"];
+
+impl Highlight {
+    /// The index into [`SEMANTIC_TOKEN_TYPES`] and the bitset of [`SEMANTIC_TOKEN_MODIFIERS`]
+    /// describing this role, or None for whitespace.
+    fn semantic(self) -> Option<(u32, u32)> {
+        match self {
+            Highlight::Whitespace => None,
+            Highlight::Word => Some((0, 0)),
+            Highlight::Quoted => Some((1, 0)),
+            Highlight::QuoteDelimiter => Some((1, 1)),
+            Highlight::Escape => Some((2, 0)),
+            Highlight::Operator => Some((3, 0)),
+            Highlight::Comment => Some((4, 0)),
+        }
+    }
+}
+
+/// Encodes highlighted spans as Language Server Protocol semantic tokens: five integers per
+/// token, giving its line relative to the previous token, its start character relative to the
+/// previous token when on the same line, its length, its type, and its modifiers. Characters are
+/// counted in UTF-16 code units, and spans are divided at line breaks.
+pub(crate) fn semantic_tokens(input: &str, spans: Vec<(Range<usize>, Highlight)>) -> Vec<u32> {
+    let mut data = Vec::new();
+    // The line and UTF-16 column of byte offset `at`, and of the previous token.
+    let (mut at, mut line, mut column) = (0, 0, 0);
+    let (mut last_line, mut last_column) = (0, 0);
+
+    for (span, role) in spans {
+        let (kind, modifiers) = match role.semantic() {
+            Some(semantic) => semantic,
+            None => continue,
+        };
+
+        let mut start = span.start;
+        for piece in input[span].split('\n') {
+            let end = start + piece.len();
+            let text = piece.trim_end_matches('\r');
+            if !text.is_empty() {
+                for ch in input[at..start].chars() {
+                    if ch == '\n' {
+                        line += 1;
+                        column = 0;
+                    } else {
+                        column += ch.len_utf16();
+                    }
+                }
+                at = start;
+
+                let delta = if line == last_line {
+                    column - last_column
+                } else {
+                    column
+                };
+                let length = text.encode_utf16().count();
+                let position = [line - last_line, delta, length];
+                data.extend(position.iter().map(|&value| value as u32));
+                data.extend([kind, modifiers]);
+                last_line = line;
+                last_column = column;
+            }
+            start = end + 1;
+        }
+    }
+
+    data
+}
+
+pub(crate) fn highlight(parser: &Parser, input: &str) -> Vec<(Range<usize>, Highlight)> {
+    let (_, roles) = paint(parser, input);
+    runs(&roles, 0..input.len())
+}
+
+/// Parses `input` as best it can, returning its tokens and comments along with the role of each
+/// byte of the input.
+pub(crate) fn paint(parser: &Parser, input: &str) -> (Vec<Token>, Vec<Highlight>) {
+    // Split long options would leave their `=` outside of any token.
+    let parser = parser.clone().split_long_options(false);
+    let mut lexer = Lexer::new(&parser, input)
+        .with_recovery()
+        .with_comments(true)
+        .with_marks();
+
+    // Each byte is painted with its role, later spans painting over earlier ones.
+    let mut roles = vec![Highlight::Whitespace; input.len()];
+    let mut paint = |span: Range<usize>, role| {
+        for byte in &mut roles[span] {
+            *byte = role;
+        }
+    };
+
+    let tokens: Vec<Token> = lexer.by_ref().filter_map(Result::ok).collect();
+    for token in &tokens {
+        let role = if token.comment {
+            Highlight::Comment
+        } else if token.plain && OPERATORS.contains(&token.as_str()) {
+            Highlight::Operator
+        } else {
+            Highlight::Word
+        };
+        paint(token.span.clone(), role);
+    }
+
+    // Quoted text is painted first, since it may contain escape sequences.
+    let mut marks = lexer.marks();
+    marks.sort_by_key(|&(_, role)| role != Highlight::Quoted);
+    for (span, role) in marks {
+        paint(span, role);
+    }
+
+    (tokens, roles)
+}
+
+/// Divides the bytes within `range` into runs painted with the same role.
+pub(crate) fn runs(roles: &[Highlight], range: Range<usize>) -> Vec<(Range<usize>, Highlight)> {
+    let mut spans: Vec<(Range<usize>, Highlight)> = Vec::new();
+    for at in range {
+        let role = roles[at];
+        match spans.last_mut() {
+            Some((span, last)) if *last == role => span.end = at + 1,
+            _ => spans.push((at..at + 1, role)),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Highlight, Parser};
+
+    #[test]
+    fn highlight_spans() {
+        use Highlight::*;
+
+        let parser = Parser::new().comment("#");
+        let input = "a\\ b \"c\\\"d\" | é # note\n'x";
+        let spans = parser.highlight(input);
+        let roles: Vec<(&str, Highlight)> = spans
+            .iter()
+            .map(|(span, role)| (&input[span.clone()], *role))
+            .collect();
+        assert_eq!(
+            roles,
+            vec![
+                ("a", Word),
+                ("\\ ", Escape),
+                ("b", Word),
+                (" ", Whitespace),
+                ("\"", QuoteDelimiter),
+                ("c", Quoted),
+                ("\\\"", Escape),
+                ("d", Quoted),
+                ("\"", QuoteDelimiter),
+                (" ", Whitespace),
+                ("|", Operator),
+                (" ", Whitespace),
+                ("é", Word),
+                (" ", Whitespace),
+                ("# note", Comment),
+                ("\n", Whitespace),
+                ("'", QuoteDelimiter),
+                ("x", Quoted),
+            ]
+        );
+        assert!(parser.highlight("").is_empty());
+    }
+
+    #[test]
+    fn semantic_tokens() {
+        let parser = Parser::new().comment("#");
+        let data = parser.semantic_tokens("é 'a\r\nb' |\n# c");
+        assert_eq!(
+            data,
+            vec![
+                0, 0, 1, 0, 0, // é
+                0, 2, 1, 1, 1, // '
+                0, 1, 1, 1, 0, // a
+                1, 0, 1, 1, 0, // b
+                0, 1, 1, 1, 1, // '
+                0, 2, 1, 3, 0, // |
+                1, 0, 3, 4, 0, // # c
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Reparsing of edited input, reusing the tokens an edit leaves untouched.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::{ParseError, Parser, Token};
+use core::ops::Range;
+
+/// A change to some input: the replacement of a byte range with new text. See
+/// [`Parser::reparse`](crate::Parser::reparse).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Edit {
+    /// The byte range of the original input that was replaced.
+    pub range: Range<usize>,
+    /// The text that replaced it.
+    pub text: String,
+}
+
+impl Edit {
+    /// Creates an edit replacing `range` with `text`.
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// Applies the edit to `input`, returning the edited input. Returns None if the edited range
+    /// does not lie within `input`.
+    pub fn apply(&self, input: &str) -> Option<String> {
+        let before = input.get(..self.range.start)?;
+        let after = input.get(self.range.end..)?;
+        Some(format!("{}{}{}", before, self.text, after))
+    }
+}
+
+pub(crate) fn reparse(
+    parser: &Parser,
+    previous: &[Token],
+    input: &str,
+    edit: &Edit,
+) -> Result<Vec<Token>, ParseError> {
+    // Whether long options are split, and whether a limit is passed, depends on every other
+    // token, so nothing can be reused.
+    let limited = parser.max_tokens.is_some()
+        || parser.max_input_length.is_some()
+        || parser.max_expansion.is_some();
+    if parser.split_long_options || limited || edit.range.start > edit.range.end {
+        return lexer(parser, input, 0).collect();
+    }
+    let removed = edit.range.end - edit.range.start;
+
+    // Tokens ending before the edit are unaffected, since a separator follows each of them.
+    let kept = previous
+        .iter()
+        .take_while(|token| token.span.end < edit.range.start)
+        .count();
+    let mut tokens = previous[..kept].to_vec();
+    let resume = tokens.last().map_or(0, |token| token.span.end);
+    if !input.is_char_boundary(resume) {
+        return lexer(parser, input, 0).collect();
+    }
+
+    // Once a token begins where one began before, the rest of the input lexes as it did before.
+    let inserted = edit.text.len();
+    let mut later = previous[kept..]
+        .iter()
+        .filter(|token| token.span.start >= edit.range.end)
+        .peekable();
+
+    for token in lexer(parser, input, resume) {
+        let token = token?;
+        let start = token.span.start;
+        while later
+            .peek()
+            .map_or(false, |old| old.span.start - removed + inserted < start)
+        {
+            later.next();
+        }
+
+        if later
+            .peek()
+            .map_or(false, |old| old.span.start - removed + inserted == start)
+        {
+            let shift = |offset: usize| offset - removed + inserted;
+            tokens.extend(later.map(|old| Token {
+                span: shift(old.span.start)..shift(old.span.end),
+                ..old.clone()
+            }));
+            return Ok(tokens);
+        }
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// A lexer producing the same tokens as [`Parser::parse_tokens`], beginning at `start`.
+fn lexer<'p, 'a>(parser: &'p Parser, input: &'a str, start: usize) -> Lexer<'p, 'a> {
+    Lexer::starting_at(parser, input, start).with_comments(parser.keep_comments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edit;
+    use crate::{ErrorKind, Limit, Parser};
+
+    #[test]
+    fn matches_full_parse() {
+        let parser = Parser::new().comment("#").keep_comments(true);
+        let input = "cp 'a b' c\\ d  e # f\ng";
+        let edits = [
+            Edit::new(0..0, "x"),
+            Edit::new(4..4, "'"),
+            Edit::new(9..10, "zz "),
+            Edit::new(13..15, ""),
+            Edit::new(17..18, "'#"),
+            Edit::new(22..22, " h"),
+            Edit::new(2..2, " \""),
+        ];
+
+        let previous = parser.parse_tokens(input).unwrap();
+        for edit in &edits {
+            let edited = edit.apply(input).unwrap();
+            assert_eq!(
+                parser.reparse(&previous, &edited, edit).ok(),
+                parser.parse_tokens(&edited),
+                "{:?}",
+                edited
+            );
+        }
+        assert_eq!(Edit::new(3..99, "").apply(input), None);
+
+        // Each limit holds for the original input, but not once a token is inserted before it.
+        let edit = Edit::new(0..0, "\\e ");
+        let edited = edit.apply("b \\e").unwrap();
+        let limited = [
+            (Parser::new().max_tokens(Some(2)), Limit::TokenCount),
+            (Parser::new().max_input_length(Some(4)), Limit::InputLength),
+            (Parser::new().max_expansion(Some(2)), Limit::ExpansionSize),
+        ];
+        for (parser, limit) in limited {
+            let parser = parser.escape_sequence('e', "ee");
+            let previous = parser.parse_tokens("b \\e").unwrap();
+            let error = parser.reparse(&previous, &edited, &edit).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::LimitsExceeded(limit));
+        }
+    }
+}
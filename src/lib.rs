@@ -1,68 +1,680 @@
 //! `comma` parses command-line-style strings. See [`parse_command`] for details.
+//!
+//! The `std` feature, enabled by default, provides everything that needs an operating system:
+//! platform strings, process commands, response files and reading commands from `io::BufRead`.
+//! Without it, the crate is `no_std`, and needs only `alloc`.
 
-use std::iter::Peekable;
-use std::str::Chars;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-fn parse_escape(ch: char, chars: &mut Peekable<Chars>) -> Option<char> {
-    Some(match ch {
-        '\\' => match chars.next()? {
-            'n' => '\n',
-            'r' => '\r',
-            't' => '\t',
-            literal => literal,
-        },
-        x => x,
-    })
-}
+extern crate alloc;
 
-fn parse_string(chars: &mut Peekable<Chars>, delim: char) -> Option<String> {
-    let mut output = String::new();
+mod alias;
+mod args;
+pub mod ast;
+#[cfg(feature = "tokio")]
+mod async_reader;
+mod audit;
+#[cfg(feature = "clap")]
+mod cli;
+mod command;
+mod completion;
+mod continuation;
+mod cow;
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod desktop;
+mod diff;
+mod error;
+mod fixed;
+mod format;
+mod from_command;
+mod highlight;
+mod incremental;
+#[cfg(feature = "rustyline")]
+mod line_editor;
+mod lines;
+mod os;
+mod parser;
+mod position;
+mod prelude;
+mod quote;
+#[cfg(feature = "std")]
+mod reader;
+mod redact;
+#[cfg(feature = "std")]
+mod response;
+mod script;
+#[cfg(feature = "serde")]
+pub mod serde_with;
+mod stream;
+pub mod systemd;
+mod token;
+mod tokenizer;
+mod trigger;
+mod vars;
+mod warning;
 
-    while let Some(ch) = chars.next() {
-        if ch == delim {
-            return Some(output);
-        }
-        output.push(parse_escape(ch, chars)?);
-    }
+pub use args::{split_options, split_short_flags, SplitArgs, TokenKind};
+#[cfg(feature = "tokio")]
+pub use async_reader::AsyncCommands;
+pub use audit::{Hazard, HazardKind};
+#[cfg(feature = "clap")]
+pub use cli::{ClapError, ProgramName};
+#[cfg(feature = "derive")]
+pub use comma_derive::FromCommand;
+pub use command::Command;
+#[cfg(feature = "std")]
+pub use command::CommandArgsExt;
+pub use completion::Cursor;
+pub use continuation::{Completeness, Continuation};
+pub use cow::{RawToken, RawTokens};
+pub use diff::{Change, Mismatch};
+pub use error::{ErrorCode, ErrorKind, Limit, ParseError};
+pub use format::Formatter;
+pub use from_command::{FromCommand, FromCommandError};
+pub use highlight::{Highlight, SEMANTIC_TOKEN_MODIFIERS, SEMANTIC_TOKEN_TYPES};
+pub use incremental::Edit;
+#[cfg(feature = "rustyline")]
+pub use line_editor::LineHelper;
+pub use lines::{LineCommand, LineCommands};
+#[cfg(feature = "unicode-normalization")]
+pub use parser::Normalization;
+#[cfg(feature = "smallvec")]
+pub use parser::SmallTokens;
+pub use parser::{ControlCharacters, Escapes, ForbiddenCharacters, Newlines, Parser};
+pub use parser::{Tokens, TrailingBackslash, TryTokens};
+pub use position::Position;
+pub use quote::{percent_encode, quote};
+#[cfg(feature = "std")]
+pub use reader::{ReadCommands, ReadError};
+#[cfg(feature = "std")]
+pub use response::{expand_response_files, expand_response_files_with, ResponseFileError};
+pub use script::ScriptCommand;
+pub use stream::TokenStream;
+pub use token::{Quoting, Token};
+pub use tokenizer::Tokenizer;
+pub use trigger::{parse_triggered, strip_trigger};
+#[cfg(feature = "std")]
+pub use vars::Env;
+pub use vars::{expand_percent_vars, Variables};
+pub use warning::{Warning, WarningKind};
 
-    None
+/// Items used by the code that `#[derive(FromCommand)]` generates. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::from_command::Args;
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
 }
 
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use core::iter::FromIterator;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::ffi::{OsStr, OsString};
+
 /// Parses a command into a list of individual tokens.
 /// Each token is separated by one or more characters of whitespace.
 /// Pairs of single- or double-quotes can be used to ignore whitespace. Within pairs of quotation
 /// marks, a backslash (\) can be used to escape any character. The special escape sequences
 /// '\n', '\r', and '\t' are also handled as Newlines, Carriage Returns, and Tabs, respectively.
 /// Should a quotation mark be mismatched (no counterpart terminating mark exists), this function
-/// will return None. Otherwise, it returns a list of tokens in the input string.
+/// will return None; [`try_parse_command`] describes the problem instead. Otherwise, it returns a
+/// list of tokens in the input string.
 pub fn parse_command(input: &str) -> Option<Vec<String>> {
-    let mut chars = input.trim_start().chars().peekable();
-    let mut output: Vec<String> = vec![String::new()];
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            ch if ch.is_whitespace() =>
-            // Sequences of whitespace are collapsed and used to make a new vec element.
-            {
-                if !chars.peek().map_or(true, |c| c.is_whitespace()) {
-                    output.push(String::new());
-                }
-            }
-
-            '"' | '\'' => output
-                .last_mut()?
-                .push_str(parse_string(&mut chars, ch)?.as_str()),
-
-            ch => output.last_mut()?.push(parse_escape(ch, &mut chars)?),
-        }
+    try_parse_command(input).ok()
+}
+
+/// Parses a command exactly like [`parse_command`], but describes why malformed input could not
+/// be parsed, and where.
+///
+/// ```
+/// use comma::{try_parse_command, ErrorKind};
+///
+/// let error = try_parse_command("echo 'hello world").unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('\''));
+/// assert_eq!(error.offset(), 5);
+/// ```
+pub fn try_parse_command(input: &str) -> Result<Vec<String>, ParseError> {
+    let tokens = Parser::new().try_parse(input)?;
+
+    // Blank input has always produced a single empty token.
+    if tokens.is_empty() {
+        return Ok(vec![String::new()]);
+    }
+
+    Ok(tokens)
+}
+
+/// Checks that a command is well-formed, as [`try_parse_command`] would, without building any
+/// of its tokens. See [`Parser::validate`].
+///
+/// ```
+/// use comma::validate;
+///
+/// assert!(validate("cat 'notes.txt'").is_ok());
+/// assert_eq!(validate("cat 'notes.txt").unwrap_err().offset(), 4);
+/// ```
+pub fn validate(input: &str) -> Result<(), ParseError> {
+    Parser::new().validate(input)
+}
+
+/// Determines whether a command is complete, or more lines must be read to finish an open quote
+/// or heredoc. See [`Parser::classify`].
+///
+/// ```
+/// use comma::{classify, Completeness, Continuation};
+///
+/// assert_eq!(classify("ls 'My Documents'"), Completeness::Complete);
+/// assert_eq!(
+///     classify("ls 'My Documents"),
+///     Completeness::NeedsMore(Continuation::Quote('\''))
+/// );
+/// ```
+pub fn classify(input: &str) -> Completeness {
+    Parser::new().classify(input)
+}
+
+/// Counts the tokens of a command without building them, returning None if it is malformed.
+/// Unlike [`parse_command`], blank input has no tokens. See [`Parser::count_tokens`].
+pub fn count_tokens(input: &str) -> Option<usize> {
+    Parser::new().count_tokens(input)
+}
+
+/// Parses only the first token of a command, without reading the rest of it. Returns None if
+/// the input is blank or the first token is malformed. See [`Parser::first_token`].
+pub fn first_token(input: &str) -> Option<Token> {
+    Parser::new().first_token(input)
+}
+
+/// Parses only as far as the token at index `n` of a command, without building the tokens before
+/// it. Returns None if there is no such token, or the input is malformed before it ends. See
+/// [`Parser::nth_token`].
+pub fn nth_token(input: &str, n: usize) -> Option<Token> {
+    Parser::new().nth_token(input, n)
+}
+
+/// Parses the first token of the input, returning it along with the unparsed rest of the input
+/// following any whitespace. Returns None if the input is blank or its first token is malformed.
+/// See [`Parser::parse_one`].
+pub fn parse_one(input: &str) -> Option<(String, &str)> {
+    Parser::new().parse_one(input)
+}
+
+/// Parses a command like [`parse_command`] into at most `max` tokens, the last of which holds the
+/// rest of the input verbatim should there be more. Blank input produces no tokens. See
+/// [`Parser::parse_max`].
+///
+/// ```
+/// use comma::parse_command_max;
+///
+/// let tokens = parse_command_max("kick 'Big Bob' for  spamming!", 3).unwrap();
+/// assert_eq!(tokens, vec!["kick", "Big Bob", "for  spamming!"]);
+/// ```
+pub fn parse_command_max(input: &str, max: usize) -> Option<Vec<String>> {
+    Parser::new().parse_max(input, max)
+}
+
+/// Parses a command like [`try_parse_command`], and builds a [`std::process::Command`] that runs
+/// it, ready to spawn. Fails if the input is malformed, or with [`ErrorKind::EmptyCommand`] if it
+/// is blank. See [`Parser::process_command`].
+///
+/// ```no_run
+/// let status = comma::process_command("rustc --version")?.status()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "std")]
+pub fn process_command(input: &str) -> Result<std::process::Command, ParseError> {
+    Parser::new().process_command(input)
+}
+
+/// Returns an iterator over the commands read from `reader`, one per logical line. Lines continue
+/// while a quote is left open or they end in a backslash, and lines without tokens are skipped.
+/// See [`Parser::commands_from_reader`].
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let script = BufReader::new(File::open("setup.txt")?);
+/// for command in comma::parse_commands_from_reader(script) {
+///     println!("{:?}", command?);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "std")]
+pub fn parse_commands_from_reader<R: std::io::BufRead>(reader: R) -> ReadCommands<R> {
+    Parser::new()
+        .trailing_backslash(TrailingBackslash::Continue)
+        .commands_from_reader(reader)
+}
+
+/// Returns a [`Stream`](futures_core::Stream) of the commands read from `reader`, such as a
+/// network connection, one per logical line as with [`parse_commands_from_reader`]. See
+/// [`Parser::commands_from_async_reader`].
+///
+/// Streams are usually consumed with `StreamExt::next` from the `futures` crate; this example
+/// polls the stream directly instead.
+///
+/// ```
+/// use futures_core::Stream;
+/// use std::future::poll_fn;
+/// use std::pin::Pin;
+/// use tokio::io::AsyncBufRead;
+///
+/// async fn serve(connection: impl AsyncBufRead + Unpin) -> Result<(), comma::ReadError> {
+///     let mut commands = comma::parse_commands_from_async_reader(connection);
+///     while let Some(command) = poll_fn(|cx| Pin::new(&mut commands).poll_next(cx)).await {
+///         println!("{:?}", command?);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub fn parse_commands_from_async_reader<R>(reader: R) -> AsyncCommands<R>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    Parser::new()
+        .trailing_backslash(TrailingBackslash::Continue)
+        .commands_from_async_reader(reader)
+}
+
+/// Parses a platform string, which need not be valid Unicode, into platform string tokens.
+/// Blank input produces no tokens. See [`Parser::parse_os`].
+///
+/// ```
+/// # #[cfg(unix)]
+/// # {
+/// use comma::parse_command_os;
+/// use std::ffi::OsStr;
+/// use std::os::unix::ffi::OsStrExt;
+///
+/// let tokens = parse_command_os(OsStr::from_bytes(b"cat 'caf\xe9 menu.txt'")).unwrap();
+/// assert_eq!(tokens[1].as_bytes(), b"caf\xe9 menu.txt");
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn parse_command_os(input: &OsStr) -> Result<Vec<OsString>, ParseError> {
+    Parser::new().parse_os(input)
+}
+
+/// Parses a command and feeds its tokens to clap, inserting the clap command's name before them,
+/// as REPL input usually omits it. See [`Parser::try_parse_clap`].
+#[cfg(feature = "clap")]
+pub fn try_parse_clap<T: clap::Parser>(input: &str) -> Result<T, ClapError> {
+    Parser::new().try_parse_clap(input, ProgramName::CommandName)
+}
+
+/// Parses bytes that need not be valid UTF-8 into byte string tokens. Only ASCII whitespace,
+/// quotation marks and backslashes are special; every other byte is passed through untouched.
+/// Blank input produces no tokens. See [`Parser::parse_bytes`].
+///
+/// ```
+/// use comma::parse_command_bytes;
+///
+/// let tokens = parse_command_bytes(b"log caf\xe9\xc2\xa0bar").unwrap();
+/// assert_eq!(tokens, vec![&b"log"[..], b"caf\xe9\xc2\xa0bar"]);
+/// ```
+pub fn parse_command_bytes(input: &[u8]) -> Result<Vec<Vec<u8>>, ParseError> {
+    Parser::new().ascii_whitespace(true).parse_bytes(input)
+}
+
+/// Parses a command like [`parse_command`], but tokens holding no quotes or escapes borrow their
+/// text from `input` instead of being copied. See [`Parser::parse_cow`].
+///
+/// ```
+/// use comma::parse_command_cow;
+/// use std::borrow::Cow;
+///
+/// let tokens = parse_command_cow("grep -n 'fn main' src").unwrap();
+/// assert_eq!(tokens, ["grep", "-n", "fn main", "src"]);
+/// assert!(matches!(tokens[0], Cow::Borrowed("grep")));
+/// assert!(matches!(tokens[2], Cow::Owned(_)));
+/// ```
+pub fn parse_command_cow(input: &str) -> Option<Vec<Cow<'_, str>>> {
+    Parser::new().parse_cow(input)
+}
+
+/// Parses a command like [`parse_command`], but collects each token into the string type `S`
+/// rather than a `String`. See [`Parser::parse_as`].
+///
+/// ```
+/// use comma::parse_command_as;
+///
+/// let tokens = parse_command_as::<Box<str>>("echo \"hello world\"").unwrap();
+/// assert_eq!(&*tokens[1], "hello world");
+/// ```
+pub fn parse_command_as<S: FromIterator<char>>(input: &str) -> Option<Vec<S>> {
+    Parser::new().parse_as(input)
+}
+
+/// Parses a command like [`try_parse_command`], but replaces the contents of `tokens` with its
+/// tokens instead of returning a new vector. See [`Parser::parse_into`].
+///
+/// ```
+/// use comma::parse_command_into;
+///
+/// let mut tokens = Vec::new();
+/// for line in ["mv a b", "rm 'c d'"] {
+///     parse_command_into(line, &mut tokens)?;
+/// }
+/// assert_eq!(tokens, ["rm", "c d"]);
+/// # Ok::<(), comma::ParseError>(())
+/// ```
+pub fn parse_command_into(input: &str, tokens: &mut Vec<String>) -> Result<(), ParseError> {
+    Parser::new().parse_into(input, tokens)
+}
+
+/// Parses a command like [`parse_command`], but returns the tokens in a [`SmallTokens`], which
+/// holds up to eight tokens without allocating. See [`Parser::parse_small`].
+#[cfg(feature = "smallvec")]
+pub fn parse_command_small(input: &str) -> Option<SmallTokens> {
+    Parser::new().parse_small(input)
+}
+
+/// Parses a command like [`parse_command`], but allocates its tokens in `bump`. See
+/// [`Parser::parse_in`].
+#[cfg(feature = "bumpalo")]
+pub fn parse_command_in<'b>(input: &str, bump: &'b bumpalo::Bump) -> Option<&'b [&'b str]> {
+    Parser::new().parse_in(input, bump)
+}
+
+/// Parses a command like [`try_parse_command`], but adds each token to `collection` as it is
+/// parsed. See [`Parser::parse_extend`].
+pub fn parse_command_extend<E>(input: &str, collection: &mut E) -> Result<(), ParseError>
+where
+    E: Extend<String>,
+{
+    Parser::new().parse_extend(input, collection)
+}
+
+/// Parses a command like [`parse_command_into`], but reuses the strings already in `tokens` to
+/// hold the new tokens. See [`Parser::parse_reusing`].
+pub fn parse_command_reusing(input: &str, tokens: &mut Vec<String>) -> Result<(), ParseError> {
+    Parser::new().parse_reusing(input, tokens)
+}
+
+/// Parses a command without allocating, writing its tokens into `buffer` and `tokens`, and
+/// returning how many there are. Blank input produces no tokens. Fails with
+/// [`ErrorKind::CapacityExceeded`] if either buffer is too small. See [`Parser::parse_fixed`].
+///
+/// ```
+/// use comma::parse_command_fixed;
+///
+/// let mut buffer = [0; 64];
+/// let mut tokens = [""; 8];
+/// let count = parse_command_fixed("set led \"on\" 50%", &mut buffer, &mut tokens).unwrap();
+/// assert_eq!(tokens[..count], ["set", "led", "on", "50%"]);
+/// ```
+pub fn parse_command_fixed<'b>(
+    input: &str,
+    buffer: &'b mut [u8],
+    tokens: &mut [&'b str],
+) -> Result<usize, ParseError> {
+    Parser::new().parse_fixed(input, buffer, tokens)
+}
+
+/// Parses a wide Windows command line, such as that returned by `GetCommandLineW`, into
+/// platform string tokens without any lossy conversion. Blank input produces no tokens. See
+/// [`Parser::parse_wide`], which is available on every platform.
+#[cfg(all(windows, feature = "std"))]
+pub fn parse_command_wide(input: &[u16]) -> Result<Vec<OsString>, ParseError> {
+    use std::os::windows::ffi::OsStringExt;
+    let tokens = Parser::new().parse_wide(input)?;
+    Ok(tokens
+        .iter()
+        .map(|token| OsString::from_wide(token))
+        .collect())
+}
+
+/// Parses a command like [`parse_command`], but never fails. Alongside the tokens, returns every
+/// problem found in the input, which is empty if the input is well-formed. Should a quote be
+/// unterminated, it is treated as closed by the end of the input, so the partial token it began
+/// is still returned. See [`Parser::parse_lossy`].
+///
+/// ```
+/// use comma::parse_command_lossy;
+///
+/// let (tokens, errors) = parse_command_lossy("echo \"unfinished business");
+/// assert_eq!(tokens, vec!["echo", "unfinished business"]);
+/// assert_eq!(errors[0].offset(), 5);
+/// ```
+pub fn parse_command_lossy(input: &str) -> (Vec<String>, Vec<ParseError>) {
+    let (tokens, errors) = Parser::new().parse_lossy(input);
+
+    // Blank input produces a single empty token, as it does for `parse_command`.
+    if tokens.is_empty() && errors.is_empty() {
+        return (vec![String::new()], errors);
     }
 
-    Some(output)
+    (tokens, errors)
+}
+
+/// Parses a command like [`parse_command`], but produces [`Token`]s that describe how each token
+/// was written. Blank input produces no tokens.
+pub fn parse_tokens(input: &str) -> Option<Vec<Token>> {
+    Parser::new().parse_tokens(input)
+}
+
+/// Parses a command like [`try_parse_command`], reporting every shell metacharacter found within
+/// its tokens. See [`Parser::audit`].
+///
+/// ```
+/// use comma::{audit_command, HazardKind};
+///
+/// let hazards = audit_command("ping 'localhost; rm -rf ~'").unwrap();
+/// assert_eq!(hazards[0].kind(), HazardKind::CommandSeparator);
+/// assert_eq!(hazards[0].span(), 15..16);
+/// ```
+pub fn audit_command(input: &str) -> Result<Vec<Hazard>, ParseError> {
+    Parser::new().audit(input)
+}
+
+/// Re-renders a command for safe logging, replacing the value following each of the given
+/// `flags` with `***`. See [`Parser::redact`].
+///
+/// ```
+/// use comma::redact_command;
+///
+/// let result = redact_command("curl -H 'Accept: */*' --token=abc123", &["--token"]);
+/// assert_eq!(result.unwrap(), "curl -H 'Accept: */*' --token=***");
+/// ```
+pub fn redact_command(input: &str, flags: &[&str]) -> Result<String, ParseError> {
+    Parser::new().redact(input, flags)
+}
+
+/// Parses a command like [`try_parse_command`], and writes it back out with single spaces and
+/// minimal, consistent quoting, so that equivalent commands produce equal strings. Blank input
+/// produces an empty string. See [`Parser::canonicalize`].
+///
+/// ```
+/// use comma::normalize;
+///
+/// assert_eq!(normalize("cp  \"notes.txt\"\t'my backup'").unwrap(), "cp notes.txt 'my backup'");
+/// assert_eq!(normalize("cp notes.txt my\\ backup").unwrap(), "cp notes.txt 'my backup'");
+/// ```
+pub fn normalize(input: &str) -> Result<String, ParseError> {
+    Parser::new().canonicalize(input)
+}
+
+/// Compares two commands like [`try_parse_command`] would parse them, ignoring differences in
+/// quoting, escaping and whitespace. Returns None if they are equal, or else the first token at
+/// which they differ. See [`Parser::compare`].
+///
+/// ```
+/// use comma::commands_equal;
+///
+/// assert_eq!(commands_equal("echo \"a b\"", "echo 'a b'").unwrap(), None);
+/// let mismatch = commands_equal("echo a b", "echo 'a b'").unwrap().unwrap();
+/// assert_eq!(mismatch.to_string(), "token 1 differs: \"a\" != \"a b\"");
+/// ```
+pub fn commands_equal(a: &str, b: &str) -> Result<Option<Mismatch>, ParseError> {
+    Parser::new().compare(a, b)
+}
+
+/// Parses two commands like [`try_parse_command`], and lists the tokens added, removed and
+/// changed between them. See [`Parser::diff`].
+///
+/// ```
+/// use comma::{diff_commands, Change};
+///
+/// let changes = diff_commands("make -j4 all", "make -j8 all install").unwrap();
+/// assert_eq!(changes.len(), 2);
+/// assert!(matches!(&changes[1], Change::Added(token) if token.as_str() == "install"));
+/// ```
+pub fn diff_commands(old: &str, new: &str) -> Result<Vec<Change>, ParseError> {
+    Parser::new().diff(old, new)
+}
+
+/// Parses a command like [`parse_command`], pairing each token with the half-open byte range of
+/// the input it came from, including any quotes and escapes, so that tokens can be mapped back to
+/// the input. Blank input produces no tokens.
+///
+/// ```
+/// use comma::parse_command_spanned;
+///
+/// let result = parse_command_spanned("say \"hi there\"").unwrap();
+/// assert_eq!(result[1], (String::from("hi there"), 4..14));
+/// ```
+pub fn parse_command_spanned(input: &str) -> Option<Vec<(String, Range<usize>)>> {
+    Parser::new().parse_spanned(input)
+}
+
+/// Parses a command like [`parse_command`], pairing each token with a [`TokenKind`] describing
+/// whether it is a flag, a positional argument, or an operator. Blank input produces no tokens.
+pub fn parse_classified(input: &str) -> Option<Vec<(TokenKind, String)>> {
+    Parser::new().parse_classified(input)
+}
+
+/// Parses a game-console command line, in which words prefixed with `+` begin chained commands,
+/// into a list of commands. Each `+` is removed, leaving the command's name as its first token.
+/// Any tokens preceding the first `+` word form a leading command of their own. Quoted words,
+/// and a `+` on its own, never begin a command. Blank input produces no commands.
+///
+/// ```
+/// use comma::parse_plus_commands;
+///
+/// let commands = parse_plus_commands("+set name 'Big Bob' +map e1m1").unwrap();
+/// assert_eq!(commands, vec![vec!["set", "name", "Big Bob"], vec!["map", "e1m1"]]);
+/// ```
+pub fn parse_plus_commands(input: &str) -> Option<Vec<Vec<String>>> {
+    Parser::new().parse_plus_commands(input)
+}
+
+/// Parses a command like [`parse_command`], first rewriting its leading word using `resolve`.
+/// Should `resolve` return a replacement for an unquoted first word, the replacement is substituted
+/// into the input verbatim, so it may contain quotes and multiple words, and its own first word
+/// is then resolved in turn. An alias is never expanded twice, so aliases may refer to commands of
+/// the same name, and cycles terminate. Blank input produces no tokens.
+///
+/// ```
+/// use comma::parse_with_aliases;
+///
+/// let aliases = |name: &str| match name {
+///     "ll" => Some(String::from("ls -l")),
+///     "ls" => Some(String::from("ls --color=auto")),
+///     _ => None,
+/// };
+/// let result = parse_with_aliases("ll \"My Documents\"", aliases).unwrap();
+/// assert_eq!(result, vec!["ls", "--color=auto", "-l", "My Documents"]);
+/// ```
+pub fn parse_with_aliases<F>(input: &str, resolve: F) -> Option<Vec<String>>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    Parser::new().parse_with_aliases(input, resolve)
+}
+
+/// Parses input containing several commands, separated by unquoted newlines or semicolons.
+/// Each command is returned alongside the line on which it begins; empty commands are skipped.
+/// Returns None if any command is malformed.
+pub fn parse_script(input: &str) -> Option<Vec<ScriptCommand>> {
+    Parser::new().parse_script(input)
+}
+
+/// Parses a command list into a tree of [`ast::Node`]s. Commands are separated by unquoted
+/// newlines or semicolons, and may be grouped within subshell parentheses, e.g. `(cd /tmp; ls)`, or
+/// braces, e.g. `{ ls; pwd; }`. Returns None if the input is malformed or a group is unbalanced.
+///
+/// The input is read as [`parse_shell`] would read it without pipes, redirections or
+/// assignments, so that `|`, `&`, `<` and `>` are part of ordinary words, and the resulting
+/// [`ast::List`] is then converted into nodes.
+pub fn parse_ast(input: &str) -> Option<Vec<ast::Node>> {
+    Parser::new().parse_ast(input)
+}
+
+/// Parses a command list written in a subset of POSIX shell syntax into an [`ast::List`].
+/// Commands are separated by unquoted `;`, `&` or newlines, joined by `&&` and `||`, and piped
+/// together with `|`. Each simple command may begin with `NAME=value` assignments, and may
+/// contain redirections such as `<in`, `>out`, `>>log` and `2>&1`. A list may also stand in for a
+/// command when grouped within subshell parentheses, e.g. `(cd /tmp; ls)`, or braces, e.g.
+/// `{ ls; pwd; }`. Returns None if the input is malformed, an operator is missing a command or
+/// target, or a group is unbalanced.
+///
+/// ```
+/// use comma::ast::{Command, Connector};
+/// use comma::parse_shell;
+///
+/// let list = parse_shell("cd build && LANG=C make 2>errors.log | less").unwrap();
+/// let and_or = &list.items[0];
+/// assert_eq!(and_or.rest[0].0, Connector::And);
+///
+/// let make = match &and_or.rest[0].1.commands[0] {
+///     Command::Simple(make) => make,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(make.assignments, vec![(String::from("LANG"), String::from("C"))]);
+/// assert_eq!(make.words, vec!["make"]);
+/// assert_eq!(make.redirects[0].target, "errors.log");
+///
+/// let list = parse_shell("(cd /tmp; ls) | wc").unwrap();
+/// let subshell = &list.items[0].first.commands[0];
+/// assert!(matches!(subshell, Command::Subshell(inner) if inner.items.len() == 2));
+/// ```
+pub fn parse_shell(input: &str) -> Option<ast::List> {
+    Parser::new().parse_shell(input)
+}
+
+/// Parses a command into a lossless [`cst::SyntaxTree`], which keeps the whitespace, comments,
+/// quotes and escapes of the input, so that writing the tree back out reproduces the input
+/// exactly. This never fails: malformed input is parsed as best it can be, with an unterminated
+/// quote extending to the end of the input.
+pub fn parse_cst(input: &str) -> cst::SyntaxTree {
+    Parser::new().parse_cst(input)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parse_command;
+    use crate::ErrorKind;
+    use crate::{
+        first_token, normalize, parse_command, parse_command_lossy, parse_command_spanned,
+    };
+    use crate::{try_parse_command, Parser};
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_deserialize() {
+        use crate::Command;
+        use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+        use serde::de::{Deserialize, IntoDeserializer};
+
+        let config = MapDeserializer::<_, Error>::new(vec![("keep_quotes", true)].into_iter());
+        let parser = Parser::deserialize(config).unwrap();
+        assert_eq!(
+            parser.parse("a 'b'"),
+            Some(vec![String::from("a"), String::from("'b'")])
+        );
+
+        let tokens: SeqDeserializer<_, Error> = vec!["ls", "My Files"].into_deserializer();
+        let command = Command::deserialize(tokens).unwrap();
+        assert_eq!(command, "ls 'My Files'".parse().unwrap());
+
+        let kind = ErrorKind::deserialize("TrailingBackslash".into_deserializer());
+        assert_eq!(kind, Ok::<_, Error>(ErrorKind::TrailingBackslash));
+    }
 
     #[test]
     fn parsing_works() {
@@ -93,4 +705,51 @@ mod tests {
         let result = parse_command("ß 𱁬").unwrap();
         assert_eq!(result, vec![String::from("ß"), String::from("𱁬")]);
     }
+
+    #[test]
+    fn try_parse_errors() {
+        assert_eq!(try_parse_command("  ").unwrap(), vec![String::new()]);
+        let error = try_parse_command("a \"b\\\"").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+        assert_eq!(error.span(), 2..6);
+        assert_eq!(try_parse_command("a\\").unwrap_err().offset(), 1);
+    }
+
+    #[test]
+    fn spanned() {
+        assert_eq!(parse_command_spanned(" \t"), Some(vec![]));
+        let result = parse_command_spanned("é'ß' x\\\\").unwrap();
+        assert_eq!(
+            result,
+            vec![(String::from("éß"), 0..6), (String::from("x\\"), 7..10)]
+        );
+        assert_eq!(parse_command_spanned("'"), None);
+    }
+
+    #[test]
+    fn lossy() {
+        assert_eq!(parse_command_lossy(" "), (vec![String::new()], Vec::new()));
+        let (tokens, errors) = parse_command_lossy("a 'b\\' c\\");
+        assert_eq!(tokens, vec!["a", "b' c\\"]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].kind(), ErrorKind::TrailingBackslash);
+    }
+
+    #[test]
+    fn normalized() {
+        assert_eq!(normalize(" \t").unwrap(), "");
+        for input in ["a\\'b \"\" 'c\\d' \"e'f\"", "x\\ y  'é'", "'\\\"' \\\\"] {
+            let normalized = normalize(input).unwrap();
+            assert_eq!(normalize(&normalized).unwrap(), normalized);
+            assert_eq!(Parser::new().parse(&normalized), Parser::new().parse(input));
+        }
+        assert!(normalize("'").is_err());
+    }
+
+    #[test]
+    fn first() {
+        assert_eq!(first_token(" \t"), None);
+        assert_eq!(first_token("'a"), None);
+        assert_eq!(first_token("a\\ b 'c").unwrap().as_str(), "a b");
+    }
 }
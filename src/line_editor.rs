@@ -0,0 +1,205 @@
+//! A [`rustyline`] helper that validates, highlights and completes commands as they are typed.
+
+use crate::{Cursor, Highlight, Parser};
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+
+/// A [`rustyline::Helper`] for reading commands: pressing enter while [`Parser::classify`] finds
+/// the command unfinished continues it on a new line; quotes, escapes, operators and comments
+/// are coloured as [`Parser::highlight`] classifies them; and completion candidates are escaped
+/// to suit the token at the cursor.
+///
+/// ```no_run
+/// use comma::{LineHelper, Parser};
+/// use rustyline::history::DefaultHistory;
+/// use rustyline::Editor;
+///
+/// let commands = ["checkout", "commit", "status"];
+/// let helper = LineHelper::new(Parser::new()).with_completions(move |cursor| {
+///     let names = commands.iter().filter(|name| name.starts_with(&cursor.prefix));
+///     names.map(|name| name.to_string()).collect()
+/// });
+///
+/// let mut editor = Editor::<_, DefaultHistory>::new()?;
+/// editor.set_helper(Some(helper));
+/// let line = editor.readline("> ")?;
+/// let tokens = Parser::new().try_parse(&line)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct LineHelper<F = fn(&Cursor) -> Vec<String>> {
+    parser: Parser,
+    candidates: F,
+}
+
+impl LineHelper {
+    /// Creates a helper that reads commands as `parser` parses them, offering no completions.
+    pub fn new(parser: Parser) -> Self {
+        LineHelper {
+            parser,
+            candidates: |_| Vec::new(),
+        }
+    }
+}
+
+impl<F> LineHelper<F> {
+    /// Completes the token at the cursor with the values `candidates` returns for it. They are
+    /// given unescaped, usually filtered by the cursor's [`prefix`](Cursor::prefix), and replace
+    /// the whole token up to the cursor once escaped with [`Cursor::escape`].
+    pub fn with_completions<G>(self, candidates: G) -> LineHelper<G>
+    where
+        G: Fn(&Cursor) -> Vec<String>,
+    {
+        LineHelper {
+            parser: self.parser,
+            candidates,
+        }
+    }
+
+    /// The parser the helper reads commands with.
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    fn validation(&self, input: &str) -> ValidationResult {
+        if !self.parser.classify(input).is_complete() {
+            return ValidationResult::Incomplete;
+        }
+        match self.parser.validate(input) {
+            Ok(()) => ValidationResult::Valid(None),
+            Err(error) => ValidationResult::Invalid(Some(format!(" ({})", error))),
+        }
+    }
+}
+
+/// The ANSI escape sequence colouring each kind of span, if it is coloured at all.
+fn style(highlight: Highlight) -> Option<&'static str> {
+    match highlight {
+        Highlight::Quoted => Some("\x1b[32m"),
+        Highlight::QuoteDelimiter => Some("\x1b[1;32m"),
+        Highlight::Escape => Some("\x1b[36m"),
+        Highlight::Operator => Some("\x1b[35m"),
+        Highlight::Comment => Some("\x1b[2m"),
+        _ => None,
+    }
+}
+
+impl<F> Validator for LineHelper<F> {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(self.validation(ctx.input()))
+    }
+}
+
+impl<F> Highlighter for LineHelper<F> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut highlighted = String::with_capacity(line.len());
+        let mut coloured = false;
+        for (span, highlight) in self.parser.highlight(line) {
+            match style(highlight) {
+                Some(style) => {
+                    highlighted.push_str(style);
+                    highlighted.push_str(&line[span]);
+                    highlighted.push_str("\x1b[0m");
+                    coloured = true;
+                }
+                None => highlighted.push_str(&line[span]),
+            }
+        }
+        if coloured {
+            Cow::Owned(highlighted)
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        // Any character can open or close a quote, changing the colour of everything after it.
+        true
+    }
+}
+
+impl<F: Fn(&Cursor) -> Vec<String>> Completer for LineHelper<F> {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let cursor = self.parser.cursor(line, pos);
+        let quote = cursor.quote.map(String::from).unwrap_or_default();
+        let pairs = (self.candidates)(&cursor)
+            .into_iter()
+            .map(|candidate| Pair {
+                replacement: format!("{}{}", quote, cursor.escape(&candidate)),
+                display: candidate,
+            })
+            .collect();
+        Ok((cursor.span.start, pairs))
+    }
+}
+
+impl<F> Hinter for LineHelper<F> {
+    type Hint = String;
+}
+
+impl<F: Fn(&Cursor) -> Vec<String>> Helper for LineHelper<F> {}
+
+#[cfg(test)]
+mod tests {
+    use super::LineHelper;
+    use crate::Parser;
+    use rustyline::completion::Completer;
+    use rustyline::highlight::Highlighter;
+    use rustyline::history::MemHistory;
+    use rustyline::validate::ValidationResult;
+    use rustyline::Context;
+
+    #[test]
+    fn reads_commands() {
+        let helper = LineHelper::new(Parser::new());
+        assert!(matches!(
+            helper.validation("echo 'a"),
+            ValidationResult::Incomplete
+        ));
+        assert!(matches!(
+            helper.validation("cat <<EOF\nx"),
+            ValidationResult::Incomplete
+        ));
+        assert!(matches!(
+            helper.validation("echo a\\"),
+            ValidationResult::Invalid(Some(_))
+        ));
+        assert!(matches!(
+            helper.validation("echo 'a\nb'"),
+            ValidationResult::Valid(None)
+        ));
+
+        assert_eq!(helper.highlight("ls a", 0), "ls a");
+        assert_eq!(
+            helper.highlight("ls 'a'", 0),
+            "ls \x1b[1;32m'\x1b[0m\x1b[32ma\x1b[0m\x1b[1;32m'\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn completes_tokens() {
+        let helper = LineHelper::new(Parser::new())
+            .with_completions(|cursor| vec![format!("{} Files", cursor.prefix)]);
+        let history = MemHistory::new();
+        let context = Context::new(&history);
+
+        let (start, pairs) = helper.complete("cd My", 5, &context).unwrap();
+        assert_eq!(start, 3);
+        assert_eq!(pairs[0].replacement, "My\\ Files");
+        assert_eq!(pairs[0].display, "My Files");
+
+        let (start, pairs) = helper.complete("cd 'My", 6, &context).unwrap();
+        assert_eq!(start, 3);
+        assert_eq!(pairs[0].replacement, "'My Files");
+    }
+}
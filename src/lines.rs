@@ -0,0 +1,108 @@
+//! Splitting of input into commands, one per logical line.
+
+use crate::prelude::*;
+use crate::{ParseError, Parser};
+use core::ops::{Range, RangeInclusive};
+
+/// A command read from one logical line of input. See [`Parser::line_commands`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineCommand {
+    /// The 1-based lines of the input that the command spans.
+    pub lines: RangeInclusive<usize>,
+    /// The byte range of the input holding the command, excluding its final line terminator.
+    pub span: Range<usize>,
+    /// The command's tokens, or the reason it is malformed, with offsets into the whole input.
+    pub tokens: Result<Vec<String>, ParseError>,
+}
+
+/// An iterator over the commands in some input, one per logical line. See
+/// [`Parser::line_commands`].
+pub struct LineCommands<'p, 'a> {
+    parser: &'p Parser,
+    input: &'a str,
+    /// The byte offset of the next line.
+    offset: usize,
+    /// The number of lines read so far.
+    line: usize,
+}
+
+impl<'p, 'a> LineCommands<'p, 'a> {
+    pub(crate) fn new(parser: &'p Parser, input: &'a str) -> Self {
+        Self {
+            parser,
+            input,
+            offset: 0,
+            line: 0,
+        }
+    }
+}
+
+impl Iterator for LineCommands<'_, '_> {
+    type Item = LineCommand;
+
+    fn next(&mut self) -> Option<LineCommand> {
+        while self.offset < self.input.len() {
+            let start = self.offset;
+            let first = self.line + 1;
+
+            // Lines are joined until they form a whole command, or the input runs out.
+            let end = loop {
+                let rest = &self.input[self.offset..];
+                let line_end = rest
+                    .find('\n')
+                    .map_or(self.input.len(), |at| self.offset + at);
+                self.line += 1;
+                self.offset = line_end + 1;
+
+                let end = match self.input[..line_end].strip_suffix('\r') {
+                    Some(line) => line.len(),
+                    None => line_end,
+                };
+                let text = &self.input[start..end];
+                if self.offset >= self.input.len() || self.parser.classify(text).is_complete() {
+                    break end;
+                }
+            };
+
+            let tokens = self.parser.try_parse(&self.input[start..end]);
+            if tokens.as_ref().map_or(false, Vec::is_empty) {
+                continue;
+            }
+            return Some(LineCommand {
+                lines: first..=self.line,
+                span: start..end,
+                tokens: tokens.map_err(|error| error.shifted(start)),
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, Parser, TrailingBackslash};
+
+    #[test]
+    fn joins_lines() {
+        let input = "cd /tmp\r\n\n  # note\necho 'a\n\nb' \\\n  c\nrm \"x\n";
+        let parser = Parser::new()
+            .comment("#")
+            .trailing_backslash(TrailingBackslash::Continue);
+        let commands: Vec<_> = parser.line_commands(input).collect();
+        assert_eq!(commands.len(), 3);
+
+        assert_eq!(commands[0].lines, 1..=1);
+        assert_eq!(commands[0].span, 0..7);
+        assert_eq!(commands[1].lines, 4..=7);
+        assert_eq!(&input[commands[1].span.clone()], "echo 'a\n\nb' \\\n  c");
+        assert_eq!(
+            commands[1].tokens,
+            Ok(vec!["echo".into(), "a\n\nb".into(), "c".into()])
+        );
+
+        assert_eq!(commands[2].lines, 8..=8);
+        let error = commands[2].tokens.clone().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+        assert_eq!(error.offset(), input.len() - 3);
+    }
+}
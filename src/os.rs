@@ -0,0 +1,179 @@
+//! Parsing of platform strings and bytes, which need not be valid Unicode.
+//!
+//! The lexer works on `str`, so units of the input that are not valid Unicode are each
+//! substituted by a character in a reserved range of the last private use plane, which the
+//! lexer treats as ordinary token text, and are restored in the tokens it produces. Characters
+//! of the input that fall within the reserved range are themselves written as substitutes for
+//! each of their units, so that restoring the tokens is never ambiguous.
+
+use crate::prelude::*;
+use crate::{ParseError, Parser};
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::ffi::{OsStr, OsString};
+
+/// The unit `u` is substituted by the character `BASE + u`. Only units within 0x80..=0xDFFF ever
+/// need substituting, so the reserved range is U+102080..=U+10FFFF.
+const BASE: u32 = 0x10F800 - 0xD800;
+
+fn substitute(unit: u16) -> char {
+    char::from_u32(BASE + u32::from(unit)).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+fn is_reserved(ch: char) -> bool {
+    u32::from(ch) >= BASE + 0x80
+}
+
+fn restore(ch: char) -> Option<u16> {
+    if is_reserved(ch) {
+        u16::try_from(u32::from(ch) - BASE).ok()
+    } else {
+        None
+    }
+}
+
+/// Converts bytes to a string, substituting each byte that is not part of valid UTF-8.
+pub(crate) fn decode_bytes(mut bytes: &[u8]) -> String {
+    let mut decoded = String::with_capacity(bytes.len());
+    while !bytes.is_empty() {
+        let (valid, invalid) = match core::str::from_utf8(bytes) {
+            Ok(valid) => (valid, 0),
+            Err(error) => {
+                let valid = &bytes[..error.valid_up_to()];
+                let invalid = error.error_len().unwrap_or(bytes.len() - valid.len());
+                (core::str::from_utf8(valid).unwrap_or_default(), invalid)
+            }
+        };
+        for ch in valid.chars() {
+            if is_reserved(ch) {
+                let mut buffer = [0; 4];
+                decoded.extend(
+                    ch.encode_utf8(&mut buffer)
+                        .bytes()
+                        .map(u16::from)
+                        .map(substitute),
+                );
+            } else {
+                decoded.push(ch);
+            }
+        }
+        let rest = &bytes[valid.len()..];
+        decoded.extend(
+            rest[..invalid]
+                .iter()
+                .map(|&byte| substitute(u16::from(byte))),
+        );
+        bytes = &rest[invalid..];
+    }
+    decoded
+}
+
+/// Converts a string produced from [`decode_bytes`] input back to bytes.
+pub(crate) fn encode_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for ch in text.chars() {
+        match restore(ch).and_then(|unit| u8::try_from(unit).ok()) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Converts UTF-16 to a string, substituting each unpaired surrogate.
+pub(crate) fn decode_wide(units: &[u16]) -> String {
+    let mut decoded = String::with_capacity(units.len());
+    for result in char::decode_utf16(units.iter().copied()) {
+        match result {
+            Ok(ch) if is_reserved(ch) => {
+                let mut buffer = [0; 2];
+                decoded.extend(ch.encode_utf16(&mut buffer).iter().copied().map(substitute));
+            }
+            Ok(ch) => decoded.push(ch),
+            Err(error) => decoded.push(substitute(error.unpaired_surrogate())),
+        }
+    }
+    decoded
+}
+
+/// Converts a string produced from [`decode_wide`] input back to UTF-16.
+pub(crate) fn encode_wide(text: &str) -> Vec<u16> {
+    let mut units = Vec::with_capacity(text.len());
+    for ch in text.chars() {
+        match restore(ch) {
+            Some(unit) => units.push(unit),
+            None => units.extend_from_slice(ch.encode_utf16(&mut [0; 2])),
+        }
+    }
+    units
+}
+
+pub(crate) fn parse_bytes(parser: &Parser, input: &[u8]) -> Result<Vec<Vec<u8>>, ParseError> {
+    let tokens = parser.try_parse(&decode_bytes(input))?;
+    Ok(tokens.iter().map(|token| encode_bytes(token)).collect())
+}
+
+pub(crate) fn parse_wide(parser: &Parser, input: &[u16]) -> Result<Vec<Vec<u16>>, ParseError> {
+    let tokens = parser.try_parse(&decode_wide(input))?;
+    Ok(tokens.iter().map(|token| encode_wide(token)).collect())
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn parse(parser: &Parser, input: &OsStr) -> Result<Vec<OsString>, ParseError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        let tokens = parser.try_parse(&decode_bytes(input.as_bytes()))?;
+        Ok(tokens
+            .iter()
+            .map(|token| OsString::from_vec(encode_bytes(token)))
+            .collect())
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+        let units: Vec<u16> = input.encode_wide().collect();
+        let tokens = parse_wide(parser, &units)?;
+        Ok(tokens
+            .iter()
+            .map(|token| OsString::from_wide(token))
+            .collect())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let tokens = parser.try_parse(&input.to_string_lossy())?;
+        Ok(tokens.into_iter().map(OsString::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+
+    #[test]
+    fn lossless_bytes() {
+        let input = b"a\xff'b \xc3' \xf4\x8f\xbf\xbf\\ c\xe2\x82";
+        let tokens = Parser::new().parse_bytes(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                b"a\xffb \xc3".to_vec(),
+                b"\xf4\x8f\xbf\xbf c\xe2\x82".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless_wide() {
+        let mut input: Vec<u16> = "x\"".encode_utf16().collect();
+        input.extend_from_slice(&[0xDC00, 0xDBFF, 0xDFFF, 0x20, 0xD800]);
+        input.extend("\" y".encode_utf16());
+        let tokens = Parser::new().parse_wide(&input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![vec![0x78, 0xDC00, 0xDBFF, 0xDFFF, 0x20, 0xD800], vec![0x79]]
+        );
+    }
+}
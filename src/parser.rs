@@ -0,0 +1,3071 @@
+//! The configurable tokenizer behind [`parse_command`](crate::parse_command).
+
+use crate::alias;
+use crate::args::{chain, classify};
+use crate::ast::{self, Node};
+use crate::audit;
+#[cfg(feature = "clap")]
+use crate::cli::{self, ClapError, ProgramName};
+use crate::completion;
+use crate::continuation::{self, Completeness};
+use crate::cow::{self, RawTokens};
+use crate::cst::{self, SyntaxTree};
+use crate::diff;
+use crate::fixed;
+use crate::highlight;
+use crate::incremental::{self, Edit};
+use crate::os;
+use crate::prelude::*;
+use crate::quote::{percent_decode, quote};
+use crate::redact;
+use crate::script;
+#[cfg(feature = "tokio")]
+use crate::AsyncCommands;
+#[cfg(feature = "std")]
+use crate::ReadCommands;
+use crate::{strip_trigger, Cursor, Quoting, ScriptCommand, Token, TokenKind, TokenStream};
+use crate::{Change, ErrorKind, Hazard, Limit, Mismatch, ParseError};
+use crate::{Highlight, Warning, WarningKind};
+use crate::{LineCommands, Tokenizer};
+use alloc::borrow::Cow;
+#[cfg(feature = "bumpalo")]
+use bumpalo::Bump;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Range;
+use core::str::CharIndices;
+#[cfg(feature = "std")]
+use std::ffi::{OsStr, OsString};
+#[cfg(feature = "std")]
+use std::io::BufRead;
+#[cfg(feature = "std")]
+use std::process;
+
+/// A configurable command parser.
+///
+/// A default `Parser` splits input exactly like [`parse_command`](crate::parse_command), except
+/// that blank input produces no tokens at all. The builder methods switch on alternative
+/// behaviour:
+///
+/// ```
+/// use comma::Parser;
+///
+/// let parser = Parser::new().keep_quotes(true);
+/// let tokens = parser.parse("ssh host 'ls -la' \"a\\\"b\"").unwrap();
+/// assert_eq!(tokens, vec!["ssh", "host", "'ls -la'", "\"a\\\"b\""]);
+/// ```
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Parser {
+    pub(crate) keep_quotes: bool,
+    comments: Vec<String>,
+    pub(crate) keep_comments: bool,
+    pub(crate) trailing_backslash: TrailingBackslash,
+    separators: Separators,
+    control_characters: ControlCharacters,
+    forbidden: Vec<char>,
+    forbidden_characters: ForbiddenCharacters,
+    pub(crate) split_long_options: bool,
+    strict_escapes: bool,
+    escapes: Escapes,
+    literal_quotes: bool,
+    escape_sequences: Vec<(char, String)>,
+    percent_decode: bool,
+    newlines: Newlines,
+    strip_bom: bool,
+    auto_close_quotes: bool,
+    pub(crate) max_token_length: Option<usize>,
+    pub(crate) max_input_length: Option<usize>,
+    pub(crate) max_tokens: Option<usize>,
+    pub(crate) max_expansion: Option<usize>,
+    #[cfg(feature = "unicode-normalization")]
+    normalization: Option<Normalization>,
+}
+
+/// The tokens of a command, stored inline when there are no more than eight. See
+/// [`Parser::parse_small`].
+#[cfg(feature = "smallvec")]
+pub type SmallTokens = smallvec::SmallVec<[String; 8]>;
+
+/// A Unicode normalization form applied to each token. See [`Parser::normalize`].
+#[cfg(feature = "unicode-normalization")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Normalization {
+    /// Canonical composition, under which canonically equivalent strings compare equal.
+    Nfc,
+    /// Compatibility composition, which additionally folds compatibility variants such as
+    /// ligatures and full-width forms.
+    Nfkc,
+}
+
+/// How a [`Parser`] treats control characters, other than tabs, newlines and carriage returns,
+/// that appear in its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ControlCharacters {
+    /// Control characters are treated like any other character. This is the default.
+    Allow,
+    /// Control characters are removed from the input before it is parsed.
+    Strip,
+    /// Input containing a control character is malformed, failing with
+    /// [`ErrorKind::ControlCharacter`] at its position.
+    Reject,
+}
+
+impl Default for ControlCharacters {
+    fn default() -> Self {
+        ControlCharacters::Allow
+    }
+}
+
+/// How a [`Parser`] treats the characters given to [`Parser::forbid`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ForbiddenCharacters {
+    /// Input containing a forbidden character is malformed, failing with
+    /// [`ErrorKind::ForbiddenCharacter`] at its position. This is the default.
+    Reject,
+    /// Forbidden characters are removed from the input before it is parsed.
+    Strip,
+    /// Forbidden characters are parsed as usual, but each one in a token is preceded by a
+    /// backslash, so the token can be handed to a shell without the character taking effect.
+    Escape,
+}
+
+impl Default for ForbiddenCharacters {
+    fn default() -> Self {
+        ForbiddenCharacters::Reject
+    }
+}
+
+/// How a [`Parser`] treats unquoted, unescaped newlines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Newlines {
+    /// Newlines separate tokens like any other whitespace. This is the default.
+    Separate,
+    /// A newline ends the command, and any input following it is left unparsed. See
+    /// [`Parser::parse_line`].
+    Terminate,
+    /// Input containing a newline is malformed, failing with [`ErrorKind::UnexpectedNewline`] at
+    /// its position.
+    Reject,
+}
+
+impl Default for Newlines {
+    fn default() -> Self {
+        Newlines::Separate
+    }
+}
+
+/// The characters that separate tokens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Separators {
+    Unicode,
+    Ascii,
+    Custom(Vec<char>),
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators::Unicode
+    }
+}
+
+/// Where a [`Parser`] treats backslashes as escape characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Escapes {
+    /// A backslash escapes the following character, both inside and outside quotes. This is the
+    /// default.
+    Anywhere,
+    /// A backslash is always a literal character. Quotes still group characters, but cannot
+    /// contain their own quotation mark.
+    Never,
+    /// A backslash is only an escape character within double quotes, and only before a double
+    /// quote or another backslash. Elsewhere it is literal, so unquoted Windows paths such as
+    /// `C:\Users\me` are kept intact.
+    DoubleQuoted,
+}
+
+impl Default for Escapes {
+    fn default() -> Self {
+        Escapes::Anywhere
+    }
+}
+
+/// How a [`Parser`] treats a backslash at the very end of its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrailingBackslash {
+    /// The input is malformed. This is the default.
+    Error,
+    /// The backslash is kept as a literal character.
+    Literal,
+    /// The input is incomplete and continues on another line. Parsing fails, but
+    /// [`Parser::needs_continuation`] reports the input as continued. A backslash followed by a
+    /// newline is a line continuation, removed entirely, so the next line can be appended to the
+    /// input after a newline.
+    Continue,
+}
+
+impl Default for TrailingBackslash {
+    fn default() -> Self {
+        TrailingBackslash::Error
+    }
+}
+
+impl Parser {
+    /// Creates a parser with the default behaviour.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// When enabled, tokens retain their quotation marks and backslashes verbatim. Input is
+    /// still only split on unquoted, unescaped whitespace, so each token can be forwarded to
+    /// another shell unchanged.
+    pub fn keep_quotes(mut self, keep: bool) -> Self {
+        self.keep_quotes = keep;
+        self
+    }
+
+    /// Sets how a backslash at the end of the input is treated.
+    ///
+    /// ```
+    /// use comma::{Parser, TrailingBackslash};
+    ///
+    /// let parser = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+    /// let mut line = String::from("echo one \\");
+    /// assert!(parser.needs_continuation(&line));
+    ///
+    /// line.push_str("\ntwo");
+    /// assert_eq!(parser.parse(&line).unwrap(), vec!["echo", "one", "two"]);
+    /// ```
+    pub fn trailing_backslash(mut self, policy: TrailingBackslash) -> Self {
+        self.trailing_backslash = policy;
+        self
+    }
+
+    /// When enabled, only ASCII whitespace (space, tab, newline, carriage return and form feed)
+    /// separates tokens. Other whitespace, such as the non-breaking spaces often pasted from web
+    /// pages, is kept within tokens. Disabling this restores the default of splitting on any
+    /// Unicode whitespace.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().ascii_whitespace(true);
+    /// assert_eq!(parser.parse("open Q1\u{a0}report").unwrap(), vec!["open", "Q1\u{a0}report"]);
+    /// ```
+    pub fn ascii_whitespace(mut self, ascii: bool) -> Self {
+        self.separators = if ascii {
+            Separators::Ascii
+        } else {
+            Separators::Unicode
+        };
+        self
+    }
+
+    /// Separates tokens using only the given characters, rather than whitespace.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().separators(vec![',']);
+    /// assert_eq!(parser.parse("a b,'c,d'").unwrap(), vec!["a b", "c,d"]);
+    /// ```
+    pub fn separators(mut self, separators: impl IntoIterator<Item = char>) -> Self {
+        self.separators = Separators::Custom(separators.into_iter().collect());
+        self
+    }
+
+    /// Normalizes each token to the given Unicode normalization form, or disables normalization if
+    /// None is given. This allows commands typed on systems that produce decomposed characters,
+    /// such as macOS, to compare equal to the same command written elsewhere.
+    ///
+    /// ```
+    /// use comma::{Normalization, Parser};
+    ///
+    /// let parser = Parser::new().normalize(Some(Normalization::Nfc));
+    /// assert_eq!(parser.parse("open cafe\u{301}").unwrap(), vec!["open", "caf\u{e9}"]);
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalize(mut self, normalization: Option<Normalization>) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// When enabled, a byte order mark (U+FEFF) at the very start of the input is ignored, as
+    /// editors on Windows often save one at the start of a file. A byte order mark anywhere else
+    /// fails with [`ErrorKind::ByteOrderMark`] at its position.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let parser = Parser::new().strip_bom(true);
+    /// assert_eq!(parser.parse("\u{feff}make all").unwrap(), vec!["make", "all"]);
+    ///
+    /// let error = parser.try_parse("make \u{feff}all").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::ByteOrderMark);
+    /// assert_eq!(error.offset(), 5);
+    /// ```
+    pub fn strip_bom(mut self, strip: bool) -> Self {
+        self.strip_bom = strip;
+        self
+    }
+
+    /// Sets how control characters in the input are treated. Tabs, newlines, carriage returns and
+    /// any characters that separate tokens are always allowed.
+    ///
+    /// ```
+    /// use comma::{ControlCharacters, ErrorKind, Parser};
+    ///
+    /// let strip = Parser::new().control_characters(ControlCharacters::Strip);
+    /// assert_eq!(strip.parse("rm \u{1b}[2Jfile").unwrap(), vec!["rm", "[2Jfile"]);
+    ///
+    /// let reject = Parser::new().control_characters(ControlCharacters::Reject);
+    /// let error = reject.try_parse("rm \u{1b}[2Jfile").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::ControlCharacter('\u{1b}'));
+    /// assert_eq!(error.offset(), 3);
+    /// ```
+    pub fn control_characters(mut self, policy: ControlCharacters) -> Self {
+        self.control_characters = policy;
+        self
+    }
+
+    /// Forbids the given characters, for parsing commands from untrusted users at a trust
+    /// boundary, and sets how they are neutralized. Characters are forbidden wherever they appear,
+    /// even within quotes or after a backslash. Calling this again replaces the characters
+    /// forbidden before.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, ForbiddenCharacters, Parser};
+    ///
+    /// let reject = Parser::new().forbid(vec![';', '|'], ForbiddenCharacters::Reject);
+    /// let error = reject.try_parse("ls 'a;b'").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::ForbiddenCharacter(';'));
+    /// assert_eq!(error.offset(), 5);
+    ///
+    /// let strip = Parser::new().forbid(vec![';', '|'], ForbiddenCharacters::Strip);
+    /// assert_eq!(strip.parse("ls 'a;b' |x").unwrap(), vec!["ls", "ab", "x"]);
+    ///
+    /// let escape = Parser::new().forbid(vec![';', '|'], ForbiddenCharacters::Escape);
+    /// assert_eq!(escape.parse("ls 'a;b' |x").unwrap(), vec!["ls", "a\\;b", "\\|x"]);
+    /// ```
+    pub fn forbid(
+        mut self,
+        forbidden: impl IntoIterator<Item = char>,
+        policy: ForbiddenCharacters,
+    ) -> Self {
+        self.forbidden = forbidden.into_iter().collect();
+        self.forbidden_characters = policy;
+        self
+    }
+
+    /// Sets where backslashes act as escape characters.
+    ///
+    /// ```
+    /// use comma::{Escapes, Parser};
+    ///
+    /// let parser = Parser::new().escapes(Escapes::Never);
+    /// let result = parser.parse(r#"grep "\d+\s" C:\logs\app.log"#).unwrap();
+    /// assert_eq!(result, vec!["grep", r"\d+\s", r"C:\logs\app.log"]);
+    ///
+    /// let parser = Parser::new().escapes(Escapes::DoubleQuoted);
+    /// let result = parser.parse(r#"copy C:\Users\me "D:\a \"b\" c\\""#).unwrap();
+    /// assert_eq!(result, vec!["copy", r"C:\Users\me", r#"D:\a "b" c\"#]);
+    /// ```
+    pub fn escapes(mut self, escapes: Escapes) -> Self {
+        self.escapes = escapes;
+        self
+    }
+
+    /// When enabled, the end of the input closes any quote left open, as some shells do for
+    /// one-liners, instead of failing with [`ErrorKind::UnterminatedQuote`].
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().auto_close_quotes(true);
+    /// assert_eq!(parser.parse("echo \"it's late").unwrap(), vec!["echo", "it's late"]);
+    /// ```
+    pub fn auto_close_quotes(mut self, close: bool) -> Self {
+        self.auto_close_quotes = close;
+        self
+    }
+
+    /// When enabled, quotation marks are ordinary characters, so only escaping a separator with a
+    /// backslash keeps it within a token, as in Makefiles and some configuration formats.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().literal_quotes(true);
+    /// let result = parser.parse(r#"echo "it's" My\ Documents"#).unwrap();
+    /// assert_eq!(result, vec!["echo", "\"it's\"", "My Documents"]);
+    /// ```
+    pub fn literal_quotes(mut self, literal: bool) -> Self {
+        self.literal_quotes = literal;
+        self
+    }
+
+    /// Sets how unquoted, unescaped newlines are treated. Newlines within quotes, and escaped
+    /// newlines, are always part of a token.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Newlines, Parser};
+    ///
+    /// let parser = Parser::new().newlines(Newlines::Terminate);
+    /// assert_eq!(parser.parse("echo 'a\nb'\nrm -rf /").unwrap(), vec!["echo", "a\nb"]);
+    ///
+    /// let parser = Parser::new().newlines(Newlines::Reject);
+    /// let error = parser.try_parse("echo a\nrm -rf /").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::UnexpectedNewline);
+    /// assert_eq!(error.offset(), 6);
+    /// ```
+    pub fn newlines(mut self, newlines: Newlines) -> Self {
+        self.newlines = newlines;
+        self
+    }
+
+    /// When enabled, `%XX` sequences in each token are decoded as percent-encoded UTF-8, as
+    /// produced by [`percent_encode`](crate::percent_encode), so that tokens can be carried
+    /// through URLs and query parameters. A `%` that does not begin a valid sequence is kept
+    /// literally, as is a token that would not decode to valid UTF-8. Decoding happens after
+    /// quotes and escapes are removed, and is skipped when [`Parser::keep_quotes`] is enabled.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().percent_decode(true);
+    /// let result = parser.parse("open My%20Files 100% '%41'").unwrap();
+    /// assert_eq!(result, vec!["open", "My Files", "100%", "A"]);
+    /// ```
+    pub fn percent_decode(mut self, decode: bool) -> Self {
+        self.percent_decode = decode;
+        self
+    }
+
+    /// When enabled, a backslash may only escape a quotation mark, another backslash, whitespace,
+    /// or one of `n`, `r` and `t`. Any other escape sequence, such as a mistyped `\q`, fails with
+    /// [`ErrorKind::UnknownEscape`] at the backslash rather than producing the escaped character.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let parser = Parser::new().strict_escapes(true);
+    /// assert_eq!(parser.parse(r"a\ b \'c\'").unwrap(), vec!["a b", "'c'"]);
+    ///
+    /// let error = parser.try_parse(r"echo \q").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::UnknownEscape('q'));
+    /// assert_eq!(error.offset(), 5);
+    /// ```
+    pub fn strict_escapes(mut self, strict: bool) -> Self {
+        self.strict_escapes = strict;
+        self
+    }
+
+    /// When enabled, long options written as `--flag=value` are split at the first unquoted `=`
+    /// into separate `--flag` and `value` tokens. Options following a standalone `--` are left
+    /// intact.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().split_long_options(true);
+    /// let result = parser.parse("git log --format='%h %s' --author= -- --x=y").unwrap();
+    /// assert_eq!(result, vec!["git", "log", "--format", "%h %s", "--author", "", "--", "--x=y"]);
+    /// ```
+    pub fn split_long_options(mut self, split: bool) -> Self {
+        self.split_long_options = split;
+        self
+    }
+
+    /// Sets the maximum length, in bytes of input including any quotes and escapes, of a token.
+    /// A longer token fails with [`ErrorKind::TokenTooLong`], spanning as much of it as was read,
+    /// as soon as the limit is passed. This lets servers using a [`Tokenizer`] or
+    /// [`ReadCommands`] bound the input they buffer, so that untrusted input cannot make them hold
+    /// gigabytes for one unterminated quote. There is no maximum by default.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let parser = Parser::new().max_token_length(Some(8));
+    /// assert!(parser.parse("rm 'six ch'").is_some());
+    /// assert!(parser.parse("rm 'seven c'").is_none());
+    ///
+    /// let mut tokenizer = parser.tokenizer();
+    /// tokenizer.feed("echo \"never").unwrap();
+    /// let error = tokenizer.feed(" closed").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::TokenTooLong(8));
+    /// assert_eq!(error.span(), 5..14);
+    /// ```
+    pub fn max_token_length(mut self, max: Option<usize>) -> Self {
+        self.max_token_length = max;
+        self
+    }
+
+    /// Sets the maximum length, in bytes, of the input parsed at once. Longer input fails with
+    /// [`ErrorKind::LimitsExceeded`], spanning the input past the limit, without any of that
+    /// input being read. A [`Tokenizer`] applies the limit to all the input it is fed, and
+    /// [`ReadCommands`] and [`Parser::line_commands`] to each command. Together with
+    /// [`Parser::max_tokens`] and [`Parser::max_expansion`], this bounds the time and memory that
+    /// hostile input can cost. There is no maximum by default.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Limit, Parser};
+    ///
+    /// let parser = Parser::new().max_input_length(Some(8));
+    /// assert!(parser.parse("ls -la ~").is_some());
+    /// let error = parser.try_parse("ls -la ~/src").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::LimitsExceeded(Limit::InputLength));
+    /// assert_eq!(error.span(), 8..12);
+    /// ```
+    pub fn max_input_length(mut self, max: Option<usize>) -> Self {
+        self.max_input_length = max;
+        self
+    }
+
+    /// Sets the maximum number of tokens the input may hold, counting each half of a split long
+    /// option. The first token past the limit fails with [`ErrorKind::LimitsExceeded`], spanning
+    /// that token. There is no maximum by default.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Limit, Parser};
+    ///
+    /// let parser = Parser::new().max_tokens(Some(2));
+    /// assert!(parser.parse("rm a").is_some());
+    /// let error = parser.try_parse("rm a b").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::LimitsExceeded(Limit::TokenCount));
+    /// assert_eq!(error.span(), 5..6);
+    /// ```
+    pub fn max_tokens(mut self, max: Option<usize>) -> Self {
+        self.max_tokens = max;
+        self
+    }
+
+    /// Sets the maximum number of bytes that expansions may produce in total, counting the text of
+    /// each [escape sequence](Parser::escape_sequence) registered, and of each alias resolved by
+    /// [`Parser::parse_with_aliases`]. The expansion that passes the limit fails with
+    /// [`ErrorKind::LimitsExceeded`], spanning the text expanded. There is no maximum by default.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Limit, Parser};
+    ///
+    /// let parser = Parser::new().escape_sequence('x', "0123456789").max_expansion(Some(20));
+    /// assert!(parser.parse(r"echo \x\x").is_some());
+    /// let error = parser.try_parse(r"echo \x\x \x").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::LimitsExceeded(Limit::ExpansionSize));
+    /// assert_eq!(error.span(), 10..12);
+    /// ```
+    pub fn max_expansion(mut self, max: Option<usize>) -> Self {
+        self.max_expansion = max;
+        self
+    }
+
+    /// Whether the value of a token may differ from its text even when it holds no quotes or
+    /// escapes, because characters are stripped or the token is decoded or normalized. Splitting
+    /// long options is not counted.
+    pub(crate) fn rewrites_plain_tokens(&self) -> bool {
+        #[cfg(feature = "unicode-normalization")]
+        if self.normalization.is_some() {
+            return true;
+        }
+        let strips_forbidden =
+            !self.forbidden.is_empty() && self.forbidden_characters != ForbiddenCharacters::Reject;
+        self.control_characters == ControlCharacters::Strip
+            || strips_forbidden
+            || self.percent_decode
+    }
+
+    /// Whether `ch` is a control character subject to [`Parser::control_characters`].
+    fn is_control(&self, ch: char) -> bool {
+        ch.is_control() && !matches!(ch, '\t' | '\n' | '\r') && !self.is_whitespace(ch)
+    }
+
+    /// Whether `ch` separates tokens.
+    pub(crate) fn is_whitespace(&self, ch: char) -> bool {
+        match &self.separators {
+            Separators::Unicode => ch.is_whitespace(),
+            Separators::Ascii => ch.is_ascii_whitespace(),
+            Separators::Custom(separators) => separators.contains(&ch),
+        }
+    }
+
+    /// Whether `rest` begins with a comment introduced by `marker`.
+    fn starts_comment(&self, rest: &str, marker: &str) -> bool {
+        let needs_boundary = marker.chars().last().map_or(false, char::is_alphanumeric);
+        rest.starts_with(marker)
+            && (!needs_boundary
+                || rest[marker.len()..]
+                    .chars()
+                    .next()
+                    .map_or(true, |ch| self.is_whitespace(ch)))
+    }
+
+    /// Registers a marker, such as `#`, `//` or `REM`, that begins a comment extending to the end
+    /// of the line. Markers are only recognized at the start of an unquoted token, and markers
+    /// ending in a letter or digit must also be followed by whitespace, so `echo a#b` and
+    /// `REMOVE` are left alone. Empty markers are ignored.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().comment("//").comment("REM");
+    /// assert_eq!(parser.parse("say hi // greet").unwrap(), vec!["say", "hi"]);
+    /// assert_eq!(parser.parse("REM nothing to see").unwrap(), Vec::<String>::new());
+    /// ```
+    pub fn comment(mut self, marker: impl Into<String>) -> Self {
+        let marker = marker.into();
+        if !marker.is_empty() {
+            self.comments.push(marker);
+        }
+        self
+    }
+
+    /// Registers an escape sequence, so that a backslash followed by `ch` expands to `expansion`.
+    /// Registered sequences take precedence over the built-in `\n`, `\r` and `\t`, and over any
+    /// sequence registered earlier for the same character. They are recognized wherever
+    /// backslashes act as escape characters, and are allowed by [`Parser::strict_escapes`].
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new().escape_sequence('d', "{date}").escape_sequence('s', " ");
+    /// let result = parser.parse(r"backup-\d.tar 'a\sb'").unwrap();
+    /// assert_eq!(result, vec!["backup-{date}.tar", "a b"]);
+    /// ```
+    pub fn escape_sequence(mut self, ch: char, expansion: impl Into<String>) -> Self {
+        self.escape_sequences
+            .retain(|&(existing, _)| existing != ch);
+        self.escape_sequences.push((ch, expansion.into()));
+        self
+    }
+
+    /// When enabled, comments are returned by [`Parser::parse_tokens`] as trivia tokens for which
+    /// [`Token::is_comment`] is true, rather than being discarded. Comments are never included
+    /// in the output of other methods.
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.keep_comments = keep;
+        self
+    }
+
+    /// Whether parsing failed only because the input ends in a backslash, under the
+    /// [`TrailingBackslash::Continue`] policy, so that another line should be read.
+    pub fn needs_continuation(&self, input: &str) -> bool {
+        self.trailing_backslash == TrailingBackslash::Continue
+            && Lexer::new(self, input)
+                .filter_map(Result::err)
+                .any(|error| error.kind() == ErrorKind::TrailingBackslash)
+    }
+
+    /// Determines whether `input` is a whole command, or an interactive shell should show a
+    /// continuation prompt and read another line: because a quote is left open, the input ends
+    /// in a backslash under [`TrailingBackslash::Continue`], or a heredoc introduced by `<<` or
+    /// `<<-` has not been ended by its delimiter. Heredoc bodies are not otherwise parsed, and
+    /// input that is malformed in any other way is complete, so that parsing it reports the
+    /// error.
+    ///
+    /// ```
+    /// use comma::{Completeness, Continuation, Parser};
+    ///
+    /// let parser = Parser::new();
+    /// let mut input = String::from("echo 'one");
+    /// assert_eq!(
+    ///     parser.classify(&input),
+    ///     Completeness::NeedsMore(Continuation::Quote('\''))
+    /// );
+    /// input.push_str("\ntwo' && cat <<EOF");
+    /// assert_eq!(
+    ///     parser.classify(&input),
+    ///     Completeness::NeedsMore(Continuation::Heredoc(String::from("EOF")))
+    /// );
+    /// input.push_str("\nit's here\nEOF");
+    /// assert!(parser.classify(&input).is_complete());
+    /// ```
+    pub fn classify(&self, input: &str) -> Completeness {
+        continuation::classify(self, input)
+    }
+
+    /// Parses a command into a list of tokens, returning None if the input is malformed.
+    pub fn parse(&self, input: &str) -> Option<Vec<String>> {
+        self.try_parse(input).ok()
+    }
+
+    /// Parses a command into a list of tokens, or describes why the input is malformed.
+    pub fn try_parse(&self, input: &str) -> Result<Vec<String>, ParseError> {
+        Lexer::new(self, input)
+            .map(|token| token.map(Token::into_string))
+            .collect()
+    }
+
+    /// Like [`Parser::parse`], but a token holding no quotes or escapes borrows its text from
+    /// `input` rather than being copied, so that parsing plain words allocates nothing but the
+    /// vector. Only the other tokens are built character by character. Every token is copied when
+    /// the parser may rewrite even plain tokens, such as when it splits long options or
+    /// [percent-decodes](Parser::percent_decode) them.
+    pub fn parse_cow<'a>(&self, input: &'a str) -> Option<Vec<Cow<'a, str>>> {
+        self.try_parse_cow(input).ok()
+    }
+
+    /// Like [`Parser::parse_cow`], but describes why malformed input could not be parsed.
+    pub fn try_parse_cow<'a>(&self, input: &'a str) -> Result<Vec<Cow<'a, str>>, ParseError> {
+        let mut tokens = Vec::new();
+        cow::parse(self, input, |token| tokens.push(token))?;
+        Ok(tokens)
+    }
+
+    /// Like [`Parser::parse`], but collects each token into any string type that can be built
+    /// from its characters, such as `Box<str>` or a small-string type, rather than a `String`.
+    /// Tokens holding no quotes or escapes are collected straight from the input, as by
+    /// [`Parser::parse_cow`]; only the others are built in a `String` first.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let tokens: Vec<Box<str>> = parser.parse_as("tar -xf 'my archive.tar'").unwrap();
+    /// assert_eq!(&*tokens[2], "my archive.tar");
+    /// ```
+    pub fn parse_as<S: FromIterator<char>>(&self, input: &str) -> Option<Vec<S>> {
+        self.try_parse_as(input).ok()
+    }
+
+    /// Like [`Parser::parse_as`], but describes why malformed input could not be parsed.
+    pub fn try_parse_as<S: FromIterator<char>>(&self, input: &str) -> Result<Vec<S>, ParseError> {
+        let mut tokens = Vec::new();
+        cow::parse(self, input, |token| tokens.push(token.chars().collect()))?;
+        Ok(tokens)
+    }
+
+    /// Like [`Parser::try_parse`], but replaces the contents of `tokens` with the tokens parsed,
+    /// so that a loop parsing many commands can reuse one vector. On failure, `tokens` holds the
+    /// tokens read before the problem. See [`Parser::parse_reusing`] to also reuse the strings.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let mut tokens = Vec::new();
+    /// for line in ["get 'a key'", "del a"] {
+    ///     parser.parse_into(line, &mut tokens)?;
+    ///     assert_eq!(tokens.len(), 2);
+    /// }
+    /// assert_eq!(tokens, ["del", "a"]);
+    /// # Ok::<(), comma::ParseError>(())
+    /// ```
+    pub fn parse_into(&self, input: &str, tokens: &mut Vec<String>) -> Result<(), ParseError> {
+        tokens.clear();
+        for token in Lexer::new(self, input) {
+            tokens.push(token?.into_string());
+        }
+        Ok(())
+    }
+
+    /// Like [`Parser::parse`], but returns the tokens in a [`SmallTokens`], which holds up to eight
+    /// tokens without allocating, as most commands need.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let tokens = Parser::new().parse_small("kill -9 1234").unwrap();
+    /// assert!(!tokens.spilled());
+    /// assert_eq!(tokens.as_slice(), ["kill", "-9", "1234"]);
+    /// ```
+    #[cfg(feature = "smallvec")]
+    pub fn parse_small(&self, input: &str) -> Option<SmallTokens> {
+        self.try_parse_small(input).ok()
+    }
+
+    /// Like [`Parser::parse_small`], but describes why malformed input could not be parsed.
+    #[cfg(feature = "smallvec")]
+    pub fn try_parse_small(&self, input: &str) -> Result<SmallTokens, ParseError> {
+        let mut tokens = SmallTokens::new();
+        self.parse_extend(input, &mut tokens)?;
+        Ok(tokens)
+    }
+
+    /// Like [`Parser::parse`], but allocates the tokens, and the slice holding them, in `bump`, so
+    /// that they are all freed together when the arena is reset or dropped. Only tokens holding
+    /// quotes or escapes are built on the heap first.
+    ///
+    /// ```
+    /// use bumpalo::Bump;
+    /// use comma::Parser;
+    ///
+    /// let bump = Bump::new();
+    /// let parser = Parser::new();
+    /// let commands: Vec<&[&str]> = ["ls -l", "cd 'my dir'"]
+    ///     .iter()
+    ///     .map(|line| parser.parse_in(line, &bump).unwrap())
+    ///     .collect();
+    /// assert_eq!(commands[1], ["cd", "my dir"]);
+    /// ```
+    #[cfg(feature = "bumpalo")]
+    pub fn parse_in<'b>(&self, input: &str, bump: &'b Bump) -> Option<&'b [&'b str]> {
+        self.try_parse_in(input, bump).ok()
+    }
+
+    /// Like [`Parser::parse_in`], but describes why malformed input could not be parsed.
+    #[cfg(feature = "bumpalo")]
+    pub fn try_parse_in<'b>(
+        &self,
+        input: &str,
+        bump: &'b Bump,
+    ) -> Result<&'b [&'b str], ParseError> {
+        let mut tokens = bumpalo::collections::Vec::new_in(bump);
+        cow::parse(self, input, |token| tokens.push(&*bump.alloc_str(&token)))?;
+        Ok(tokens.into_bump_slice())
+    }
+
+    /// Like [`Parser::try_parse`], but adds each token to `collection` as it is parsed, so that
+    /// tokens can be streamed into any collection, such as a set or a queue, without an
+    /// intermediate vector. On failure, `collection` holds the tokens read before the problem.
+    ///
+    /// ```
+    /// use comma::Parser;
+    /// use std::collections::BTreeSet;
+    ///
+    /// let mut words = BTreeSet::new();
+    /// let parser = Parser::new();
+    /// parser.parse_extend("to be 'or not' to be", &mut words)?;
+    /// assert_eq!(words.into_iter().collect::<Vec<_>>(), ["be", "or not", "to"]);
+    /// # Ok::<(), comma::ParseError>(())
+    /// ```
+    pub fn parse_extend<E>(&self, input: &str, collection: &mut E) -> Result<(), ParseError>
+    where
+        E: Extend<String>,
+    {
+        let mut tokens = self.tokens(input);
+        collection.extend(tokens.by_ref());
+        tokens.error().map_or(Ok(()), Err)
+    }
+
+    /// Like [`Parser::parse_into`], but builds each token in the string already at its position
+    /// in `tokens`, if any, so that parsing commands no longer than those parsed before needs no
+    /// new allocations. Tokens that must be rebuilt, such as those kept quoted or
+    /// [percent-decoded](Parser::percent_decode), are allocated as usual.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let mut tokens = Vec::new();
+    /// parser.parse_reusing("set 'long key' 1", &mut tokens)?;
+    /// let capacity = tokens[1].capacity();
+    /// parser.parse_reusing("get short", &mut tokens)?;
+    /// assert_eq!(tokens, ["get", "short"]);
+    /// assert_eq!(tokens[1].capacity(), capacity);
+    /// # Ok::<(), comma::ParseError>(())
+    /// ```
+    pub fn parse_reusing(&self, input: &str, tokens: &mut Vec<String>) -> Result<(), ParseError> {
+        let mut lexer = Lexer::new(self, input);
+        let mut count = 0;
+        let result = loop {
+            if let Some(slot) = tokens.get_mut(count) {
+                lexer.reuse(core::mem::take(slot));
+            }
+            let token = match lexer.next() {
+                Some(Ok(token)) => token.into_string(),
+                Some(Err(error)) => break Err(error),
+                None => break Ok(()),
+            };
+            match tokens.get_mut(count) {
+                Some(slot) => *slot = token,
+                None => tokens.push(token),
+            }
+            count += 1;
+        };
+        tokens.truncate(count);
+        result
+    }
+
+    /// Checks that a command is well-formed, describing the first problem if it is not, exactly
+    /// as [`Parser::try_parse`] would. The tokens themselves are never built, so checking input
+    /// never allocates unless it is malformed.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let parser = Parser::new();
+    /// assert!(parser.validate("scp 'a b.txt' host:~/").is_ok());
+    /// let error = parser.validate("scp \"a b.txt host:~/").unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+    /// ```
+    pub fn validate(&self, input: &str) -> Result<(), ParseError> {
+        Lexer::new(self, input)
+            .with_discard()
+            .try_for_each(|token| token.map(drop))
+    }
+
+    /// Counts the tokens of a command, returning None if it is malformed. Unless
+    /// [`Parser::split_long_options`] is enabled, the tokens are never built, so counting them
+    /// does not allocate.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// assert_eq!(parser.count_tokens("tar -czf 'backup 1.tgz' ~/docs"), Some(4));
+    /// assert_eq!(parser.count_tokens("  "), Some(0));
+    /// assert_eq!(parser.count_tokens("tar 'oops"), None);
+    /// ```
+    pub fn count_tokens(&self, input: &str) -> Option<usize> {
+        // Whether a long option is split depends on its text, which must then be built.
+        let lexer = Lexer::new(self, input);
+        let lexer = if self.split_long_options {
+            lexer
+        } else {
+            lexer.with_discard()
+        };
+        lexer.map(|token| token.ok().map(|_| 1)).sum()
+    }
+
+    /// Parses only the first token of a command, returning None if there is none or it is
+    /// malformed. The rest of the input is not read, so dispatching on a command's name is cheap
+    /// however long the command is, and malformed input after the first token is not noticed.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let token = Parser::new().first_token("  \"git\" commit -m 'unfinished").unwrap();
+    /// assert_eq!((token.as_str(), token.span()), ("git", 2..7));
+    /// ```
+    pub fn first_token(&self, input: &str) -> Option<Token> {
+        Lexer::new(self, input).next()?.ok()
+    }
+
+    /// Parses only as far as the token at index `n` of a command, returning None if the command
+    /// has fewer tokens or is malformed before that token ends. The preceding tokens are skipped
+    /// without being built, unless [`Parser::split_long_options`] is enabled, and the rest of the
+    /// input is not read.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let token = parser.nth_token("docker run --name 'web server' nginx '", 3).unwrap();
+    /// assert_eq!((token.as_str(), token.span()), ("web server", 18..30));
+    /// assert_eq!(parser.nth_token("docker run", 2), None);
+    /// ```
+    pub fn nth_token(&self, input: &str, n: usize) -> Option<Token> {
+        // Whether a long option is split depends on its text, which must then be built.
+        let mut lexer = Lexer::new(self, input);
+        lexer.discard = !self.split_long_options;
+        for _ in 0..n {
+            lexer.next()?.ok()?;
+        }
+        lexer.discard = false;
+        lexer.next()?.ok()
+    }
+
+    /// Returns an iterator over the commands read from `reader`, parsing each logical line as a
+    /// command once it is complete, so that large scripts need not be loaded into memory. Lines
+    /// continue while a quote is left open, or, under [`TrailingBackslash::Continue`], while they
+    /// end in a backslash.
+    ///
+    /// ```
+    /// use comma::{Parser, TrailingBackslash};
+    ///
+    /// let script = "mkdir 'My\nFiles'\n\ncp a \\\n  b\n";
+    /// let parser = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+    /// let commands: Vec<_> = parser.commands_from_reader(script.as_bytes()).collect();
+    /// assert_eq!(commands.len(), 2);
+    /// assert_eq!(commands[1].as_ref().unwrap(), &["cp", "a", "b"]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn commands_from_reader<R: BufRead>(&self, reader: R) -> ReadCommands<R> {
+        ReadCommands::new(self.clone(), reader)
+    }
+
+    /// Returns a [`Stream`](futures_core::Stream) of the commands read from `reader`, such as a
+    /// network connection, joining and parsing lines like [`Parser::commands_from_reader`].
+    #[cfg(feature = "tokio")]
+    pub fn commands_from_async_reader<R>(&self, reader: R) -> AsyncCommands<R>
+    where
+        R: tokio::io::AsyncBufRead + Unpin,
+    {
+        AsyncCommands::new(self.clone(), reader)
+    }
+
+    /// Parses a command and builds a [`std::process::Command`] that runs its first token with
+    /// the rest as arguments. Fails if the input is malformed, or with
+    /// [`ErrorKind::EmptyCommand`] if it is blank.
+    #[cfg(feature = "std")]
+    pub fn process_command(&self, input: &str) -> Result<process::Command, ParseError> {
+        let mut tokens = self.try_parse(input)?.into_iter();
+        let program = tokens
+            .next()
+            .ok_or_else(|| ParseError::new(ErrorKind::EmptyCommand, 0..input.len()))?;
+        let mut command = process::Command::new(program);
+        command.args(tokens);
+        Ok(command)
+    }
+
+    /// Parses a command and feeds its tokens to [`clap::Parser::try_parse_from`], so that a REPL
+    /// can reuse its existing clap definitions for interactive input. `program` says where the
+    /// program name, which clap expects first, comes from.
+    ///
+    /// Fails with [`ClapError::Clap`] if clap rejects the tokens, or if they ask for help or the
+    /// version, in which case the error's message is what clap would have printed.
+    ///
+    /// ```
+    /// use comma::{Parser, ProgramName};
+    ///
+    /// // Usually written `#[derive(clap::Parser)] struct Open { path: String }`.
+    /// # struct Open { path: String }
+    /// # impl clap::FromArgMatches for Open {
+    /// #     fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+    /// #         let path = matches.get_one::<String>("path").cloned().unwrap_or_default();
+    /// #         Ok(Open { path })
+    /// #     }
+    /// #     fn update_from_arg_matches(&mut self, matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+    /// #         *self = Self::from_arg_matches(matches)?;
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// # impl clap::CommandFactory for Open {
+    /// #     fn command() -> clap::Command {
+    /// #         clap::Command::new("open").arg(clap::Arg::new("path").required(true))
+    /// #     }
+    /// #     fn command_for_update() -> clap::Command {
+    /// #         Self::command()
+    /// #     }
+    /// # }
+    /// # impl clap::Parser for Open {}
+    /// let open: Open = Parser::new().try_parse_clap("'My File.txt'", ProgramName::CommandName)?;
+    /// assert_eq!(open.path, "My File.txt");
+    /// # Ok::<(), comma::ClapError>(())
+    /// ```
+    #[cfg(feature = "clap")]
+    pub fn try_parse_clap<T: clap::Parser>(
+        &self,
+        input: &str,
+        program: ProgramName,
+    ) -> Result<T, ClapError> {
+        cli::parse(self, input, program)
+    }
+
+    /// Parses a command and matches its tokens against a [`clap::Command`] built with clap's
+    /// builder API, like [`Parser::try_parse_clap`].
+    ///
+    /// ```
+    /// use clap::{Arg, Command};
+    /// use comma::{Parser, ProgramName};
+    ///
+    /// let repl = Command::new("repl").subcommand(Command::new("open").arg(Arg::new("path")));
+    /// let matches = Parser::new().clap_matches(repl, "open 'My File.txt'", ProgramName::CommandName)?;
+    /// let (name, args) = matches.subcommand().unwrap();
+    /// assert_eq!(name, "open");
+    /// assert_eq!(args.get_one::<String>("path").unwrap(), "My File.txt");
+    /// # Ok::<(), comma::ClapError>(())
+    /// ```
+    #[cfg(feature = "clap")]
+    pub fn clap_matches(
+        &self,
+        command: clap::Command,
+        input: &str,
+        program: ProgramName,
+    ) -> Result<clap::ArgMatches, ClapError> {
+        cli::matches(self, command, input, program)
+    }
+
+    /// Parses a platform string, such as a command line from the environment, which need not be
+    /// valid Unicode. On Unix, bytes that are not valid UTF-8 are kept in the tokens exactly as
+    /// they were; on Windows, so are unpaired surrogates. Error positions count bytes of the
+    /// input's UTF-8 form, and are exact only if the input is valid Unicode.
+    ///
+    /// ```
+    /// use comma::Parser;
+    /// use std::ffi::OsStr;
+    ///
+    /// let tokens = Parser::new().parse_os(OsStr::new("cp 'a b' c")).unwrap();
+    /// assert_eq!(tokens, ["cp", "a b", "c"]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn parse_os(&self, input: &OsStr) -> Result<Vec<OsString>, ParseError> {
+        os::parse(self, input)
+    }
+
+    /// Parses bytes that need not be valid UTF-8, such as a command line embedded in a binary
+    /// protocol. Bytes that are not part of valid UTF-8 are never special, and are kept in the
+    /// tokens exactly as they were. Error positions count bytes of the input's UTF-8 form, and
+    /// are exact only if the input is valid UTF-8.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let tokens = Parser::new().parse_bytes(b"PUT \"\xff\x00 \xfe\" x").unwrap();
+    /// assert_eq!(tokens, vec![&b"PUT"[..], b"\xff\x00 \xfe", b"x"]);
+    /// ```
+    pub fn parse_bytes(&self, input: &[u8]) -> Result<Vec<Vec<u8>>, ParseError> {
+        os::parse_bytes(self, input)
+    }
+
+    /// Parses a command without allocating, writing the text of its tokens one after another
+    /// into `buffer`, and a slice of it for each token into `tokens`. Returns the number of
+    /// tokens. Fails with [`ErrorKind::CapacityExceeded`], spanning the token that did not fit,
+    /// if either runs out of room.
+    ///
+    /// Percent decoding, Unicode normalization, escaping of forbidden characters and splitting
+    /// of long options all need to allocate, so they are never applied here.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let mut buffer = [0; 32];
+    /// let mut tokens = [""; 4];
+    /// let count = Parser::new().parse_fixed("mv 'old name' new", &mut buffer, &mut tokens)?;
+    /// assert_eq!(tokens[..count], ["mv", "old name", "new"]);
+    ///
+    /// let mut few = [""; 2];
+    /// let error = Parser::new().parse_fixed("a b c", &mut [0; 8], &mut few).unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::CapacityExceeded);
+    /// # Ok::<(), comma::ParseError>(())
+    /// ```
+    pub fn parse_fixed<'b>(
+        &self,
+        input: &str,
+        buffer: &'b mut [u8],
+        tokens: &mut [&'b str],
+    ) -> Result<usize, ParseError> {
+        fixed::parse(self, input, buffer, tokens)
+    }
+
+    /// Parses UTF-16 that need not be valid, such as the command line returned by
+    /// `GetCommandLineW` on Windows. Unpaired surrogates are never special, and are kept in the
+    /// tokens exactly as they were. Error positions count bytes of the input's UTF-8 form.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let input: Vec<u16> = "notepad \"My Notes.txt\"".encode_utf16().collect();
+    /// let tokens = Parser::new().parse_wide(&input).unwrap();
+    /// assert_eq!(String::from_utf16(&tokens[1]).unwrap(), "My Notes.txt");
+    /// ```
+    pub fn parse_wide(&self, input: &[u16]) -> Result<Vec<Vec<u16>>, ParseError> {
+        os::parse_wide(self, input)
+    }
+
+    /// Returns an iterator that parses tokens on demand, so callers needing only the first few
+    /// tokens of a long command do not pay to parse the rest. Iteration ends early if the input
+    /// is malformed, which [`Tokens::error`] then describes.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let mut tokens = parser.tokens("grep -r 'TODO' src/ 'unterminated");
+    /// assert_eq!(tokens.next().as_deref(), Some("grep"));
+    /// assert_eq!(tokens.by_ref().count(), 3);
+    /// assert!(tokens.error().is_some());
+    /// ```
+    pub fn tokens<'p, 'a>(&'p self, input: &'a str) -> Tokens<'p, 'a> {
+        Tokens {
+            lexer: Lexer::new(self, input),
+            error: None,
+        }
+    }
+
+    /// Returns an iterator that parses [`Token`]s on demand, yielding an error in place of the
+    /// token at which the input is found to be malformed. The tokens preceding it remain usable,
+    /// and iteration ends after the error.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let parser = Parser::new();
+    /// let mut tokens = parser.try_tokens("kill -9 \"1234");
+    /// assert_eq!(tokens.next().unwrap().unwrap().as_str(), "kill");
+    /// assert_eq!(tokens.next().unwrap().unwrap().as_str(), "-9");
+    ///
+    /// let error = tokens.next().unwrap().unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+    /// assert!(tokens.next().is_none());
+    /// ```
+    pub fn try_tokens<'p, 'a>(&'p self, input: &'a str) -> TryTokens<'p, 'a> {
+        TryTokens {
+            lexer: Lexer::new(self, input),
+        }
+    }
+
+    /// Returns an iterator over the [`RawToken`](crate::RawToken)s of `input`, each a slice of the input along
+    /// with whether it needs unescaping, so that callers can resolve quotes and escapes only in
+    /// the tokens they use. Reading tokens this way never allocates. Long options are never split,
+    /// and like [`Parser::try_tokens`], an error is yielded in place of the token at which the
+    /// input is found to be malformed.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let input = r#"GET /index.html "User Agent""#;
+    /// let mut tokens = parser.raw_tokens(input).map(Result::unwrap);
+    ///
+    /// let method = tokens.next().unwrap();
+    /// assert_eq!((method.text(), method.needs_unescape()), ("GET", false));
+    /// let agent = tokens.nth(1).unwrap();
+    /// assert_eq!(agent.text(), "\"User Agent\"");
+    /// assert_eq!(agent.unescape(&parser), "User Agent");
+    /// ```
+    pub fn raw_tokens<'p, 'a>(&'p self, input: &'a str) -> RawTokens<'p, 'a> {
+        RawTokens::new(self, input)
+    }
+
+    /// Returns a [`TokenStream`], which parses [`Token`]s on demand like [`Parser::try_tokens`],
+    /// but can also look ahead at tokens without consuming them, as recursive-descent parsers
+    /// for small command languages often need to.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let mut stream = parser.token_stream("move north 3");
+    /// while let Some(Ok(token)) = stream.next() {
+    ///     let count = match stream.peek() {
+    ///         Some(Ok(next)) => next.as_str().parse::<u32>().ok(),
+    ///         _ => None,
+    ///     };
+    ///     if count.is_some() {
+    ///         assert_eq!((token.as_str(), count), ("north", Some(3)));
+    ///         stream.next();
+    ///     }
+    /// }
+    /// assert_eq!(stream.position(), 3);
+    /// ```
+    pub fn token_stream<'p, 'a>(&'p self, input: &'a str) -> TokenStream<'p, 'a> {
+        TokenStream::new(Lexer::new(self, input))
+    }
+
+    /// Returns a [`Tokenizer`], which is fed input in chunks and returns each [`Token`] once it
+    /// is complete, for input that arrives a piece at a time.
+    pub fn tokenizer(&self) -> Tokenizer<'_> {
+        Tokenizer::new(self)
+    }
+
+    /// Parses a single line of input, returning its tokens and the input after the line. With
+    /// [`Newlines::Terminate`], the line ends at the first unquoted, unescaped newline, which is
+    /// consumed; otherwise it spans the whole input.
+    ///
+    /// ```
+    /// use comma::{Newlines, Parser};
+    ///
+    /// let parser = Parser::new().newlines(Newlines::Terminate);
+    /// let (tokens, rest) = parser.parse_line("PING 'a\nb'\r\nQUIT\n").unwrap();
+    /// assert_eq!(tokens, vec!["PING", "a\nb"]);
+    /// assert_eq!(rest, "QUIT\n");
+    /// ```
+    pub fn parse_line<'a>(&self, input: &'a str) -> Option<(Vec<String>, &'a str)> {
+        let mut lexer = Lexer::new(self, input);
+        let tokens = lexer
+            .by_ref()
+            .map(|token| token.map(Token::into_string))
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let end = lexer.offset();
+        let rest = input[end..].strip_prefix('\n').unwrap_or(&input[end..]);
+        Some((tokens, rest))
+    }
+
+    /// Parses the first token of the input, returning it along with the rest of the input,
+    /// untouched except that the whitespace following the token is skipped. This suits commands
+    /// that take their argument verbatim. Returns None if the input is blank, or if its first
+    /// token is malformed.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let (verb, rest) = Parser::new().parse_one("'say'   it's  \"done\"").unwrap();
+    /// assert_eq!(verb, "say");
+    /// assert_eq!(rest, "it's  \"done\"");
+    /// ```
+    pub fn parse_one<'a>(&self, input: &'a str) -> Option<(String, &'a str)> {
+        let mut lexer = Lexer::new(self, input);
+        let token = lexer.next()?.ok()?;
+        let end = match lexer.pending.take() {
+            // The value of a split long option is left unparsed.
+            Some(value) => value.span.start,
+            None => {
+                lexer.skip_separators();
+                lexer.offset()
+            }
+        };
+        // A rejected character would otherwise cut the rest short.
+        if lexer.chars.error().is_some() {
+            return None;
+        }
+        Some((token.into_string(), &input[end..]))
+    }
+
+    /// Parses a command into at most `max` tokens. Should the command have more, the last token
+    /// instead holds the rest of the input verbatim, from the start of what would have been its
+    /// next token. Malformed input in that rest is not an error. Returns None if the input is
+    /// otherwise malformed.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let tokens = parser.parse_max("'commit' -m \"it's done\"  now", 2).unwrap();
+    /// assert_eq!(tokens, vec!["commit", "-m \"it's done\"  now"]);
+    /// assert_eq!(parser.parse_max("a 'b c'", 2).unwrap(), vec!["a", "b c"]);
+    /// ```
+    pub fn parse_max(&self, input: &str, max: usize) -> Option<Vec<String>> {
+        let mut lexer = Lexer::new(self, input);
+        let mut tokens = Vec::new();
+        if max == 0 {
+            return Some(tokens);
+        }
+        while tokens.len() + 1 < max {
+            match lexer.next() {
+                Some(token) => tokens.push(token.ok()?.into_string()),
+                None => return Some(tokens),
+            }
+        }
+
+        let rest = match &lexer.pending {
+            Some(value) => value.span.start,
+            None => {
+                lexer.skip_separators();
+                lexer.offset()
+            }
+        };
+        // The rest is kept verbatim only if it holds more than one token.
+        let last = match lexer.next() {
+            Some(token) => token.ok()?.into_string(),
+            None => return Some(tokens),
+        };
+        match lexer.next() {
+            _ if lexer.chars.error().is_some() => return None,
+            Some(_) => tokens.push(input[rest..].to_string()),
+            None => tokens.push(last),
+        }
+        Some(tokens)
+    }
+
+    /// Parses a command like [`Parser::try_parse`], additionally returning warnings about parts of
+    /// the input that are well-formed but questionable: unquoted glob characters, tokens that mix
+    /// single and double quotes, and escape sequences that could be omitted.
+    ///
+    /// ```
+    /// use comma::{Parser, WarningKind};
+    ///
+    /// let (tokens, warnings) = Parser::new().parse_with_warnings("rm *.log \\q").unwrap();
+    /// assert_eq!(tokens, vec!["rm", "*.log", "q"]);
+    /// assert_eq!(warnings[0].kind(), WarningKind::UnquotedGlob('*'));
+    /// assert_eq!(warnings[1].kind(), WarningKind::UnnecessaryEscape('q'));
+    /// assert_eq!(warnings[1].span(), 9..11);
+    /// ```
+    pub fn parse_with_warnings(
+        &self,
+        input: &str,
+    ) -> Result<(Vec<String>, Vec<Warning>), ParseError> {
+        let mut lexer = Lexer::new(self, input).with_warnings();
+        let tokens = lexer
+            .by_ref()
+            .map(|token| token.map(Token::into_string))
+            .collect::<Result<_, _>>()?;
+        Ok((tokens, lexer.warnings()))
+    }
+
+    /// Parses a command without ever failing, returning the tokens alongside every problem found,
+    /// as described by [`Parser::diagnose`]. Malformed input still yields the tokens parsed so far,
+    /// including any partial token cut short by an unterminated quote.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let (tokens, errors) = Parser::new().parse_lossy("git commit -m 'WIP: fix");
+    /// assert_eq!(tokens, vec!["git", "commit", "-m", "WIP: fix"]);
+    /// assert_eq!(errors[0].kind(), ErrorKind::UnterminatedQuote('\''));
+    /// ```
+    pub fn parse_lossy(&self, input: &str) -> (Vec<String>, Vec<ParseError>) {
+        let mut lexer = Lexer::new(self, input).with_recovery();
+        let tokens = lexer
+            .by_ref()
+            .filter_map(Result::ok)
+            .map(Token::into_string)
+            .collect();
+        (tokens, lexer.errors())
+    }
+
+    /// Finds every problem with `input` in a single pass, returning them in the order they appear.
+    /// After each problem, parsing continues as best it can: an unterminated quote is closed by
+    /// the end of the input, and a rejected character or escape sequence is kept literally. An
+    /// empty result means the input can be parsed.
+    ///
+    /// ```
+    /// use comma::{ErrorKind, Parser};
+    ///
+    /// let parser = Parser::new().strict_escapes(true);
+    /// let errors = parser.diagnose(r#"cp \q a\z "b"#);
+    /// let kinds: Vec<ErrorKind> = errors.iter().map(|error| error.kind()).collect();
+    /// assert_eq!(
+    ///     kinds,
+    ///     vec![
+    ///         ErrorKind::UnknownEscape('q'),
+    ///         ErrorKind::UnknownEscape('z'),
+    ///         ErrorKind::UnterminatedQuote('"'),
+    ///     ]
+    /// );
+    /// assert_eq!(errors[2].span(), 10..12);
+    /// ```
+    pub fn diagnose(&self, input: &str) -> Vec<ParseError> {
+        let mut lexer = Lexer::new(self, input).with_recovery();
+        lexer.by_ref().for_each(drop);
+        lexer.errors()
+    }
+
+    /// Like [`Parser::parse`], but pairs each token with the byte range of the input it was parsed
+    /// from. See [`Token::span`].
+    pub fn parse_spanned(&self, input: &str) -> Option<Vec<(String, Range<usize>)>> {
+        Lexer::new(self, input)
+            .map(|token| token.map(|token| (token.value, token.span)))
+            .collect::<Result<_, _>>()
+            .ok()
+    }
+
+    /// Like [`Parser::parse`], but produces [`Token`]s describing how each token was written.
+    pub fn parse_tokens(&self, input: &str) -> Option<Vec<Token>> {
+        Lexer::new(self, input)
+            .with_comments(self.keep_comments)
+            .collect::<Result<_, _>>()
+            .ok()
+    }
+
+    /// Parses a command, reporting every shell metacharacter found within its tokens: characters
+    /// such as `;`, `|`, `>` and backticks, and the sequence `$(`, that would run or redirect
+    /// commands should a token later be handed to a real shell unquoted. Metacharacters are
+    /// reported wherever they appear in a token, even when quoted or escaped, since quoting is
+    /// removed by parsing. Services can use this to reject or flag risky input.
+    ///
+    /// ```
+    /// use comma::{HazardKind, Parser};
+    ///
+    /// let hazards = Parser::new().audit("convert 'in.png' \\`reboot\\`.png").unwrap();
+    /// assert_eq!(hazards[0].kind(), HazardKind::CommandSubstitution);
+    /// assert_eq!(hazards[0].token(), 2);
+    /// assert_eq!(hazards[0].span(), 18..19);
+    /// ```
+    pub fn audit(&self, input: &str) -> Result<Vec<Hazard>, ParseError> {
+        let tokens: Vec<Token> = Lexer::new(self, input).collect::<Result<_, _>>()?;
+        Ok(audit::audit(input, &tokens))
+    }
+
+    /// Re-renders a command for safe logging, replacing the value following each of the given
+    /// `flags` with `***`. Values may be separate tokens, as in `--password hunter2`, or attached
+    /// to a long option, as in `--token=abc`. Everything else, including quoting and spacing, is
+    /// kept exactly as written.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let input = "mysql -u 'root' --password \"p@ss word\" --ssl-key=key.pem";
+    /// let result = parser.redact(input, &["--password", "--ssl-key"]).unwrap();
+    /// assert_eq!(result, "mysql -u 'root' --password *** --ssl-key=***");
+    /// ```
+    pub fn redact(&self, input: &str, flags: &[&str]) -> Result<String, ParseError> {
+        redact::redact(self, input, flags)
+    }
+
+    /// Parses a command and writes it back out in a canonical form: tokens separated by single
+    /// spaces, each quoted by [`quote`](crate::quote) only if necessary. Commands that parse to the
+    /// same tokens always produce the same string, however they were quoted, escaped or spaced.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// let a = parser.canonicalize(r#"  grep \"TODO\"   "src dir""#).unwrap();
+    /// let b = parser.canonicalize(r#"grep '"TODO"' src\ dir"#).unwrap();
+    /// assert_eq!(a, r#"grep '"TODO"' 'src dir'"#);
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn canonicalize(&self, input: &str) -> Result<String, ParseError> {
+        let tokens: Vec<String> = Lexer::new(self, input)
+            .map(|token| token.map(|token| quote(token.as_str())))
+            .collect::<Result<_, _>>()?;
+        Ok(tokens.join(" "))
+    }
+
+    /// Compares the tokens of two commands, ignoring how they were quoted, escaped and spaced.
+    /// Returns None if the commands are equal, or else describes the first token at which they
+    /// differ. Input following that token is not parsed, but malformed input before it is an
+    /// error.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let parser = Parser::new();
+    /// assert_eq!(parser.compare("ls 'My Files'", "ls  My\\ Files").unwrap(), None);
+    ///
+    /// let mismatch = parser.compare("git push origin", "git push -f origin").unwrap().unwrap();
+    /// assert_eq!(mismatch.index, 2);
+    /// assert_eq!(mismatch.left.as_deref(), Some("origin"));
+    /// assert_eq!(mismatch.right.as_deref(), Some("-f"));
+    /// ```
+    pub fn compare(&self, a: &str, b: &str) -> Result<Option<Mismatch>, ParseError> {
+        diff::compare(Lexer::new(self, a), Lexer::new(self, b))
+    }
+
+    /// Parses two commands, and lists the tokens added to, removed from or changed in `old` to
+    /// produce `new`, in order. Tokens the commands share are left out, and a token replaced by
+    /// another at the same place is reported as a single [`Change::Changed`]. Each token keeps its
+    /// span within its own command, so the changes can be shown against either one.
+    ///
+    /// ```
+    /// use comma::{Change, Parser};
+    ///
+    /// let parser = Parser::new();
+    /// let changes = parser.diff("rsync -a src/ host:", "rsync -a --delete src/ 'host:/srv'").unwrap();
+    /// match &changes[..] {
+    ///     [Change::Added(added), Change::Changed(old, new)] => {
+    ///         assert_eq!((added.as_str(), added.span()), ("--delete", 9..17));
+    ///         assert_eq!((old.as_str(), new.as_str()), ("host:", "host:/srv"));
+    ///     }
+    ///     changes => panic!("unexpected changes {:?}", changes),
+    /// }
+    /// ```
+    pub fn diff(&self, old: &str, new: &str) -> Result<Vec<Change>, ParseError> {
+        let old = Lexer::new(self, old).collect::<Result<_, _>>()?;
+        let new = Lexer::new(self, new).collect::<Result<_, _>>()?;
+        Ok(diff::diff(old, new))
+    }
+
+    /// Describes the token at byte offset `offset` of `input`, typically the user's cursor, for
+    /// implementing tab completion. Input following the cursor may be malformed, and so may input
+    /// preceding it, since the cursor is often within a quote that is yet to be closed. An offset
+    /// past the end of the input is treated as the end.
+    ///
+    /// ```
+    /// use comma::Parser;
+    ///
+    /// let input = "vim \"My Documents/no";
+    /// let cursor = Parser::new().cursor(input, input.len());
+    /// assert_eq!(cursor.token, 1);
+    /// assert_eq!(cursor.span, 4..20);
+    /// assert_eq!(cursor.quote, Some('"'));
+    /// assert_eq!(cursor.prefix, "My Documents/no");
+    /// ```
+    pub fn cursor(&self, input: &str, offset: usize) -> Cursor {
+        completion::locate(self, input, offset)
+    }
+
+    /// Divides `input` into spans of quotation marks, quoted text, escape sequences, words,
+    /// operators, comments and whitespace, so that terminal interfaces and editors can colour a
+    /// command exactly as it will be parsed. The spans cover the whole input, in order. Malformed
+    /// input is still highlighted, with an unterminated quote extending to the end of the input.
+    ///
+    /// ```
+    /// use comma::{Highlight, Parser};
+    ///
+    /// let spans = Parser::new().highlight("ls 'a b'");
+    /// assert_eq!(
+    ///     spans,
+    ///     vec![
+    ///         (0..2, Highlight::Word),
+    ///         (2..3, Highlight::Whitespace),
+    ///         (3..4, Highlight::QuoteDelimiter),
+    ///         (4..7, Highlight::Quoted),
+    ///         (7..8, Highlight::QuoteDelimiter),
+    ///     ]
+    /// );
+    /// ```
+    pub fn highlight(&self, input: &str) -> Vec<(Range<usize>, Highlight)> {
+        highlight::highlight(self, input)
+    }
+
+    /// Highlights `input` like [`Parser::highlight`], encoding the result as the data of a
+    /// Language Server Protocol `SemanticTokens` response. Each token is described by five
+    /// integers, with types and modifiers indexing into [`SEMANTIC_TOKEN_TYPES`] and
+    /// [`SEMANTIC_TOKEN_MODIFIERS`], which form the legend a server should advertise. Whitespace
+    /// is omitted, spans are divided at line breaks, and characters are counted in UTF-16 code
+    /// units.
+    ///
+    /// ```
+    /// use comma::{Parser, SEMANTIC_TOKEN_TYPES};
+    ///
+    /// let data = Parser::new().semantic_tokens("echo\n  |");
+    /// assert_eq!(data, vec![0, 0, 4, 0, 0, 1, 2, 1, 3, 0]);
+    /// assert_eq!(SEMANTIC_TOKEN_TYPES[3], "operator");
+    /// ```
+    ///
+    /// [`SEMANTIC_TOKEN_TYPES`]: crate::SEMANTIC_TOKEN_TYPES
+    /// [`SEMANTIC_TOKEN_MODIFIERS`]: crate::SEMANTIC_TOKEN_MODIFIERS
+    pub fn semantic_tokens(&self, input: &str) -> Vec<u32> {
+        highlight::semantic_tokens(input, self.highlight(input))
+    }
+
+    /// Updates the tokens `previous`, parsed by [`Parser::parse_tokens`] from some input, after
+    /// that input was changed by `edit` to become `input`. Only the tokens near the edit are
+    /// parsed again; those before it are reused, as are those after it once parsing reaches a
+    /// token that begins where one began before. The result is the same as parsing `input` from
+    /// scratch, which editors can otherwise not afford on every keystroke in a long command.
+    /// Nothing is reused when long options are split or any limit, such as
+    /// [`Parser::max_tokens`], is set, since these depend on the whole input.
+    ///
+    /// ```
+    /// use comma::{Edit, Parser};
+    ///
+    /// let parser = Parser::new();
+    /// let input = "ffmpeg -i in.mp4 -c:v libx264 out.mp4";
+    /// let tokens = parser.parse_tokens(input).unwrap();
+    ///
+    /// let edit = Edit::new(10..12, "'my video'");
+    /// let edited = edit.apply(input).unwrap();
+    /// let tokens = parser.reparse(&tokens, &edited, &edit).unwrap();
+    /// assert_eq!(tokens[2].as_str(), "my video.mp4");
+    /// assert_eq!(tokens[5].span(), 38..45);
+    /// ```
+    pub fn reparse(
+        &self,
+        previous: &[Token],
+        input: &str,
+        edit: &Edit,
+    ) -> Result<Vec<Token>, ParseError> {
+        incremental::reparse(self, previous, input, edit)
+    }
+
+    /// Like [`Parser::parse`], but pairs each token with its [`TokenKind`].
+    pub fn parse_classified(&self, input: &str) -> Option<Vec<(TokenKind, String)>> {
+        let tokens: Vec<Token> = Lexer::new(self, input).collect::<Result<_, _>>().ok()?;
+        Some(
+            classify(&tokens)
+                .into_iter()
+                .zip(tokens.into_iter().map(Token::into_string))
+                .collect(),
+        )
+    }
+
+    /// Like [`parse_plus_commands`](crate::parse_plus_commands), but using this parser's settings.
+    pub fn parse_plus_commands(&self, input: &str) -> Option<Vec<Vec<String>>> {
+        let tokens: Vec<Token> = Lexer::new(self, input).collect::<Result<_, _>>().ok()?;
+        Some(chain(tokens))
+    }
+
+    /// Like [`parse_with_aliases`](crate::parse_with_aliases), but using this parser's settings.
+    pub fn parse_with_aliases<F>(&self, input: &str, resolve: F) -> Option<Vec<String>>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        alias::parse(self, input, resolve).ok()
+    }
+
+    /// Like [`parse_triggered`](crate::parse_triggered), but using this parser's settings.
+    pub fn parse_triggered<'t>(
+        &self,
+        input: &str,
+        triggers: &[&'t str],
+    ) -> Option<(&'t str, Vec<String>)> {
+        let (trigger, rest) = strip_trigger(input, triggers)?;
+        Some((trigger, self.parse(rest)?))
+    }
+
+    /// Returns an iterator over the commands in `input`, one per logical line, each reported
+    /// with the lines it spans. A line is joined with the next while [`Parser::classify`] finds
+    /// it unfinished: while a quote is left open, or, under [`TrailingBackslash::Continue`],
+    /// while it ends in a backslash. Each command is then parsed on its own, so a malformed
+    /// command does not prevent reading the rest. Lines holding no tokens are skipped.
+    ///
+    /// ```
+    /// use comma::{Parser, TrailingBackslash};
+    ///
+    /// let input = "mkdir build\n\ncmake -S . \\\n  -B build\necho 'done\n'";
+    /// let parser = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+    /// let commands: Vec<_> = parser.line_commands(input).collect();
+    ///
+    /// assert_eq!(commands[1].lines, 3..=4);
+    /// assert_eq!(commands[1].tokens.as_ref().unwrap(), &["cmake", "-S", ".", "-B", "build"]);
+    /// assert_eq!(commands[2].lines, 5..=6);
+    /// ```
+    pub fn line_commands<'p, 'a>(&'p self, input: &'a str) -> LineCommands<'p, 'a> {
+        LineCommands::new(self, input)
+    }
+
+    /// Like [`parse_script`](crate::parse_script), but using this parser's settings.
+    pub fn parse_script(&self, input: &str) -> Option<Vec<ScriptCommand>> {
+        script::parse(self, input).ok()
+    }
+
+    /// Like [`parse_cst`](crate::parse_cst), but using this parser's settings.
+    pub fn parse_cst(&self, input: &str) -> SyntaxTree {
+        cst::parse(self, input)
+    }
+
+    /// Like [`parse_shell`](crate::parse_shell), but using this parser's settings.
+    pub fn parse_shell(&self, input: &str) -> Option<ast::List> {
+        ast::parse_shell(self, input).ok()
+    }
+
+    /// Like [`parse_ast`](crate::parse_ast), but using this parser's settings.
+    pub fn parse_ast(&self, input: &str) -> Option<Vec<Node>> {
+        ast::parse(self, input).ok()
+    }
+}
+
+/// An iterator over the tokens of a command, parsed as they are requested. See
+/// [`Parser::tokens`].
+pub struct Tokens<'p, 'a> {
+    lexer: Lexer<'p, 'a>,
+    error: Option<ParseError>,
+}
+
+impl Tokens<'_, '_> {
+    /// The problem that ended iteration early, if the input was found to be malformed.
+    pub fn error(&self) -> Option<ParseError> {
+        self.error
+    }
+}
+
+impl Iterator for Tokens<'_, '_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lexer.next()? {
+            Ok(token) => Some(token.value),
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+/// An iterator over the tokens of a command, parsed as they are requested, that reports where
+/// malformed input begins. See [`Parser::try_tokens`].
+pub struct TryTokens<'p, 'a> {
+    lexer: Lexer<'p, 'a>,
+}
+
+impl Iterator for TryTokens<'_, '_> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lexer.next()
+    }
+}
+
+/// The first unquoted, unescaped `=` found in a token.
+#[derive(Clone, Copy)]
+struct Equals {
+    /// The index of the `=` in the token's unescaped text.
+    index: usize,
+    /// The byte offset of the `=` in the input.
+    offset: usize,
+    /// The quoted sections preceding the `=`.
+    quotes: Quotes,
+    /// The number of escape sequences preceding the `=`.
+    escapes: usize,
+}
+
+/// The number of quoted sections of each kind found in part of a token.
+#[derive(Clone, Copy, Default)]
+struct Quotes {
+    single: usize,
+    double: usize,
+}
+
+impl Quotes {
+    fn count(self) -> usize {
+        self.single + self.double
+    }
+
+    /// The quotes found since `earlier` were counted.
+    fn since(self, earlier: Quotes) -> Quotes {
+        Quotes {
+            single: self.single - earlier.single,
+            double: self.double - earlier.double,
+        }
+    }
+
+    fn quoting(self) -> Quoting {
+        match (self.single > 0, self.double > 0) {
+            (false, false) => Quoting::Unquoted,
+            (true, false) => Quoting::Single,
+            (false, true) => Quoting::Double,
+            (true, true) => Quoting::Mixed,
+        }
+    }
+}
+
+/// Lazily splits input into tokens according to a [`Parser`]'s settings.
+/// The lexer stops after yielding its first error, unless it is recovering from errors.
+pub(crate) struct Lexer<'p, 'a> {
+    parser: &'p Parser,
+    input: &'a str,
+    chars: Source<'p, 'a>,
+    failed: bool,
+    /// Unquoted characters that are read as standalone punctuation rather than token text.
+    punctuation: &'static [char],
+    /// Whether comments are yielded as tokens rather than skipped.
+    comments: bool,
+    /// Whether a `--` token has been read, after which long options are never split.
+    options_ended: bool,
+    /// The value half of a split long option, returned by the next call to `next`.
+    pending: Option<Token>,
+    /// Whether errors are recorded in `errors` and parsing continues, rather than failing.
+    recover: bool,
+    errors: Vec<ParseError>,
+    /// Whether warnings about questionable input are recorded in `warnings`.
+    warn: bool,
+    warnings: Vec<Warning>,
+    /// Whether quotation marks, quoted text and escape sequences are recorded in `marks`.
+    mark: bool,
+    marks: Vec<(Range<usize>, Highlight)>,
+    /// Whether the text of tokens is discarded rather than built, so that lexing never
+    /// allocates. Tokens are then yielded with empty values, and long options are never split.
+    discard: bool,
+    /// Where the text of tokens is written instead, when it is discarded.
+    output: Option<&'p mut dyn fmt::Write>,
+    /// The number of tokens read, counted against [`Parser::max_tokens`].
+    tokens: usize,
+    /// The number of bytes produced by escape sequences, counted against
+    /// [`Parser::max_expansion`].
+    expanded: usize,
+    /// An empty string whose allocation is reused for the value of the next token read.
+    spare: String,
+}
+
+impl<'p, 'a> Lexer<'p, 'a> {
+    pub(crate) fn new(parser: &'p Parser, input: &'a str) -> Self {
+        Self {
+            parser,
+            input,
+            chars: Source::new(parser, input),
+            failed: false,
+            punctuation: &[],
+            comments: false,
+            options_ended: false,
+            pending: None,
+            recover: false,
+            errors: Vec::new(),
+            warn: false,
+            warnings: Vec::new(),
+            mark: false,
+            marks: Vec::new(),
+            discard: false,
+            output: None,
+            tokens: 0,
+            expanded: 0,
+            spare: String::new(),
+        }
+    }
+
+    /// Creates a lexer that begins reading at byte offset `start` of `input`, which must lie on
+    /// a character boundary between tokens.
+    pub(crate) fn starting_at(parser: &'p Parser, input: &'a str, start: usize) -> Self {
+        let mut lexer = Self::new(parser, input);
+        lexer.chars = Source::starting_at(parser, input, start);
+        lexer
+    }
+
+    /// Makes the lexer record warnings, which can be retrieved with [`Lexer::warnings`].
+    pub(crate) fn with_warnings(mut self) -> Self {
+        self.warn = true;
+        self
+    }
+
+    /// The warnings recorded so far, in the order they appear in the input.
+    pub(crate) fn warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = core::mem::take(&mut self.warnings);
+        warnings.sort_by_key(|warning| warning.span().start);
+        warnings
+    }
+
+    /// Records a warning, if warnings are enabled.
+    fn warning(&mut self, kind: WarningKind, span: Range<usize>) {
+        if self.warn {
+            self.warnings.push(Warning::new(kind, span));
+        }
+    }
+
+    /// Makes the lexer record the spans of quotation marks, quoted text and escape sequences, which
+    /// can be retrieved with [`Lexer::marks`].
+    pub(crate) fn with_marks(mut self) -> Self {
+        self.mark = true;
+        self
+    }
+
+    /// The spans recorded so far. Quoted text is recorded after the escape sequences within it.
+    pub(crate) fn marks(&mut self) -> Vec<(Range<usize>, Highlight)> {
+        core::mem::take(&mut self.marks)
+    }
+
+    /// Records a span, if marks are enabled.
+    fn mark(&mut self, span: Range<usize>, kind: Highlight) {
+        if self.mark {
+            self.marks.push((span, kind));
+        }
+    }
+
+    /// Makes the lexer discard the text of tokens instead of building it.
+    pub(crate) fn with_discard(mut self) -> Self {
+        self.discard = true;
+        self
+    }
+
+    /// Makes the lexer write the text of tokens to `output`, as it reads them, instead of
+    /// building it. Text that `output` has no room for is dropped.
+    pub(crate) fn with_output(mut self, output: &'p mut dyn fmt::Write) -> Self {
+        self.discard = true;
+        self.output = Some(output);
+        self
+    }
+
+    /// Makes the lexer behave as though a `--` token had already been read, or not, so that a
+    /// lexer resuming partway through some input splits long options as the first would have.
+    pub(crate) fn with_options_ended(mut self, ended: bool) -> Self {
+        self.options_ended = ended;
+        self
+    }
+
+    /// Whether a `--` token has been read. See [`Lexer::with_options_ended`].
+    pub(crate) fn options_ended(&self) -> bool {
+        self.options_ended
+    }
+
+    /// Makes the lexer count the given number of tokens and bytes of expansions as already read,
+    /// so that a lexer resuming partway through some input applies limits as the first would have.
+    pub(crate) fn with_usage(mut self, (tokens, expanded): (usize, usize)) -> Self {
+        self.tokens = tokens;
+        self.expanded = expanded;
+        self
+    }
+
+    /// The number of tokens and bytes of expansions read. See [`Lexer::with_usage`].
+    pub(crate) fn usage(&self) -> (usize, usize) {
+        (self.tokens, self.expanded)
+    }
+
+    /// Makes the lexer build the value of the next token it reads in `spare`, reusing its
+    /// allocation, unless the value must be rebuilt by a later step such as normalization.
+    pub(crate) fn reuse(&mut self, mut spare: String) {
+        spare.clear();
+        self.spare = spare;
+    }
+
+    /// Whether the value of a split long option is yet to be returned, so that the token last
+    /// returned did not end a word.
+    pub(crate) fn splitting(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Appends `ch` to a token's value, unless token text is discarded.
+    fn push(&mut self, value: &mut String, ch: char) {
+        if let Some(output) = &mut self.output {
+            output.write_char(ch).ok();
+        } else if !self.discard {
+            value.push(ch);
+        }
+    }
+
+    /// Appends `text` to a token's value, unless token text is discarded.
+    fn push_str(&mut self, value: &mut String, text: &str) {
+        if let Some(output) = &mut self.output {
+            output.write_str(text).ok();
+        } else if !self.discard {
+            value.push_str(text);
+        }
+    }
+
+    /// Makes the lexer record errors and continue past them, as best it can, instead of failing.
+    /// The errors can then be retrieved with [`Lexer::errors`].
+    pub(crate) fn with_recovery(mut self) -> Self {
+        self.recover = true;
+        self.chars.recover = true;
+        self
+    }
+
+    /// The errors recorded while recovering, in the order they appear in the input.
+    pub(crate) fn errors(&mut self) -> Vec<ParseError> {
+        let mut errors = core::mem::take(&mut self.errors);
+        errors.append(&mut self.chars.errors);
+        errors.sort_by_key(|error| error.offset());
+        errors
+    }
+
+    /// Fails with `error`, or records it if recovering from errors.
+    fn report(&mut self, error: ParseError) -> Result<(), ParseError> {
+        if self.recover {
+            self.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Sets whether comments are yielded as tokens rather than skipped.
+    pub(crate) fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Makes the given unquoted characters end tokens instead of being read as part of them.
+    /// They can then be consumed with [`Lexer::punctuation`].
+    pub(crate) fn with_punctuation(mut self, punctuation: &'static [char]) -> Self {
+        self.punctuation = punctuation;
+        self
+    }
+
+    fn is_punctuation(&self, ch: char) -> bool {
+        self.punctuation.contains(&ch) || (ch == '\n' && self.parser.newlines != Newlines::Separate)
+    }
+
+    fn is_separator(&self, ch: char) -> bool {
+        self.parser.is_whitespace(ch) && !self.is_punctuation(ch)
+    }
+
+    /// Skips any whitespace and line continuations preceding the next token or punctuation.
+    pub(crate) fn skip_separators(&mut self) {
+        loop {
+            match self.peek() {
+                Some(ch) if self.is_separator(ch) => {}
+                Some('\\') if self.continues_line() => {
+                    // Consume the backslash here, and the newline below.
+                    self.chars.next();
+                }
+                _ => break,
+            }
+            self.chars.next();
+        }
+    }
+
+    /// Whether a line continuation begins at the next character.
+    fn continues_line(&mut self) -> bool {
+        self.parser.trailing_backslash == TrailingBackslash::Continue
+            && self.parser.escapes == Escapes::Anywhere
+            && self.input[self.offset()..].starts_with("\\\n")
+    }
+
+    /// Consumes and returns the next character if it is punctuation.
+    pub(crate) fn punctuation(&mut self) -> Option<char> {
+        let ch = self.peek().filter(|&ch| self.is_punctuation(ch))?;
+        self.chars.next();
+        Some(ch)
+    }
+
+    /// Reads a comment if one begins at byte offset `start`.
+    fn comment(&mut self, start: usize) -> Option<Token> {
+        let rest = &self.input[start..];
+        self.parser
+            .comments
+            .iter()
+            .find(|marker| self.parser.starts_comment(rest, marker))?;
+
+        let end = rest.find('\n').map_or(self.input.len(), |at| start + at);
+        while self.offset() < end {
+            self.chars.next();
+        }
+
+        let mut value = core::mem::take(&mut self.spare);
+        self.push_str(&mut value, &self.input[start..end]);
+        Some(Token {
+            value,
+            span: start..end,
+            assignment: None,
+            plain: true,
+            quoting: Quoting::Unquoted,
+            comment: true,
+        })
+    }
+
+    /// The next unread character.
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, ch)| ch)
+    }
+
+    /// The byte offset of the next unread character.
+    pub(crate) fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |(at, _)| at)
+    }
+
+    fn token(&mut self, start: usize) -> Result<Token, ParseError> {
+        let mut value = core::mem::take(&mut self.spare);
+        let mut equals: Option<Equals> = None;
+        let mut quotes = Quotes::default();
+        let mut escapes = 0;
+
+        while let Some((at, ch)) = self.chars.peek() {
+            if self.parser.is_whitespace(ch) || self.is_punctuation(ch) {
+                break;
+            }
+            self.chars.next();
+
+            match ch {
+                '"' | '\'' if !self.parser.literal_quotes => {
+                    if ch == '"' {
+                        quotes.double += 1;
+                    } else {
+                        quotes.single += 1;
+                    }
+                    self.quoted(at, ch, &mut value)?;
+                }
+                '\\' if self.parser.escapes == Escapes::Anywhere => {
+                    escapes += 1;
+                    self.escape(at, &mut value)?;
+                }
+                '=' if equals.is_none() => {
+                    equals = Some(Equals {
+                        index: value.len(),
+                        offset: at,
+                        quotes,
+                        escapes,
+                    });
+                    self.push(&mut value, ch);
+                }
+                '*' | '?' | '[' => {
+                    self.warning(WarningKind::UnquotedGlob(ch), at..at + 1);
+                    self.push(&mut value, ch);
+                }
+                ch => self.push(&mut value, ch),
+            }
+        }
+
+        let span = start..self.offset();
+        if quotes.quoting() == Quoting::Mixed {
+            self.warning(WarningKind::MixedQuotes, span.clone());
+        }
+        if self.discard {
+            return Ok(Token {
+                value,
+                span,
+                assignment: None,
+                plain: quotes.count() + escapes == 0,
+                quoting: quotes.quoting(),
+                comment: false,
+            });
+        }
+        let (value, assignment) = if self.parser.keep_quotes {
+            let raw = self.input[span.clone()].to_string();
+            (raw, equals.map(|equals| equals.offset - start))
+        } else {
+            (value, equals.map(|equals| equals.index))
+        };
+
+        let (value, assignment) = if self.parser.percent_decode && !self.parser.keep_quotes {
+            let decoded = each_side(&value, assignment, |side| {
+                percent_decode(side).unwrap_or_else(|| side.to_string())
+            });
+            // A decoded token was escaped, if only in a different way.
+            if decoded.0 != value {
+                escapes += 1;
+            }
+            decoded
+        } else {
+            (value, assignment)
+        };
+
+        #[cfg(feature = "unicode-normalization")]
+        let (value, assignment) = match self.parser.normalization {
+            Some(form) => normalize(form, &value, assignment),
+            None => (value, assignment),
+        };
+
+        let (value, assignment) = match self.parser.forbidden_characters {
+            ForbiddenCharacters::Escape if !self.parser.forbidden.is_empty() => {
+                escape_forbidden(&self.parser.forbidden, &value, assignment)
+            }
+            _ => (value, assignment),
+        };
+
+        let token = Token {
+            value,
+            span,
+            assignment,
+            plain: quotes.count() + escapes == 0,
+            quoting: quotes.quoting(),
+            comment: false,
+        };
+
+        if token.plain && token.value == "--" {
+            self.options_ended = true;
+        }
+
+        match equals {
+            Some(equals) if self.parser.split_long_options && !self.options_ended => {
+                Ok(self.split_long_option(token, equals, quotes, escapes))
+            }
+            _ => Ok(token),
+        }
+    }
+
+    /// Splits a `--flag=value` token in two, returning the flag and queueing the value to be
+    /// returned next. `quotes` and `escapes` count those found in the whole token.
+    fn split_long_option(
+        &mut self,
+        mut flag: Token,
+        equals: Equals,
+        quotes: Quotes,
+        escapes: usize,
+    ) -> Token {
+        let at = match flag.assignment {
+            Some(at) if at > 2 && flag.value.starts_with("--") => at,
+            _ => return flag,
+        };
+
+        let value = flag.value.split_off(at + 1);
+        flag.value.pop();
+        self.pending = Some(Token {
+            value,
+            span: equals.offset + 1..flag.span.end,
+            assignment: None,
+            plain: quotes.count() + escapes == equals.quotes.count() + equals.escapes,
+            quoting: quotes.since(equals.quotes).quoting(),
+            comment: false,
+        });
+
+        Token {
+            span: flag.span.start..equals.offset,
+            assignment: None,
+            plain: equals.quotes.count() + equals.escapes == 0,
+            quoting: equals.quotes.quoting(),
+            ..flag
+        }
+    }
+
+    /// Reads the remainder of a string opened by the `delim` quote at byte offset `open`.
+    fn quoted(&mut self, open: usize, delim: char, value: &mut String) -> Result<(), ParseError> {
+        self.mark(open..open + 1, Highlight::QuoteDelimiter);
+        while let Some((at, ch)) = self.chars.next() {
+            match ch {
+                ch if ch == delim => {
+                    self.mark(open + 1..at, Highlight::Quoted);
+                    self.mark(at..at + 1, Highlight::QuoteDelimiter);
+                    return Ok(());
+                }
+                '\\' if self.escapes_within(delim) => self.escape(at, value)?,
+                ch => self.push(value, ch),
+            }
+        }
+
+        // The end of the input closes the quote when quotes close automatically, and also when
+        // recovering from errors, after the error is recorded.
+        let end = self.offset();
+        self.mark(open + 1..end, Highlight::Quoted);
+        if self.parser.auto_close_quotes {
+            return Ok(());
+        }
+        let span = open..end;
+        self.report(ParseError::new(ErrorKind::UnterminatedQuote(delim), span))
+    }
+
+    /// Whether a backslash just read within a string quoted by `delim` begins an escape sequence.
+    fn escapes_within(&mut self, delim: char) -> bool {
+        match self.parser.escapes {
+            Escapes::Anywhere => true,
+            Escapes::Never => false,
+            Escapes::DoubleQuoted => delim == '"' && matches!(self.peek(), Some('"' | '\\')),
+        }
+    }
+
+    /// Whether `ch` may follow a backslash when escapes are strict.
+    fn escapable(&self, ch: char) -> bool {
+        matches!(ch, '\\' | '"' | '\'') || ch.is_whitespace() || self.is_punctuation(ch)
+    }
+
+    /// Reads the escape sequence begun by a backslash at byte offset `at`, appending what it
+    /// produces to `value`.
+    fn escape(&mut self, at: usize, value: &mut String) -> Result<(), ParseError> {
+        let policy = self.parser.trailing_backslash;
+        let next = self.chars.next();
+        let end = self.offset();
+        self.mark(at..end, Highlight::Escape);
+
+        let custom = next.and_then(|(_, ch)| {
+            let sequences = &self.parser.escape_sequences;
+            sequences.iter().find(|&&(registered, _)| registered == ch)
+        });
+        if let Some((_, expansion)) = custom {
+            self.expanded += expansion.len();
+            if self
+                .parser
+                .max_expansion
+                .map_or(false, |max| self.expanded > max)
+            {
+                let kind = ErrorKind::LimitsExceeded(Limit::ExpansionSize);
+                return Err(ParseError::new(kind, at..end));
+            }
+            self.push_str(value, expansion);
+            return Ok(());
+        }
+
+        match next {
+            Some((_, 'n')) => self.push(value, '\n'),
+            Some((_, 'r')) => self.push(value, '\r'),
+            Some((_, 't')) => self.push(value, '\t'),
+            Some((_, '\n')) if policy == TrailingBackslash::Continue => {}
+            Some((_, literal)) if self.parser.strict_escapes && !self.escapable(literal) => {
+                let span = at..self.offset();
+                self.report(ParseError::new(ErrorKind::UnknownEscape(literal), span))?;
+                self.push(value, literal);
+            }
+            Some((_, literal)) => {
+                if !self.escapable(literal) {
+                    let span = at..self.offset();
+                    self.warning(WarningKind::UnnecessaryEscape(literal), span);
+                }
+                self.push(value, literal);
+            }
+            None if policy == TrailingBackslash::Literal => self.push(value, '\\'),
+            None => {
+                let span = at..at + 1;
+                self.report(ParseError::new(ErrorKind::TrailingBackslash, span))?;
+                self.push(value, '\\');
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Lexer<'_, '_> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        // A rejected character ends the input early, so it takes precedence over any other
+        // problem, and over a token it cut short.
+        let token = match self.pending.take() {
+            Some(pending) => Some(Ok(pending)),
+            None => match (self.scan(), self.chars.error()) {
+                (_, Some(error)) => Some(Err(error)),
+                (token, None) => token,
+            },
+        };
+        let token = match token {
+            Some(Ok(token)) => Some(self.count(token)),
+            token => token,
+        };
+        self.failed = matches!(token, Some(Err(_)));
+        match token {
+            // Only passing a limit fails while recovering, and it ends the input all the same.
+            Some(Err(error)) if self.recover => {
+                self.errors.push(error);
+                None
+            }
+            token => token,
+        }
+    }
+}
+
+impl Lexer<'_, '_> {
+    /// Counts a token read, failing if there are more than [`Parser::max_tokens`].
+    fn count(&mut self, token: Token) -> Result<Token, ParseError> {
+        self.tokens += 1;
+        match self.parser.max_tokens {
+            Some(max) if self.tokens > max => {
+                let kind = ErrorKind::LimitsExceeded(Limit::TokenCount);
+                Err(ParseError::new(kind, token.span))
+            }
+            _ => Ok(token),
+        }
+    }
+
+    /// Reads the next token, skipping any preceding whitespace and comments.
+    fn scan(&mut self) -> Option<Result<Token, ParseError>> {
+        let start = loop {
+            self.skip_separators();
+            let start = self.offset();
+            match self.peek() {
+                Some('\n') if !self.punctuation.contains(&'\n') => match self.parser.newlines {
+                    Newlines::Terminate => return None,
+                    // Only custom separators can leave a newline here, and it is then read as
+                    // token text, just as it would be within a token.
+                    Newlines::Separate => {}
+                    Newlines::Reject => {
+                        let span = start..start + 1;
+                        if let Err(error) =
+                            self.report(ParseError::new(ErrorKind::UnexpectedNewline, span))
+                        {
+                            return Some(Err(error));
+                        }
+                        self.chars.next();
+                        continue;
+                    }
+                },
+                Some(ch) if !self.is_punctuation(ch) => {}
+                _ => return None,
+            }
+
+            match self.comment(start) {
+                Some(comment) if self.comments => return Some(Ok(comment)),
+                Some(_) => continue,
+                None => break start,
+            }
+        };
+
+        let max = self.parser.max_token_length;
+        self.chars.token = max.map(|max| (start, max));
+        let token = self.token(start);
+        // Unless already rejected, a token one byte too long is only seen to be so once it ends.
+        let unrejected = self.chars.token.take().is_some();
+        match (token, max) {
+            (Ok(token), Some(max)) if unrejected && self.offset() - start > max => {
+                let error = ParseError::new(ErrorKind::TokenTooLong(max), start..self.offset());
+                match self.report(error) {
+                    Ok(()) => Some(Ok(token)),
+                    Err(error) => Some(Err(error)),
+                }
+            }
+            (token, _) => Some(token),
+        }
+    }
+}
+
+/// The characters of the input, with control characters, forbidden characters and byte order
+/// marks handled according to the parser's policy. A rejected character ends the input early, and is reported by
+/// [`Source::error`].
+struct Source<'p, 'a> {
+    parser: &'p Parser,
+    chars: CharIndices<'a>,
+    /// The byte offset within the input at which `chars` begins.
+    base: usize,
+    peeked: Option<Option<(usize, char)>>,
+    error: Option<ParseError>,
+    /// The start of the token being read and its maximum length, past which characters are
+    /// rejected.
+    token: Option<(usize, usize)>,
+    /// Whether rejected characters are recorded in `errors` and kept, rather than ending the
+    /// input.
+    recover: bool,
+    errors: Vec<ParseError>,
+}
+
+impl<'p, 'a> Source<'p, 'a> {
+    fn new(parser: &'p Parser, input: &'a str) -> Self {
+        Self::starting_at(parser, input, 0)
+    }
+
+    fn starting_at(parser: &'p Parser, input: &'a str, start: usize) -> Self {
+        Self {
+            parser,
+            chars: input[start..].char_indices(),
+            base: start,
+            peeked: None,
+            error: None,
+            token: None,
+            recover: false,
+            errors: Vec::new(),
+        }
+    }
+
+    fn read(&mut self) -> Option<(usize, char)> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        loop {
+            let (at, ch) = self.chars.next()?;
+            let at = self.base + at;
+            if self
+                .parser
+                .max_input_length
+                .map_or(false, |max| at + ch.len_utf8() > max)
+            {
+                // The input past the limit is never read, even when recovering from errors.
+                let end = at + ch.len_utf8() + self.chars.as_str().len();
+                let kind = ErrorKind::LimitsExceeded(Limit::InputLength);
+                self.error = Some(ParseError::new(kind, at..end));
+                return None;
+            }
+            if let Some((start, max)) = self.token {
+                if at - start > max {
+                    self.token = None;
+                    let error = ParseError::new(ErrorKind::TokenTooLong(max), start..at);
+                    if !self.reject(error) {
+                        return None;
+                    }
+                }
+            }
+            if ch == '\u{feff}' && self.parser.strip_bom {
+                if at == 0 {
+                    continue;
+                }
+                let span = at..at + ch.len_utf8();
+                if !self.reject(ParseError::new(ErrorKind::ByteOrderMark, span)) {
+                    return None;
+                }
+                return Some((at, ch));
+            }
+            if self.parser.forbidden.contains(&ch) {
+                match self.parser.forbidden_characters {
+                    ForbiddenCharacters::Escape => return Some((at, ch)),
+                    ForbiddenCharacters::Strip => continue,
+                    ForbiddenCharacters::Reject => {
+                        let span = at..at + ch.len_utf8();
+                        if !self.reject(ParseError::new(ErrorKind::ForbiddenCharacter(ch), span)) {
+                            return None;
+                        }
+                        return Some((at, ch));
+                    }
+                }
+            }
+            if !self.parser.is_control(ch) {
+                return Some((at, ch));
+            }
+
+            match self.parser.control_characters {
+                ControlCharacters::Allow => return Some((at, ch)),
+                ControlCharacters::Strip => {}
+                ControlCharacters::Reject => {
+                    let span = at..at + ch.len_utf8();
+                    if !self.reject(ParseError::new(ErrorKind::ControlCharacter(ch), span)) {
+                        return None;
+                    }
+                    return Some((at, ch));
+                }
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<(usize, char)> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read());
+        }
+        self.peeked.flatten()
+    }
+
+    /// Records a rejected character, returning whether it should be kept because the source is
+    /// recovering from errors. Otherwise, the input ends early.
+    fn reject(&mut self, error: ParseError) -> bool {
+        if self.recover {
+            self.errors.push(error);
+        } else {
+            self.error = Some(error);
+        }
+        self.recover
+    }
+
+    /// The rejected character that ended the input early, if any.
+    fn error(&self) -> Option<ParseError> {
+        self.error
+    }
+}
+
+impl Iterator for Source<'_, '_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.read(),
+        }
+    }
+}
+
+/// Normalizes a token, keeping the position of its `=` intact by normalizing either side of it
+/// separately.
+#[cfg(feature = "unicode-normalization")]
+fn normalize(
+    form: Normalization,
+    value: &str,
+    assignment: Option<usize>,
+) -> (String, Option<usize>) {
+    use unicode_normalization::UnicodeNormalization;
+
+    each_side(value, assignment, |text| match form {
+        Normalization::Nfc => text.nfc().collect(),
+        Normalization::Nfkc => text.nfkc().collect(),
+    })
+}
+
+/// Precedes each of the `forbidden` characters in a token with a backslash, returning the result
+/// and the new index of its `=`.
+fn escape_forbidden(
+    forbidden: &[char],
+    value: &str,
+    assignment: Option<usize>,
+) -> (String, Option<usize>) {
+    let mut escaped = String::with_capacity(value.len());
+    let mut moved = None;
+    for (at, ch) in value.char_indices() {
+        if forbidden.contains(&ch) {
+            escaped.push('\\');
+        }
+        if Some(at) == assignment {
+            moved = Some(escaped.len());
+        }
+        escaped.push(ch);
+    }
+    (escaped, moved)
+}
+
+/// Applies `apply` to each side of the `=` at index `assignment` in `value`, or to the whole of
+/// `value` if there is none, returning the result and the new index of the `=`.
+fn each_side<F>(value: &str, assignment: Option<usize>, apply: F) -> (String, Option<usize>)
+where
+    F: Fn(&str) -> String,
+{
+    match assignment {
+        Some(at) => {
+            let key = apply(&value[..at]);
+            let split = key.len();
+            (key + "=" + &apply(&value[at + 1..]), Some(split))
+        }
+        None => (apply(value), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::WarningKind;
+    use crate::{ControlCharacters, ErrorKind, Escapes, ForbiddenCharacters, Newlines, Parser};
+    use crate::{Limit, ParseError, Quoting, TrailingBackslash};
+    use alloc::collections::VecDeque;
+
+    #[test]
+    fn keep_quotes() {
+        let result = Parser::new()
+            .keep_quotes(true)
+            .parse("  cp \\'a b\\' \"my file\"'s'   dest\\ dir ")
+            .unwrap();
+        assert_eq!(
+            result,
+            vec!["cp", "\\'a", "b\\'", "\"my file\"'s'", "dest\\ dir"]
+        );
+    }
+
+    #[test]
+    fn keep_quotes_mismatch() {
+        assert_eq!(Parser::new().keep_quotes(true).parse("echo 'oops"), None);
+    }
+
+    #[test]
+    fn comments() {
+        let parser = Parser::new().comment("#").comment(";").keep_comments(true);
+        let result = parser.parse_tokens("echo a#b '#' # note\n; rest").unwrap();
+        let text: Vec<&str> = result.iter().map(|token| token.as_str()).collect();
+        assert_eq!(text, vec!["echo", "a#b", "#", "# note", "; rest"]);
+        assert!(result[3].is_comment() && !result[2].is_comment());
+
+        assert_eq!(parser.parse("echo a # b").unwrap(), vec!["echo", "a"]);
+    }
+
+    #[test]
+    fn trailing_backslash() {
+        let literal = Parser::new().trailing_backslash(TrailingBackslash::Literal);
+        assert_eq!(literal.parse("dir C:\\").unwrap(), vec!["dir", "C:\\"]);
+        assert_eq!(Parser::new().parse("dir C:\\"), None);
+
+        let continued = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+        assert!(continued.needs_continuation("echo 'a\\"));
+        assert!(!continued.needs_continuation("echo 'a"));
+        assert!(!literal.needs_continuation("echo \\"));
+        assert_eq!(
+            continued.parse("echo 'a\\\nb' \\\n \\\nc").unwrap(),
+            vec!["echo", "ab", "c"]
+        );
+    }
+
+    #[test]
+    fn ascii_whitespace() {
+        let input = "a\u{3000}b\tc\u{a0} d";
+        assert_eq!(
+            Parser::new().parse(input).unwrap(),
+            vec!["a", "b", "c", "d"]
+        );
+
+        let ascii = Parser::new().ascii_whitespace(true);
+        assert_eq!(
+            ascii.parse(input).unwrap(),
+            vec!["a\u{3000}b", "c\u{a0}", "d"]
+        );
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn normalization() {
+        use crate::Normalization;
+
+        let parser = Parser::new().normalize(Some(Normalization::Nfkc));
+        let tokens = parser.parse_tokens("\u{fb01}le=cafe\u{301}").unwrap();
+        assert_eq!(tokens[0].as_str(), "file=caf\u{e9}");
+        assert_eq!(tokens[0].key_value(), Some(("file", "caf\u{e9}")));
+    }
+
+    #[test]
+    fn control_characters() {
+        let reject = Parser::new().control_characters(ControlCharacters::Reject);
+        let error = reject.try_parse("echo 'a\u{7}").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ControlCharacter('\u{7}'));
+        assert_eq!(error.offset(), 7);
+        assert!(reject.try_parse("echo\t'a\nb'\r\n").is_ok());
+
+        let strip = Parser::new().control_characters(ControlCharacters::Strip);
+        assert_eq!(
+            strip.parse("\u{0}a\u{0} \\\u{7f}b").unwrap(),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn parse_one() {
+        let parser = Parser::new();
+        assert_eq!(parser.parse_one(" \t"), None);
+        assert_eq!(parser.parse_one("a\\ b"), Some((String::from("a b"), "")));
+        assert_eq!(parser.parse_one("a 'b"), Some((String::from("a"), "'b")));
+        assert_eq!(parser.parse_one("'a b"), None);
+
+        let split = Parser::new().split_long_options(true);
+        assert_eq!(
+            split.parse_one("--k=v w"),
+            Some((String::from("--k"), "v w"))
+        );
+        let forbid = Parser::new().forbid(vec!['$'], ForbiddenCharacters::Reject);
+        assert_eq!(forbid.parse_one("a $b"), None);
+    }
+
+    #[test]
+    fn parse_max() {
+        let parser = Parser::new().comment("#");
+        assert_eq!(parser.parse_max("a b", 0), Some(vec![]));
+        assert_eq!(parser.parse_max("a b", 3).unwrap(), vec!["a", "b"]);
+        assert_eq!(parser.parse_max("a b # c", 2).unwrap(), vec!["a", "b"]);
+        assert_eq!(parser.parse_max("a  b 'c", 2).unwrap(), vec!["a", "b 'c"]);
+        assert_eq!(parser.parse_max("a 'b", 2), None);
+
+        let split = Parser::new().split_long_options(true);
+        assert_eq!(split.parse_max("--k=v w", 2).unwrap(), vec!["--k", "v w"]);
+    }
+
+    #[test]
+    fn validation() {
+        let parsers = [
+            Parser::new(),
+            Parser::new().comment("#").strict_escapes(true),
+            Parser::new().keep_quotes(true).split_long_options(true),
+            Parser::new()
+                .newlines(Newlines::Reject)
+                .escape_sequence('e', "E"),
+        ];
+        let inputs = [
+            "a 'b\\' c",
+            "--k='v' # \\q",
+            "a\nb \\",
+            "x\\e \"\\\"\"",
+            "'",
+        ];
+        for parser in &parsers {
+            for input in inputs {
+                let tokens = parser.try_parse(input);
+                assert_eq!(parser.validate(input), tokens.clone().map(drop));
+                assert_eq!(
+                    parser.count_tokens(input),
+                    tokens.as_ref().ok().map(Vec::len)
+                );
+                for (n, token) in tokens.iter().flatten().enumerate() {
+                    assert_eq!(parser.nth_token(input, n).unwrap().as_str(), token);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn forbidden_characters() {
+        let escape = Parser::new().forbid(vec!['=', '$'], ForbiddenCharacters::Escape);
+        let tokens = escape.parse_tokens("a=$b \\$").unwrap();
+        assert_eq!(tokens[0].as_str(), "a\\=\\$b");
+        assert_eq!(tokens[0].key_value(), Some(("a\\", "\\$b")));
+        assert_eq!(tokens[1].as_str(), "\\$");
+
+        let strip = Parser::new().forbid(vec!['\''], ForbiddenCharacters::Strip);
+        assert_eq!(strip.parse("it's").unwrap(), vec!["its"]);
+
+        let reject = Parser::new().forbid(vec!['$'], ForbiddenCharacters::Reject);
+        let errors = reject.diagnose("$a $b");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].span(), 3..4);
+    }
+
+    #[test]
+    fn long_option_tokens() {
+        let parser = Parser::new().split_long_options(true);
+        let tokens = parser
+            .parse_tokens("--\"na=me\"=v --k='a b' \"--q\"=1")
+            .unwrap();
+        let text: Vec<&str> = tokens.iter().map(|token| token.as_str()).collect();
+        assert_eq!(text, vec!["--na=me", "v", "--k", "a b", "--q", "1"]);
+        assert!(tokens[0].is_quoted() && !tokens[1].is_quoted());
+        assert!(!tokens[2].is_quoted() && tokens[3].is_quoted());
+        assert_eq!(tokens[3].span, 16..21);
+        assert_eq!(tokens[0].quoting(), Quoting::Double);
+        assert_eq!(tokens[3].quoting(), Quoting::Single);
+    }
+
+    #[test]
+    fn strict_escapes() {
+        let parser = Parser::new().strict_escapes(true);
+        assert_eq!(parser.parse("'\\n' \"\\\\\"").unwrap(), vec!["\n", "\\"]);
+
+        let error = parser.try_parse("say \"caf\\é\"").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnknownEscape('é'));
+        assert_eq!(error.offset(), 8);
+    }
+
+    #[test]
+    fn escapes_never() {
+        let parser = Parser::new()
+            .escapes(Escapes::Never)
+            .trailing_backslash(TrailingBackslash::Continue);
+        let tokens = parser.parse_tokens("a\\ b 'c\\' \\\n").unwrap();
+        let text: Vec<&str> = tokens.iter().map(|token| token.as_str()).collect();
+        assert_eq!(text, vec!["a\\", "b", "c\\", "\\"]);
+        assert!(tokens[0].plain && !tokens[2].plain);
+        assert!(!parser.needs_continuation("a \\"));
+        assert_eq!(parser.parse("\"a\\\"b\""), None);
+    }
+
+    #[test]
+    fn literal_quotes() {
+        let parser = Parser::new().literal_quotes(true);
+        let tokens = parser.parse_tokens(r#"'a b' \"c"#).unwrap();
+        let text: Vec<&str> = tokens.iter().map(|token| token.as_str()).collect();
+        assert_eq!(text, vec!["'a", "b'", "\"c"]);
+        assert!(tokens[0].plain && !tokens[0].is_quoted() && !tokens[2].plain);
+    }
+
+    #[test]
+    fn escapes_double_quoted() {
+        let parser = Parser::new().escapes(Escapes::DoubleQuoted);
+        let result = parser.parse(r#"a\b\\ '\' "\n\\\"" \"#).unwrap();
+        assert_eq!(result, vec![r"a\b\\", r"\", r#"\n\""#, r"\"]);
+        assert_eq!(parser.parse(r#"\"a b""#), Some(vec![String::from(r"\a b")]));
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let parser = Parser::new()
+            .strict_escapes(true)
+            .escape_sequence('n', "N")
+            .escape_sequence('e', "\u{1b}")
+            .escape_sequence('e', "E");
+        assert_eq!(parser.parse(r#"\n\e "\t\e""#).unwrap(), vec!["NE", "\tE"]);
+    }
+
+    #[test]
+    fn percent_decode() {
+        let parser = Parser::new().percent_decode(true);
+        let tokens = parser.parse_tokens("a%3Db=c%3D %E2%82%AC %FF").unwrap();
+        assert_eq!(tokens[0].key_value(), Some(("a=b", "c=")));
+        assert_eq!(tokens[1].as_str(), "€");
+        assert!(tokens[2].plain && !tokens[1].plain);
+        assert_eq!(tokens[2].as_str(), "%FF");
+    }
+
+    #[test]
+    fn newlines() {
+        let parser = Parser::new()
+            .newlines(Newlines::Terminate)
+            .trailing_backslash(TrailingBackslash::Continue)
+            .comment("#");
+        let (tokens, rest) = parser.parse_line("a \\\nb # c\n\nd").unwrap();
+        assert_eq!(tokens, vec!["a", "b"]);
+        assert_eq!(rest, "\nd");
+        assert_eq!(parser.parse_script("a\nb").unwrap().len(), 2);
+
+        let parser = Parser::new().newlines(Newlines::Reject);
+        assert_eq!(
+            parser.parse_line("a 'b\n'").unwrap(),
+            (vec![String::from("a"), String::from("b\n")], "")
+        );
+        assert_eq!(parser.try_parse("a\n").unwrap_err().offset(), 1);
+
+        let separate = Parser::new().separators(vec![',']);
+        assert_eq!(separate.try_parse("a,\nb").unwrap(), vec!["a", "\nb"]);
+        assert_eq!(separate.try_parse("a,b\nc").unwrap(), vec!["a", "b\nc"]);
+        assert_eq!(separate.try_parse("\na").unwrap(), vec!["\na"]);
+        let terminate = separate.clone().newlines(Newlines::Terminate);
+        assert_eq!(
+            terminate.parse_line("a,b\nc").unwrap(),
+            (vec![String::from("a"), String::from("b")], "c")
+        );
+        assert_eq!(terminate.try_parse("a,\nb").unwrap(), vec!["a"]);
+        let reject = separate.newlines(Newlines::Reject);
+        assert_eq!(reject.try_parse("a,\nb").unwrap_err().offset(), 2);
+        assert_eq!(reject.try_parse("a,b\nc").unwrap_err().offset(), 3);
+    }
+
+    #[test]
+    fn strip_bom() {
+        let parser = Parser::new().strip_bom(true);
+        let tokens = parser.parse_tokens("\u{feff}'a b'").unwrap();
+        assert_eq!(tokens[0].as_str(), "a b");
+        assert_eq!(tokens[0].span, 3..8);
+        assert_eq!(parser.try_parse("'\u{feff}'").unwrap_err().offset(), 1);
+        assert_eq!(Parser::new().parse("\u{feff}a").unwrap(), vec!["\u{feff}a"]);
+    }
+
+    #[test]
+    fn diagnose() {
+        let parser = Parser::new()
+            .control_characters(ControlCharacters::Reject)
+            .newlines(Newlines::Reject)
+            .strip_bom(true);
+        let errors = parser.diagnose("\u{feff}a\u{7}\n\u{feff}b \\");
+        let kinds: Vec<ErrorKind> = errors.iter().map(|error| error.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ErrorKind::ControlCharacter('\u{7}'),
+                ErrorKind::UnexpectedNewline,
+                ErrorKind::ByteOrderMark,
+                ErrorKind::TrailingBackslash,
+            ]
+        );
+        assert!(parser.diagnose("a 'b\n'").is_empty());
+
+        let separate = Parser::new().separators(vec![',']);
+        assert!(separate.diagnose("a,\nb").is_empty());
+        assert!(separate.diagnose("\na").is_empty());
+        let errors = separate.newlines(Newlines::Reject).diagnose("a,\nb");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), ErrorKind::UnexpectedNewline);
+        assert_eq!(errors[0].offset(), 2);
+    }
+
+    #[test]
+    fn auto_close_quotes() {
+        let parser = Parser::new().auto_close_quotes(true).keep_quotes(true);
+        let tokens = parser.parse_tokens("a 'b \"c").unwrap();
+        assert_eq!(tokens[1].as_str(), "'b \"c");
+        assert!(tokens[1].is_quoted());
+        assert_eq!(Parser::new().auto_close_quotes(true).parse("'a\\"), None);
+    }
+
+    #[test]
+    fn lazy_tokens() {
+        let parser = Parser::new();
+        let mut tokens = parser.tokens("a b 'c");
+        assert_eq!(tokens.by_ref().take(2).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(tokens.error(), None);
+        assert_eq!(tokens.next(), None);
+        assert_eq!(
+            tokens.error().unwrap().kind(),
+            ErrorKind::UnterminatedQuote('\'')
+        );
+        assert_eq!(tokens.next(), None);
+    }
+
+    #[test]
+    fn warnings() {
+        let (_, warnings) = Parser::new()
+            .parse_with_warnings(r#"'*' "a"'b' \[ "\$" x\ y"#)
+            .unwrap();
+        let kinds: Vec<WarningKind> = warnings.iter().map(|warning| warning.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                WarningKind::MixedQuotes,
+                WarningKind::UnnecessaryEscape('['),
+                WarningKind::UnnecessaryEscape('$'),
+            ]
+        );
+        assert_eq!(warnings[0].span(), 4..10);
+    }
+
+    #[test]
+    fn max_token_length() {
+        let parser = Parser::new().max_token_length(Some(3));
+        assert_eq!(parser.parse("abc 'a'").unwrap(), vec!["abc", "a"]);
+        for (input, span) in [("abcd", 0..4), ("a abcde", 2..6), ("\"ab cd\"", 0..4)] {
+            let error = parser.try_parse(input).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::TokenTooLong(3));
+            assert_eq!(error.span(), span);
+        }
+        let (_, errors) = parser.parse_lossy("abcdef abcd");
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn reuses_tokens() {
+        let parser = Parser::new()
+            .split_long_options(true)
+            .comment("#")
+            .keep_comments(true);
+        let input = "a --b='c d' e # f";
+        let expected = parser.try_parse(input).unwrap();
+        let mut tokens = vec![String::from("old"); 9];
+        parser.parse_into(input, &mut tokens).unwrap();
+        assert_eq!(tokens, expected);
+
+        let mut tokens: Vec<String> = (0..2).map(|_| String::with_capacity(64)).collect();
+        parser.parse_reusing(input, &mut tokens).unwrap();
+        assert_eq!(tokens, expected);
+        assert_eq!(tokens[0].capacity(), 64);
+        parser.parse_reusing("x", &mut tokens).unwrap();
+        assert_eq!(tokens, ["x"]);
+
+        let error = parser.parse_reusing("y 'z", &mut tokens).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('\''));
+        assert_eq!(tokens, ["y"]);
+    }
+
+    #[test]
+    fn extends_collections() {
+        let parser = Parser::new();
+        let mut queue = VecDeque::from(vec![String::from("first")]);
+        parser.parse_extend("a 'b c'", &mut queue).unwrap();
+        assert_eq!(queue, ["first", "a", "b c"]);
+
+        let error = parser.parse_extend("d \"e", &mut queue).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+        assert_eq!(queue.back().unwrap(), "d");
+    }
+
+    #[test]
+    fn limits() {
+        let kind = ErrorKind::LimitsExceeded;
+        let parser = Parser::new().max_input_length(Some(4));
+        let error = parser.try_parse("a b é").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.span()),
+            (kind(Limit::InputLength), 4..6)
+        );
+        let parser = Parser::new().max_input_length(Some(5));
+        assert_eq!(parser.try_parse("abcd").unwrap(), ["abcd"]);
+        for (input, span) in [("abcdé", 4..6), ("\\#éé", 4..6)] {
+            let error = parser.try_parse(input).unwrap_err();
+            assert_eq!(
+                (error.kind(), error.span()),
+                (kind(Limit::InputLength), span)
+            );
+        }
+        let parser = Parser::new().max_input_length(Some(4));
+        let (tokens, errors) = parser.parse_lossy("a 'bcdef");
+        assert_eq!(tokens, vec!["a"]);
+        assert_eq!(errors[1].kind(), kind(Limit::InputLength));
+
+        let parser = Parser::new().max_tokens(Some(2)).split_long_options(true);
+        assert_eq!(parser.parse("a --b").unwrap(), vec!["a", "--b"]);
+        let error = parser.try_parse("a --b=c").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.span()),
+            (kind(Limit::TokenCount), 6..7)
+        );
+        let error = ParseError::new(kind(Limit::TokenCount), 4..5);
+        assert_eq!(parser.diagnose("a b c d"), vec![error]);
+
+        let parser = Parser::new()
+            .max_expansion(Some(4))
+            .escape_sequence('e', "ee");
+        assert_eq!(parser.parse(r"\e\e").unwrap(), vec!["eeee"]);
+        let error = parser.try_parse(r"\e \e \e").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.span()),
+            (kind(Limit::ExpansionSize), 6..8)
+        );
+        let aliases = |name: &str| match name {
+            "l" => Some(String::from("ls")),
+            "ll" => Some(String::from("l -a")),
+            _ => None,
+        };
+        assert!(parser.parse_with_aliases("l", aliases).is_some());
+        assert!(parser.parse_with_aliases("ll", aliases).is_none());
+    }
+}
@@ -0,0 +1,52 @@
+//! Line and column positions within input.
+
+use core::fmt;
+
+/// A 1-based line and column within some input, as shown in user-facing messages. Columns count
+/// Unicode scalar values rather than bytes, and lines are separated by `\n`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    /// The line number, starting from 1.
+    pub line: usize,
+    /// The column number, starting from 1.
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of byte offset `offset` within `input`. Offsets beyond the end of the input
+    /// are clamped to it, and offsets within a character refer to that character.
+    ///
+    /// ```
+    /// use comma::Position;
+    ///
+    /// let input = "ls\nécho hi";
+    /// assert_eq!(Position::of(input, 0), Position { line: 1, column: 1 });
+    /// assert_eq!(Position::of(input, 9), Position { line: 2, column: 6 });
+    /// ```
+    pub fn of(input: &str, offset: usize) -> Self {
+        let offset = char_boundary(input, offset);
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// The start of the character containing byte offset `offset` within `input`, or the end of the
+/// input if the offset lies beyond it.
+pub(crate) fn char_boundary(input: &str, offset: usize) -> usize {
+    let mut offset = offset.min(input.len());
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
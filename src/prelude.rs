@@ -0,0 +1,6 @@
+//! The items of `alloc` that the standard prelude would otherwise provide, imported by every
+//! module so that the crate also builds without `std`.
+
+pub(crate) use alloc::string::{String, ToString};
+pub(crate) use alloc::vec::Vec;
+pub(crate) use alloc::{format, vec};
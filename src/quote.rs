@@ -0,0 +1,137 @@
+//! Encoding of tokens for transport within other formats.
+
+use crate::prelude::*;
+
+/// Quotes `token`, if necessary, so that a default [`Parser`](crate::Parser) parses it back to
+/// exactly one token with the same text. Tokens without whitespace, quotation marks or
+/// backslashes are left bare; others are single-quoted, or double-quoted should they contain a
+/// single quote, with backslashes and the enclosing quotation mark escaped.
+///
+/// ```
+/// use comma::{parse_command, quote};
+///
+/// assert_eq!(quote("report.txt"), "report.txt");
+/// assert_eq!(quote("My Documents"), "'My Documents'");
+/// assert_eq!(quote("it's"), r#""it's""#);
+/// assert_eq!(quote(""), "''");
+///
+/// let command = format!("rm {}", quote(r"C:\Temp Files"));
+/// assert_eq!(parse_command(&command).unwrap(), vec!["rm", r"C:\Temp Files"]);
+/// ```
+pub fn quote(token: &str) -> String {
+    quote_with(token, |_| false)
+}
+
+/// Quotes `token` like [`quote`], but also if it contains any character for which `special`
+/// returns true.
+pub(crate) fn quote_with(token: &str, special: impl Fn(char) -> bool) -> String {
+    let special = |ch: char| matches!(ch, '\'' | '"' | '\\') || ch.is_whitespace() || special(ch);
+    if !token.is_empty() && !token.contains(special) {
+        return token.to_string();
+    }
+
+    let delim = if token.contains('\'') { '"' } else { '\'' };
+    let mut quoted = String::with_capacity(token.len() + 2);
+    quoted.push(delim);
+    for ch in token.chars() {
+        if ch == delim || ch == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push(delim);
+    quoted
+}
+
+/// Percent-encodes `token` so that it contains only ASCII letters, digits, `-`, `.`, `_` and `~`.
+/// The result is safe to embed in a URL or HTTP query parameter, and is never split or altered by
+/// parsing, so a parser with [`Parser::percent_decode`](crate::Parser::percent_decode) enabled
+/// recovers the original token.
+///
+/// ```
+/// use comma::{percent_encode, Parser};
+///
+/// let encoded = percent_encode("it's 100%");
+/// assert_eq!(encoded, "it%27s%20100%25");
+///
+/// let parser = Parser::new().percent_decode(true);
+/// let command = format!("say {}", encoded);
+/// assert_eq!(parser.parse(&command).unwrap(), vec!["say", "it's 100%"]);
+/// ```
+pub fn percent_encode(token: &str) -> String {
+    let mut encoded = String::with_capacity(token.len());
+    for &byte in token.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decodes `%XX` sequences in `text`. A `%` not followed by two hexadecimal digits is kept
+/// literally. Returns None if the decoded bytes are not valid UTF-8.
+pub(crate) fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+
+    while at < bytes.len() {
+        let escaped = match bytes[at..] {
+            [b'%', high, low, ..] => hex(high).and_then(|high| Some(high << 4 | hex(low)?)),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                at += 3;
+            }
+            None => {
+                decoded.push(bytes[at]);
+                at += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// The value of an ASCII hexadecimal digit.
+fn hex(digit: u8) -> Option<u8> {
+    char::from(digit).to_digit(16).map(|value| value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::percent_decode;
+    use crate::{parse_command, percent_encode, quote};
+
+    #[test]
+    fn quote_round_trip() {
+        for token in [
+            "",
+            "a",
+            "a b",
+            "'",
+            "\"",
+            "\\",
+            "it's \"x\"\\",
+            "\n\t",
+            "é\u{a0}",
+        ] {
+            assert_eq!(parse_command(&quote(token)).unwrap(), vec![token]);
+        }
+    }
+
+    #[test]
+    fn percent_round_trip() {
+        let token = "naïve \"quotes\"\t50%";
+        assert_eq!(percent_decode(&percent_encode(token)).unwrap(), token);
+        assert_eq!(percent_decode("50% %zz %4").unwrap(), "50% %zz %4");
+        assert_eq!(percent_decode("%+1"), Some(String::from("%+1")));
+        assert_eq!(percent_decode("%FF"), None);
+    }
+}
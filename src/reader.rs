@@ -0,0 +1,319 @@
+//! Parsing of commands read a line at a time from an [`io::BufRead`].
+
+use crate::{ErrorKind, Limit, ParseError, Parser};
+use core::fmt;
+use std::error::Error;
+use std::io::{self, BufRead};
+
+/// An error reading a command with [`ReadCommands`].
+#[derive(Debug)]
+pub enum ReadError {
+    /// The input could not be read.
+    Io(io::Error),
+    /// A command was malformed. Its offsets are relative to the command's text, in which the
+    /// lines it spans are joined by `\n`.
+    Parse {
+        /// The 1-based line on which the command begins.
+        line: usize,
+        /// The problem found.
+        error: ParseError,
+    },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(error) => error.fmt(f),
+            ReadError::Parse { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(error) => Some(error),
+            ReadError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+/// Joins lines, read a chunk at a time, into commands.
+pub(crate) struct Lines {
+    parser: Parser,
+    /// The lines of the command being read, without their line terminators.
+    command: String,
+    /// The number of lines joined into `command`.
+    joined: usize,
+    /// The number of whole lines read so far.
+    read: usize,
+    /// The bytes read of the line being read.
+    line: Vec<u8>,
+    /// The length of `line` when it was last checked for an overlong token.
+    checked: usize,
+    /// Whether the rest of the line being read is discarded, after an overlong token.
+    skipping: bool,
+}
+
+impl Lines {
+    pub(crate) fn new(parser: Parser) -> Self {
+        Self {
+            parser,
+            command: String::new(),
+            joined: 0,
+            read: 0,
+            line: Vec::new(),
+            checked: 0,
+            skipping: false,
+        }
+    }
+
+    /// Reads from the start of `data` up to the end of its first line, returning the number of
+    /// bytes read and the command completed, if any. An error reading the input is fatal.
+    pub(crate) fn feed(&mut self, data: &[u8]) -> (usize, Option<Result<Vec<String>, ReadError>>) {
+        let (read, ended) = match data.iter().position(|&byte| byte == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (data.len(), false),
+        };
+        if !self.skipping {
+            self.line.extend_from_slice(&data[..read]);
+        }
+
+        let command = match (ended, self.skipping) {
+            (true, true) => {
+                self.read += 1;
+                self.skipping = false;
+                None
+            }
+            (true, false) => self.end_line(),
+            (false, true) => None,
+            (false, false) => self.check_line(),
+        };
+        (read, command)
+    }
+
+    /// Ends the input, returning the unfinished command, if any.
+    pub(crate) fn finish(&mut self) -> Option<Result<Vec<String>, ReadError>> {
+        if self.skipping {
+            self.skipping = false;
+            return None;
+        }
+        if !self.line.is_empty() {
+            if let Some(command) = self.end_line() {
+                return Some(command);
+            }
+        }
+        match self.joined {
+            0 => None,
+            _ => self.parse(),
+        }
+    }
+
+    /// Joins the line read to the command, returning the command if it is now complete.
+    fn end_line(&mut self) -> Option<Result<Vec<String>, ReadError>> {
+        let line = match String::from_utf8(core::mem::take(&mut self.line)) {
+            Ok(line) => line,
+            Err(_) => {
+                let message = "stream did not contain valid UTF-8";
+                let error = io::Error::new(io::ErrorKind::InvalidData, message);
+                return Some(Err(ReadError::Io(error)));
+            }
+        };
+        self.checked = 0;
+        self.read += 1;
+        if self.joined > 0 {
+            self.command.push('\n');
+        }
+        self.joined += 1;
+
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        self.command
+            .push_str(line.strip_suffix('\r').unwrap_or(line));
+        if self.parser.classify(&self.command).is_complete() {
+            self.parse()
+        } else {
+            None
+        }
+    }
+
+    /// Checks the line being read for a command or token longer than the parser allows, so that a
+    /// line is never buffered long after it is known to fail. Tokens are checked each time the
+    /// line grows by the maximum token length.
+    fn check_line(&mut self) -> Option<Result<Vec<String>, ReadError>> {
+        let len = self.command.len() + usize::from(self.joined > 0) + self.line.len();
+        if let Some(max) = self.parser.max_input_length.filter(|&max| len > max) {
+            let kind = ErrorKind::LimitsExceeded(Limit::InputLength);
+            return Some(self.abandon(ParseError::new(kind, max..len)));
+        }
+
+        let max = self.parser.max_token_length?;
+        if self.line.len() < self.checked.saturating_add(max) {
+            return None;
+        }
+        self.checked = self.line.len();
+
+        let line = match core::str::from_utf8(&self.line) {
+            Ok(line) => line,
+            Err(error) => core::str::from_utf8(&self.line[..error.valid_up_to()]).ok()?,
+        };
+        let mut text = self.command.clone();
+        if self.joined > 0 {
+            text.push('\n');
+        }
+        text.push_str(line);
+        match self.parser.validate(&text) {
+            Err(error) if matches!(error.kind(), ErrorKind::TokenTooLong(_)) => {
+                Some(self.abandon(error))
+            }
+            _ => None,
+        }
+    }
+
+    /// Fails the command being read with `error`, discarding the rest of the line.
+    fn abandon(&mut self, error: ParseError) -> Result<Vec<String>, ReadError> {
+        let line = self.read + 1 - self.joined;
+        self.command.clear();
+        self.joined = 0;
+        self.line.clear();
+        self.checked = 0;
+        self.skipping = true;
+        Err(ReadError::Parse { line, error })
+    }
+
+    fn parse(&mut self) -> Option<Result<Vec<String>, ReadError>> {
+        let line = self.read + 1 - self.joined;
+        let result = self.parser.try_parse(&self.command);
+        self.command.clear();
+        self.joined = 0;
+        match result {
+            Ok(tokens) if tokens.is_empty() => None,
+            Ok(tokens) => Some(Ok(tokens)),
+            Err(error) => Some(Err(ReadError::Parse { line, error })),
+        }
+    }
+}
+
+/// An iterator over the commands read from an [`io::BufRead`], one per logical line. A line
+/// continues onto the next while [`Parser::classify`] finds it unfinished, such as when a quote
+/// is left open. Lines holding no tokens are skipped. Only one command is held in memory at a
+/// time, and with [`Parser::max_token_length`] or [`Parser::max_input_length`], a line is read no
+/// further than needed to find a token or command too long. See [`Parser::commands_from_reader`].
+///
+/// Iteration ends after an error reading the input, but continues after a malformed command. A
+/// command too long, or with a token too long, is abandoned at the end of the line on which it
+/// was found.
+pub struct ReadCommands<R> {
+    reader: R,
+    lines: Lines,
+    done: bool,
+}
+
+impl<R: BufRead> ReadCommands<R> {
+    pub(crate) fn new(parser: Parser, reader: R) -> Self {
+        Self {
+            reader,
+            lines: Lines::new(parser),
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ReadCommands<R> {
+    type Item = Result<Vec<String>, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let data = match self.reader.fill_buf() {
+                Ok(data) => data,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(ReadError::Io(error)));
+                }
+            };
+            if data.is_empty() {
+                self.done = true;
+                return self.lines.finish();
+            }
+
+            let (read, command) = self.lines.feed(data);
+            self.reader.consume(read);
+            if let Some(command) = command {
+                self.done = matches!(command, Err(ReadError::Io(_)));
+                return Some(command);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadError;
+    use crate::{ErrorKind, Limit, Parser, TrailingBackslash};
+    use std::io::BufReader;
+
+    #[test]
+    fn reads_commands() {
+        let input = "ls -l\r\n\n  echo 'a\nb' \\\nc\nx \"y\n";
+        let parser = Parser::new().trailing_backslash(TrailingBackslash::Continue);
+        let mut commands = parser.commands_from_reader(input.as_bytes());
+
+        assert_eq!(commands.next().unwrap().unwrap(), vec!["ls", "-l"]);
+        assert_eq!(commands.next().unwrap().unwrap(), vec!["echo", "a\nb", "c"]);
+        match commands.next() {
+            Some(Err(ReadError::Parse { line, error })) => {
+                assert_eq!(line, 6);
+                assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('"'));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(commands.next().is_none());
+    }
+
+    #[test]
+    fn bounds_tokens() {
+        let input = format!("ok 1\necho 'a{}\nnext\n", "b".repeat(100));
+        let parser = Parser::new().max_token_length(Some(16));
+        let reader = BufReader::with_capacity(4, input.as_bytes());
+        let mut commands = parser.commands_from_reader(reader);
+
+        assert_eq!(commands.next().unwrap().unwrap(), vec!["ok", "1"]);
+        match commands.next() {
+            Some(Err(ReadError::Parse { line, error })) => {
+                assert_eq!(line, 2);
+                assert_eq!(error.kind(), ErrorKind::TokenTooLong(16));
+                assert_eq!(error.span(), 5..22);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(commands.next().unwrap().unwrap(), vec!["next"]);
+        assert!(commands.next().is_none());
+    }
+
+    #[test]
+    fn bounds_commands() {
+        let input = format!("ok 1\necho 'a\n{}'\nnext\n", "b ".repeat(50));
+        let parser = Parser::new().max_input_length(Some(32));
+        let reader = BufReader::with_capacity(4, input.as_bytes());
+        let mut commands = parser.commands_from_reader(reader);
+
+        assert_eq!(commands.next().unwrap().unwrap(), vec!["ok", "1"]);
+        match commands.next() {
+            Some(Err(ReadError::Parse { line, error })) => {
+                assert_eq!(line, 2);
+                assert_eq!(error.kind(), ErrorKind::LimitsExceeded(Limit::InputLength));
+                assert_eq!(error.span(), 32..35);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(commands.next().unwrap().unwrap(), vec!["next"]);
+        assert!(commands.next().is_none());
+    }
+}
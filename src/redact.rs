@@ -0,0 +1,53 @@
+//! Masking of sensitive arguments for logging.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::ParseError;
+use crate::Parser;
+
+/// The text substituted for each sensitive value.
+const MASK: &str = "***";
+
+pub(crate) fn redact(parser: &Parser, input: &str, flags: &[&str]) -> Result<String, ParseError> {
+    // Splitting long options gives the value of `--flag=value` a token, and span, of its own.
+    let parser = parser.clone().split_long_options(true);
+    let mut output = String::with_capacity(input.len());
+    let mut copied = 0;
+    let mut sensitive = false;
+
+    for token in Lexer::new(&parser, input) {
+        let token = token?;
+        if sensitive {
+            output.push_str(&input[copied..token.span.start]);
+            output.push_str(MASK);
+            copied = token.span.end;
+        }
+        sensitive = flags.contains(&token.as_str());
+    }
+
+    output.push_str(&input[copied..]);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::redact_command;
+
+    #[test]
+    fn redacts_values() {
+        let flags = ["--password", "-p", "--token"];
+        let result = redact_command(
+            "login  -u 'me' -p \"s3 cret\" --token='a b' --token= --password",
+            &flags,
+        );
+        assert_eq!(
+            result.unwrap(),
+            "login  -u 'me' -p *** --token=*** --token=*** --password"
+        );
+        assert_eq!(
+            redact_command("echo '--password' x", &flags).unwrap(),
+            "echo '--password' ***"
+        );
+        assert!(redact_command("-p 'open", &flags).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Expansion of `@file` response-file arguments.
+
+use crate::prelude::*;
+use crate::{ParseError, Parser};
+use core::fmt;
+use std::error::Error;
+use std::io;
+
+/// The reason a response file could not be expanded. See [`expand_response_files`].
+#[derive(Debug)]
+pub enum ResponseFileError {
+    /// The named file could not be loaded.
+    Io {
+        /// The file's path, as written after the `@`.
+        path: String,
+        /// The problem loading it.
+        error: io::Error,
+    },
+    /// The named file's contents were not a valid command line.
+    Parse {
+        /// The file's path, as written after the `@`.
+        path: String,
+        /// The problem found, with offsets relative to the file's contents.
+        error: ParseError,
+    },
+    /// The named file was nested more deeply within other response files than allowed.
+    TooDeep {
+        /// The file's path, as written after the `@`.
+        path: String,
+    },
+}
+
+impl fmt::Display for ResponseFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResponseFileError::Io { path, error } => {
+                write!(f, "could not read response file {}: {}", path, error)
+            }
+            ResponseFileError::Parse { path, error } => {
+                write!(f, "malformed response file {}: {}", path, error)
+            }
+            ResponseFileError::TooDeep { path } => {
+                write!(f, "response file {} is nested too deeply", path)
+            }
+        }
+    }
+}
+
+impl Error for ResponseFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResponseFileError::Io { error, .. } => Some(error),
+            ResponseFileError::Parse { error, .. } => Some(error),
+            ResponseFileError::TooDeep { .. } => None,
+        }
+    }
+}
+
+/// Replaces each `@file` argument with the tokens parsed from the named file, which may itself
+/// contain further `@file` arguments up to `max_depth` levels deep. Files are read from disk; see
+/// [`expand_response_files_with`] to load them some other way.
+pub fn expand_response_files(
+    args: Vec<String>,
+    max_depth: usize,
+) -> Result<Vec<String>, ResponseFileError> {
+    expand_response_files_with(args, max_depth, |path| std::fs::read_to_string(path))
+}
+
+/// Like [`expand_response_files`], but loads each file's contents using `load`.
+/// The contents are parsed as one command line in which newlines are ordinary whitespace.
+///
+/// ```
+/// use comma::{expand_response_files_with, parse_command};
+/// use std::io;
+///
+/// let args = parse_command("cc @flags main.c").unwrap();
+/// let expanded = expand_response_files_with(args, 4, |path| match path {
+///     "flags" => Ok(String::from("-O2\n-o 'my app'")),
+///     _ => Err(io::ErrorKind::NotFound.into()),
+/// });
+/// assert_eq!(expanded.unwrap(), vec!["cc", "-O2", "-o", "my app", "main.c"]);
+/// ```
+pub fn expand_response_files_with<F>(
+    args: Vec<String>,
+    max_depth: usize,
+    mut load: F,
+) -> Result<Vec<String>, ResponseFileError>
+where
+    F: FnMut(&str) -> io::Result<String>,
+{
+    let mut output = Vec::with_capacity(args.len());
+    expand(&Parser::new(), args, max_depth, &mut load, &mut output)?;
+    Ok(output)
+}
+
+fn expand<F>(
+    parser: &Parser,
+    args: Vec<String>,
+    depth: usize,
+    load: &mut F,
+    output: &mut Vec<String>,
+) -> Result<(), ResponseFileError>
+where
+    F: FnMut(&str) -> io::Result<String>,
+{
+    for arg in args {
+        let path = match arg.strip_prefix('@') {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                output.push(arg);
+                continue;
+            }
+        };
+
+        if depth == 0 {
+            return Err(ResponseFileError::TooDeep { path: path.into() });
+        }
+
+        let contents = load(path).map_err(|error| ResponseFileError::Io {
+            path: path.into(),
+            error,
+        })?;
+        let nested = parser
+            .try_parse(&contents)
+            .map_err(|error| ResponseFileError::Parse {
+                path: path.into(),
+                error,
+            })?;
+        expand(parser, nested, depth - 1, load, output)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{expand_response_files_with, ErrorKind, ResponseFileError};
+    use std::error::Error;
+
+    #[test]
+    fn recursion_limit() {
+        let args = vec![String::from("@loop")];
+        let result = expand_response_files_with(args, 3, |_| Ok(String::from("x @loop")));
+        match result {
+            Err(ResponseFileError::TooDeep { path }) => assert_eq!(path, "loop"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_file() {
+        let args = vec![String::from("@bad"), String::from("@")];
+        let result = expand_response_files_with(args, 3, |_| Ok(String::from("a 'open")));
+        let error = result.unwrap_err();
+        match &error {
+            ResponseFileError::Parse { path, error } => {
+                assert_eq!(path, "bad");
+                assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('\''));
+                assert_eq!(error.offset(), 2);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(error.source().is_some());
+        assert!(error
+            .to_string()
+            .starts_with("malformed response file bad: "));
+    }
+}
@@ -0,0 +1,88 @@
+//! Splitting input containing several commands.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::ParseError;
+use crate::Parser;
+
+/// One command read from a script. See [`parse_script`](crate::parse_script).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptCommand {
+    /// The 1-based line on which the command's first token begins.
+    pub line: usize,
+    /// The command's tokens.
+    pub tokens: Vec<String>,
+}
+
+pub(crate) fn parse(parser: &Parser, input: &str) -> Result<Vec<ScriptCommand>, ParseError> {
+    let mut lexer = Lexer::new(parser, input).with_punctuation(&['\n', ';']);
+    let mut commands = Vec::new();
+    let mut current: Option<ScriptCommand> = None;
+
+    // Lines are counted incrementally up to the start of each command.
+    let mut line = 1;
+    let mut counted = 0;
+
+    loop {
+        lexer.skip_separators();
+        if lexer.punctuation().is_some() {
+            commands.extend(current.take());
+            continue;
+        }
+
+        let token = match lexer.next() {
+            Some(token) => token?,
+            None => break,
+        };
+
+        let command = current.get_or_insert_with(|| {
+            line += input[counted..token.span.start].matches('\n').count();
+            counted = token.span.start;
+            ScriptCommand {
+                line,
+                tokens: Vec::new(),
+            }
+        });
+        command.tokens.push(token.into_string());
+    }
+
+    commands.extend(current);
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_script, ScriptCommand};
+
+    #[test]
+    fn script_lines() {
+        let result = parse_script("cd /tmp; ls\n\n echo 'a;\nb' ;;\nexit").unwrap();
+        assert_eq!(
+            result,
+            vec![
+                ScriptCommand {
+                    line: 1,
+                    tokens: vec!["cd".into(), "/tmp".into()]
+                },
+                ScriptCommand {
+                    line: 1,
+                    tokens: vec!["ls".into()]
+                },
+                ScriptCommand {
+                    line: 3,
+                    tokens: vec!["echo".into(), "a;\nb".into()]
+                },
+                ScriptCommand {
+                    line: 5,
+                    tokens: vec!["exit".into()]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn script_escaped_terminator() {
+        let result = parse_script("echo a\\;b").unwrap();
+        assert_eq!(result[0].tokens, vec!["echo", "a;b"]);
+    }
+}
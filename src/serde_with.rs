@@ -0,0 +1,56 @@
+//! A [`serde`] `with` module that stores a `Vec<String>` field as a single command string.
+//!
+//! Annotate a field with `#[serde(with = "comma::serde_with")]` so that configuration files can
+//! write arguments the way they would be typed at a shell. Deserializing parses the string like
+//! [`try_parse_command`](crate::try_parse_command), except that a blank string produces no
+//! tokens; serializing quotes each token with [`quote`](crate::quote) and joins them with spaces.
+//!
+//! ```
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Tool {
+//!     #[serde(with = "comma::serde_with")]
+//!     extra_args: Vec<String>,
+//! }
+//!
+//! let tool: Tool = comma::de::from_str(r#"extra_args="--foo 'a b'""#)?;
+//! assert_eq!(tool.extra_args, vec!["--foo", "a b"]);
+//! # Ok::<(), comma::de::Error>(())
+//! ```
+
+use crate::prelude::*;
+use crate::{quote, Parser};
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::Serializer;
+
+/// Serializes `tokens` as one command string.
+pub fn serialize<S: Serializer>(tokens: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    let quoted: Vec<String> = tokens.iter().map(|token| quote(token)).collect();
+    serializer.serialize_str(&quoted.join(" "))
+}
+
+/// Deserializes a command string into its tokens, failing if the command is malformed.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let input = String::deserialize(deserializer)?;
+    Parser::new().try_parse(&input).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{Error, StrDeserializer};
+    use serde::de::IntoDeserializer;
+
+    #[test]
+    fn deserializes() {
+        let input: StrDeserializer<Error> = "-v 'x y'\\!".into_deserializer();
+        assert_eq!(super::deserialize(input).unwrap(), vec!["-v", "x y!"]);
+
+        let input: StrDeserializer<Error> = "  ".into_deserializer();
+        assert!(super::deserialize(input).unwrap().is_empty());
+
+        let input: StrDeserializer<Error> = "a \"b".into_deserializer();
+        let error = super::deserialize(input).unwrap_err();
+        assert_eq!(error.to_string(), "unterminated \" quote at byte 2");
+    }
+}
@@ -0,0 +1,99 @@
+//! A token iterator with lookahead, for building parsers on top of comma's tokenizer.
+
+use crate::parser::Lexer;
+use crate::{ParseError, Token};
+use alloc::collections::VecDeque;
+
+/// An iterator over the tokens of a command, parsed as they are requested, which can look ahead
+/// at tokens without consuming them. Like [`TryTokens`](crate::TryTokens), it yields an error in
+/// place of the token at which the input is found to be malformed, and ends after the error. See
+/// [`Parser::token_stream`](crate::Parser::token_stream).
+///
+/// ```
+/// use comma::Parser;
+///
+/// let parser = Parser::new();
+/// let mut stream = parser.token_stream("set volume 'to 11'");
+/// assert_eq!(stream.peek_n(2).unwrap().as_ref().unwrap().as_str(), "to 11");
+/// assert_eq!(stream.peek().unwrap().as_ref().unwrap().as_str(), "set");
+///
+/// stream.next();
+/// assert_eq!((stream.position(), stream.offset()), (1, 3));
+/// ```
+pub struct TokenStream<'p, 'a> {
+    lexer: Lexer<'p, 'a>,
+    /// Tokens read from the lexer but not yet consumed.
+    lookahead: VecDeque<Result<Token, ParseError>>,
+    position: usize,
+    offset: usize,
+}
+
+impl<'p, 'a> TokenStream<'p, 'a> {
+    pub(crate) fn new(lexer: Lexer<'p, 'a>) -> Self {
+        Self {
+            lexer,
+            lookahead: VecDeque::new(),
+            position: 0,
+            offset: 0,
+        }
+    }
+
+    /// The next token, without consuming it.
+    pub fn peek(&mut self) -> Option<&Result<Token, ParseError>> {
+        self.peek_n(0)
+    }
+
+    /// The token `n` places after the next one, without consuming any tokens. `peek_n(0)` is the
+    /// same as [`TokenStream::peek`]. Returns None if the stream ends sooner, including after an
+    /// error.
+    pub fn peek_n(&mut self, n: usize) -> Option<&Result<Token, ParseError>> {
+        while self.lookahead.len() <= n {
+            let token = self.lexer.next()?;
+            self.lookahead.push_back(token);
+        }
+        self.lookahead.get(n)
+    }
+
+    /// The number of tokens consumed so far, which is the index of the next token.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The byte offset of the input just after the last token consumed, or 0 if none has been.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Iterator for TokenStream<'_, '_> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = match self.lookahead.pop_front() {
+            Some(token) => token,
+            None => self.lexer.next()?,
+        };
+        if let Ok(token) = &token {
+            self.position += 1;
+            self.offset = token.span.end;
+        }
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+
+    #[test]
+    fn lookahead() {
+        let parser = Parser::new();
+        let mut stream = parser.token_stream("a b 'c");
+        assert!(stream.peek_n(2).unwrap().is_err());
+        assert!(stream.peek_n(3).is_none());
+        let values: Vec<_> = stream.by_ref().map(|token| token.is_ok()).collect();
+        assert_eq!(values, vec![true, true, false]);
+        assert_eq!((stream.position(), stream.offset()), (2, 3));
+        assert!(stream.peek().is_none());
+    }
+}
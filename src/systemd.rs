@@ -0,0 +1,245 @@
+//! Parsing of systemd `ExecStart=`-style command lines.
+//!
+//! The rules implemented here follow `systemd.service(5)` and `systemd.syntax(7)`: items are
+//! separated by whitespace, may be wrapped whole in single or double quotes, and may contain
+//! C-style escapes. A lone `;` separates multiple commands, and the executable may carry special
+//! prefixes that alter how it is run. Specifiers and environment variable substitution are not
+//! performed.
+
+use crate::prelude::*;
+use core::iter::Peekable;
+use core::str::Chars;
+
+/// The special prefixes given to an executable path. See [`parse_exec_start`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ExecPrefixes {
+    /// `@`: the second item is passed as `argv[0]` rather than the executable path.
+    pub argv0: bool,
+    /// `-`: a failing exit code is recorded but otherwise ignored.
+    pub ignore_failure: bool,
+    /// `:`: environment variable substitution is not applied.
+    pub no_substitution: bool,
+    /// `+`: the process is run with full privileges.
+    pub full_privileges: bool,
+    /// `!`: the process is run with elevated privileges, without changing credentials.
+    pub elevated: bool,
+    /// `!!`: like `!`, but only used when ambient capabilities are unsupported.
+    pub ambient: bool,
+}
+
+/// A single command from an `ExecStart=` line. See [`parse_exec_start`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecCommand {
+    /// The special prefixes given to the executable.
+    pub prefixes: ExecPrefixes,
+    /// The executable to run, with its prefixes removed.
+    pub path: String,
+    /// The complete argument list, beginning with `argv[0]`.
+    pub argv: Vec<String>,
+}
+
+/// One whitespace-separated item.
+struct Item {
+    text: String,
+    /// Whether this item is an unquoted, unescaped `;` separating two commands.
+    separator: bool,
+}
+
+/// Parses the value of an `ExecStart=` setting into its commands.
+/// Returns None if a quote is mismatched, an escape sequence is invalid, a command is empty, or
+/// the executable's prefixes conflict.
+///
+/// ```
+/// use comma::systemd::parse_exec_start;
+///
+/// let commands = parse_exec_start("-@/bin/sh sh -c 'echo \"hi\\tthere\"' ; /bin/true").unwrap();
+/// assert_eq!(commands.len(), 2);
+/// assert!(commands[0].prefixes.ignore_failure && commands[0].prefixes.argv0);
+/// assert_eq!(commands[0].path, "/bin/sh");
+/// assert_eq!(commands[0].argv, vec!["sh", "-c", "echo \"hi\tthere\""]);
+/// assert_eq!(commands[1].argv, vec!["/bin/true"]);
+/// ```
+pub fn parse_exec_start(value: &str) -> Option<Vec<ExecCommand>> {
+    let mut chars = value.chars().peekable();
+    let mut commands = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+
+    while let Some(item) = next_item(&mut chars) {
+        let item = item?;
+        if item.separator {
+            commands.push(command(core::mem::take(&mut current))?);
+        } else {
+            current.push(item.text);
+        }
+    }
+
+    if !current.is_empty() || !commands.is_empty() {
+        commands.push(command(current)?);
+    }
+    Some(commands)
+}
+
+/// Builds a command from its items, interpreting the executable's prefixes.
+fn command(mut items: Vec<String>) -> Option<ExecCommand> {
+    let first = items.first_mut()?;
+    let mut prefixes = ExecPrefixes::default();
+    let mut chars = first.chars().peekable();
+    let mut length = 0;
+
+    while let Some(ch) = chars.next() {
+        let flag = match ch {
+            '@' => &mut prefixes.argv0,
+            '-' => &mut prefixes.ignore_failure,
+            ':' => &mut prefixes.no_substitution,
+            '+' => &mut prefixes.full_privileges,
+            '!' if chars.next_if_eq(&'!').is_some() => {
+                length += 1;
+                &mut prefixes.ambient
+            }
+            '!' => &mut prefixes.elevated,
+            _ => break,
+        };
+        if *flag {
+            return None;
+        }
+        *flag = true;
+        length += 1;
+    }
+
+    let privileges = [
+        prefixes.full_privileges,
+        prefixes.elevated,
+        prefixes.ambient,
+    ];
+    if privileges.iter().filter(|&&set| set).count() > 1 {
+        return None;
+    }
+
+    let path = first.split_off(length);
+    if path.is_empty() {
+        return None;
+    }
+
+    let argv = if prefixes.argv0 {
+        if items.len() < 2 {
+            return None;
+        }
+        items.split_off(1)
+    } else {
+        items[0] = path.clone();
+        items
+    };
+
+    Some(ExecCommand {
+        prefixes,
+        path,
+        argv,
+    })
+}
+
+/// Reads the next item, returning None at the end of input and Some(None) if it is malformed.
+fn next_item(chars: &mut Peekable<Chars>) -> Option<Option<Item>> {
+    while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+    let first = *chars.peek()?;
+    let mut text = String::new();
+
+    if first == '"' || first == '\'' {
+        chars.next();
+        loop {
+            match chars.next() {
+                Some(ch) if ch == first => break,
+                Some('\\') => match unescape(chars) {
+                    Some(ch) => text.push(ch),
+                    None => return Some(None),
+                },
+                Some(ch) => text.push(ch),
+                None => return Some(None),
+            }
+        }
+
+        // A quoted item must end at whitespace or the end of input.
+        if chars.peek().map_or(false, |ch| !ch.is_whitespace()) {
+            return Some(None);
+        }
+        return Some(Some(Item {
+            text,
+            separator: false,
+        }));
+    }
+
+    let mut plain = true;
+    while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+        if ch == '\\' {
+            plain = false;
+            match unescape(chars) {
+                Some(ch) => text.push(ch),
+                None => return Some(None),
+            }
+        } else {
+            text.push(ch);
+        }
+    }
+
+    let separator = plain && text == ";";
+    Some(Some(Item { text, separator }))
+}
+
+/// Reads a C-style escape sequence following a backslash.
+fn unescape(chars: &mut Peekable<Chars>) -> Option<char> {
+    Some(match chars.next()? {
+        'a' => '\u{7}',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\u{b}',
+        's' => ' ',
+        'x' => code_point(digits(chars, 16, 2)?)?,
+        'u' => code_point(digits(chars, 16, 4)?)?,
+        'U' => code_point(digits(chars, 16, 8)?)?,
+        digit @ '0'..='3' => code_point(digit.to_digit(8)? * 64 + digits(chars, 8, 2)?)?,
+        ch @ ('\\' | '"' | '\'' | ';' | ' ') => ch,
+        _ => return None,
+    })
+}
+
+/// Reads exactly `count` digits in the given radix.
+fn digits(chars: &mut Peekable<Chars>, radix: u32, count: usize) -> Option<u32> {
+    let mut value = 0u32;
+    for _ in 0..count {
+        value = value.checked_mul(radix)? + chars.next()?.to_digit(radix)?;
+    }
+    Some(value)
+}
+
+/// Converts an escaped value to a character, rejecting NUL as systemd does.
+fn code_point(value: u32) -> Option<char> {
+    core::char::from_u32(value).filter(|&ch| ch != '\0')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::systemd::parse_exec_start;
+
+    #[test]
+    fn prefixes_and_escapes() {
+        let commands =
+            parse_exec_start("!!:/usr/bin/env x\\x41\\101\\100\\u00e9 a\\;b \\;").unwrap();
+        assert_eq!(commands.len(), 1);
+        let prefixes = commands[0].prefixes;
+        assert!(prefixes.ambient && prefixes.no_substitution && !prefixes.elevated);
+        assert_eq!(commands[0].argv, vec!["/usr/bin/env", "xAA@é", "a;b", ";"]);
+    }
+
+    #[test]
+    fn invalid_exec_start() {
+        assert_eq!(parse_exec_start("/bin/a ; ; /bin/b"), None);
+        assert_eq!(parse_exec_start("+!/bin/a"), None);
+        assert_eq!(parse_exec_start("--/bin/a"), None);
+        assert_eq!(parse_exec_start("@/bin/a"), None);
+        assert_eq!(parse_exec_start("/bin/a 'b'c"), None);
+        assert_eq!(parse_exec_start("/bin/a \\q"), None);
+        assert_eq!(parse_exec_start("").unwrap(), vec![]);
+    }
+}
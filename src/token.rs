@@ -0,0 +1,182 @@
+//! Tokens carrying metadata about how they were written.
+
+use crate::prelude::*;
+use crate::Position;
+use core::fmt;
+use core::ops::Range;
+
+/// The quotation marks used within a token. See [`Token::quoting`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Quoting {
+    /// No part of the token was quoted, although it may contain escape sequences.
+    Unquoted,
+    /// Only single quotes were used, as in `'a b'` or `a'b'`.
+    Single,
+    /// Only double quotes were used.
+    Double,
+    /// Both single and double quotes were used, as in `'it'"'"s`.
+    Mixed,
+}
+
+/// A single parsed token. See [`parse_tokens`](crate::parse_tokens).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token {
+    pub(crate) value: String,
+    pub(crate) span: Range<usize>,
+    /// Byte index into `value` of the first unquoted, unescaped `=`.
+    pub(crate) assignment: Option<usize>,
+    /// Whether the token was written without any quotes or escapes.
+    pub(crate) plain: bool,
+    pub(crate) quoting: Quoting,
+    pub(crate) comment: bool,
+}
+
+impl Token {
+    /// The token's text, with quotes and escapes already resolved.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Consumes the token, returning its text.
+    pub fn into_string(self) -> String {
+        self.value
+    }
+
+    /// The half-open byte range of the input from which the token was parsed, including any
+    /// quotes and escapes.
+    ///
+    /// ```
+    /// use comma::parse_tokens;
+    ///
+    /// let tokens = parse_tokens("cp  'a b' c\\ d").unwrap();
+    /// assert_eq!(tokens[1].span(), 4..9);
+    /// assert_eq!(tokens[2].span(), 10..14);
+    /// ```
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The exact text in `input`, which must be the input the token was parsed from, that
+    /// produced the token, with its quotes and backslashes intact. Should the token not lie within
+    /// `input`, the result is empty.
+    ///
+    /// ```
+    /// use comma::parse_tokens;
+    ///
+    /// let input = r#"git commit -m "fix \"x\"" a\ b"#;
+    /// let tokens = parse_tokens(input).unwrap();
+    /// assert_eq!(tokens[3].as_str(), "fix \"x\"");
+    /// assert_eq!(tokens[3].raw(input), r#""fix \"x\"""#);
+    /// assert_eq!(tokens[4].raw(input), r"a\ b");
+    /// ```
+    pub fn raw<'a>(&self, input: &'a str) -> &'a str {
+        input.get(self.span.clone()).unwrap_or_default()
+    }
+
+    /// Whether any part of this token was enclosed in quotation marks. This distinguishes a
+    /// deliberately empty argument, written as `""` or `''`, from an empty token produced any
+    /// other way.
+    ///
+    /// ```
+    /// use comma::parse_tokens;
+    ///
+    /// let tokens = parse_tokens("set name \"\" a'b'").unwrap();
+    /// assert!(tokens[2].as_str().is_empty() && tokens[2].is_quoted());
+    /// assert!(!tokens[1].is_quoted() && tokens[3].is_quoted());
+    /// ```
+    pub fn is_quoted(&self) -> bool {
+        self.quoting != Quoting::Unquoted
+    }
+
+    /// Which quotation marks enclosed parts of this token. Completion engines can use this to
+    /// treat quoted words differently from bare ones, for instance by not expanding globs within
+    /// them.
+    ///
+    /// ```
+    /// use comma::{parse_tokens, Quoting};
+    ///
+    /// let tokens = parse_tokens("ls *.rs 'a b' x\"y\" 'it'\"'\"s").unwrap();
+    /// assert_eq!(tokens[1].quoting(), Quoting::Unquoted);
+    /// assert_eq!(tokens[2].quoting(), Quoting::Single);
+    /// assert_eq!(tokens[3].quoting(), Quoting::Double);
+    /// assert_eq!(tokens[4].quoting(), Quoting::Mixed);
+    /// ```
+    pub fn quoting(&self) -> Quoting {
+        self.quoting
+    }
+
+    /// Whether this token is a comment, kept because of
+    /// [`Parser::keep_comments`](crate::Parser::keep_comments).
+    pub fn is_comment(&self) -> bool {
+        self.comment
+    }
+
+    /// The line and column in `input`, which must be the input the token was parsed from, at
+    /// which the token begins.
+    ///
+    /// ```
+    /// use comma::{parse_tokens, Position};
+    ///
+    /// let input = "echo\n  \"día\" x";
+    /// let tokens = parse_tokens(input).unwrap();
+    /// assert_eq!(tokens[1].position(input), Position { line: 2, column: 3 });
+    /// assert_eq!(tokens[2].position(input), Position { line: 2, column: 9 });
+    /// ```
+    pub fn position(&self, input: &str) -> Position {
+        Position::of(input, self.span.start)
+    }
+
+    /// Splits a `key=value` token at its first unquoted, unescaped `=`.
+    /// Returns None if there is no such `=`, or if the key would be empty.
+    ///
+    /// ```
+    /// use comma::parse_tokens;
+    ///
+    /// let tokens = parse_tokens("k=\"v w\" 'a=b' c\\=d").unwrap();
+    /// assert_eq!(tokens[0].key_value(), Some(("k", "v w")));
+    /// assert_eq!(tokens[1].key_value(), None);
+    /// assert_eq!(tokens[2].key_value(), None);
+    /// ```
+    pub fn key_value(&self) -> Option<(&str, &str)> {
+        match self.assignment {
+            Some(at) if at > 0 => Some((&self.value[..at], &self.value[at + 1..])),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+impl From<Token> for String {
+    fn from(token: Token) -> Self {
+        token.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_tokens;
+
+    #[test]
+    fn key_value_splits_first_equals() {
+        let tokens = parse_tokens("a=b=c \"x=\"y =z").unwrap();
+        assert_eq!(tokens[0].key_value(), Some(("a", "b=c")));
+        assert_eq!(tokens[1].key_value(), None);
+        assert_eq!(tokens[2].key_value(), None);
+    }
+
+    #[test]
+    fn quoted_empty() {
+        let tokens = parse_tokens("'' \\  x\\ ").unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert!(tokens[0].is_quoted());
+        assert_eq!(tokens[1].as_str(), " ");
+        assert!(!tokens[1].is_quoted() && !tokens[2].is_quoted());
+    }
+}
@@ -0,0 +1,233 @@
+//! A tokenizer fed its input in chunks, for input that arrives a piece at a time.
+
+use crate::parser::Lexer;
+use crate::prelude::*;
+use crate::{ErrorKind, Limit, ParseError, Parser, Token};
+
+/// A tokenizer that consumes input in arbitrary chunks, such as network frames or segments read
+/// by a line editor, returning each token as soon as it is known to be complete. Quotes and
+/// escapes may span chunks. The tokens, and any error, are those [`Parser::parse_tokens`] would
+/// find in the concatenated chunks, with spans describing that concatenation. See
+/// [`Parser::tokenizer`].
+///
+/// Input is kept only until the tokens it forms are returned, so a long stream of short tokens
+/// needs little memory.
+///
+/// ```
+/// use comma::Parser;
+///
+/// let parser = Parser::new();
+/// let mut tokenizer = parser.tokenizer();
+/// let mut tokens = Vec::new();
+/// for chunk in ["git com", "mit -m 'fix ", "the bug' --am", "end"] {
+///     tokens.extend(tokenizer.feed(chunk)?);
+/// }
+/// assert_eq!(tokens.len(), 4);
+/// tokens.extend(tokenizer.finish()?);
+///
+/// let tokens: Vec<_> = tokens.iter().map(|token| token.as_str()).collect();
+/// assert_eq!(tokens, ["git", "commit", "-m", "fix the bug", "--amend"]);
+/// # Ok::<(), comma::ParseError>(())
+/// ```
+pub struct Tokenizer<'p> {
+    parser: &'p Parser,
+    /// Input not yet consumed, starting just after the last word whose tokens were returned.
+    buffer: String,
+    /// The number of bytes of input removed from the front of `buffer`.
+    consumed: usize,
+    /// The number of tokens already returned from the start of `buffer`.
+    returned: usize,
+    /// Whether a `--` token was read before the start of `buffer`.
+    options_ended: bool,
+    /// The number of tokens and bytes of expansions read before the start of `buffer`.
+    usage: (usize, usize),
+    error: Option<ParseError>,
+}
+
+impl<'p> Tokenizer<'p> {
+    pub(crate) fn new(parser: &'p Parser) -> Self {
+        Self {
+            parser,
+            buffer: String::new(),
+            consumed: 0,
+            returned: 0,
+            options_ended: false,
+            usage: (0, 0),
+            error: None,
+        }
+    }
+
+    /// Appends `chunk` to the input, returning the tokens it completes. A token is complete once
+    /// the input is seen to continue past it. Fails if the input is malformed in a way that no
+    /// further input could repair; every later call then fails with the same error.
+    pub fn feed(&mut self, chunk: &str) -> Result<Vec<Token>, ParseError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let len = self.offset() + chunk.len();
+        if let Some(max) = self.parser.max_input_length.filter(|&max| len > max) {
+            // Fail at the first character that does not fit, as a lexer would.
+            let mut at = max - self.offset();
+            while !chunk.is_char_boundary(at) {
+                at -= 1;
+            }
+            let kind = ErrorKind::LimitsExceeded(Limit::InputLength);
+            let error = ParseError::new(kind, self.offset() + at..len);
+            self.error = Some(error);
+            return Err(error);
+        }
+        self.buffer.push_str(chunk);
+        self.read(false)
+    }
+
+    /// Ends the input, returning the tokens not yet returned. Fails if the input is malformed,
+    /// including when a quote is left open.
+    pub fn finish(mut self) -> Result<Vec<Token>, ParseError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.read(true)
+    }
+
+    /// The number of bytes of input fed so far.
+    pub fn offset(&self) -> usize {
+        self.consumed + self.buffer.len()
+    }
+
+    /// Lexes the buffered input, returning the complete tokens not returned before, and discards
+    /// the input that can no longer affect any token.
+    fn read(&mut self, finished: bool) -> Result<Vec<Token>, ParseError> {
+        let len = self.buffer.len();
+        let mut lexer = Lexer::new(self.parser, &self.buffer)
+            .with_comments(self.parser.keep_comments)
+            .with_options_ended(self.options_ended)
+            .with_usage(self.usage);
+        let mut tokens = Vec::new();
+        let mut read = 0;
+        // Where the next lexer can begin, and the number of tokens, `--` state and usage before it.
+        let mut resume = None;
+
+        while let Some(token) = lexer.next() {
+            match token {
+                Ok(mut token) if finished || token.span.end < len => {
+                    read += 1;
+                    if !lexer.splitting() {
+                        let state = (lexer.options_ended(), lexer.usage());
+                        resume = Some((token.span.end, read, state));
+                    }
+                    if read > self.returned {
+                        token.span =
+                            token.span.start + self.consumed..token.span.end + self.consumed;
+                        tokens.push(token);
+                    }
+                }
+                // The last token may continue in the next chunk.
+                Ok(_) => break,
+                Err(error) if !finished && incomplete(&error, len) => break,
+                Err(error) => {
+                    let error = error.shifted(self.consumed);
+                    self.error = Some(error);
+                    return Err(error);
+                }
+            }
+        }
+
+        // The lexer may have stopped short of tokens returned before, if more input is needed.
+        self.returned = self.returned.max(read);
+        if let Some((end, before, (options_ended, usage))) = resume {
+            self.buffer.drain(..end);
+            self.consumed += end;
+            self.returned -= before;
+            self.options_ended = options_ended;
+            self.usage = usage;
+        }
+        Ok(tokens)
+    }
+}
+
+/// Whether `error`, found in input `len` bytes long, might be resolved by further input.
+fn incomplete(error: &ParseError, len: usize) -> bool {
+    match error.kind() {
+        ErrorKind::UnterminatedQuote(_) | ErrorKind::TrailingBackslash => true,
+        ErrorKind::TokenTooLong(_) | ErrorKind::LimitsExceeded(_) => false,
+        _ => error.span().end >= len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, Limit, Parser};
+
+    #[test]
+    fn feeds_chunks() {
+        let parser = Parser::new().split_long_options(true);
+        let input = "say \"it's\\\" ok\" --to=all -- --x=y  z\\ ";
+        let expected = parser.parse_tokens(input).unwrap();
+
+        // Every way of cutting the input in two gives the same tokens.
+        for at in 0..=input.len() {
+            let mut tokenizer = parser.tokenizer();
+            let mut tokens = tokenizer.feed(&input[..at]).unwrap();
+            tokens.extend(tokenizer.feed(&input[at..]).unwrap());
+            assert_eq!(tokenizer.offset(), input.len());
+            tokens.extend(tokenizer.finish().unwrap());
+            assert_eq!(tokens, expected, "split at {}", at);
+        }
+
+        // And so does every way of cutting it in three.
+        for first in 0..=input.len() {
+            for second in first..=input.len() {
+                let mut tokenizer = parser.tokenizer();
+                let mut tokens = tokenizer.feed(&input[..first]).unwrap();
+                tokens.extend(tokenizer.feed(&input[first..second]).unwrap());
+                tokens.extend(tokenizer.feed(&input[second..]).unwrap());
+                tokens.extend(tokenizer.finish().unwrap());
+                assert_eq!(tokens, expected, "split at {} and {}", first, second);
+            }
+        }
+
+        let mut tokenizer = parser.tokenizer();
+        let mut tokens = Vec::new();
+        for chunk in ["--a=", "\\", "b c"] {
+            tokens.extend(tokenizer.feed(chunk).unwrap());
+        }
+        tokens.extend(tokenizer.finish().unwrap());
+        let tokens: Vec<_> = tokens.iter().map(|token| token.as_str()).collect();
+        assert_eq!(tokens, ["--a", "b", "c"]);
+
+        let mut tokenizer = parser.tokenizer();
+        assert_eq!(tokenizer.feed("a 'b").unwrap()[0].as_str(), "a");
+        assert_eq!(tokenizer.buffer, " 'b");
+        let error = tokenizer.finish().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnterminatedQuote('\''));
+        assert_eq!(error.offset(), 2);
+    }
+
+    #[test]
+    fn fails_early() {
+        let parser = Parser::new().strict_escapes(true);
+        let mut tokenizer = parser.tokenizer();
+        assert_eq!(tokenizer.feed("a \\").unwrap().len(), 1);
+        let error = tokenizer.feed("q b").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnknownEscape('q'));
+        assert_eq!(tokenizer.feed("c"), Err(error));
+    }
+
+    #[test]
+    fn applies_limits() {
+        let parser = Parser::new().max_tokens(Some(3)).max_input_length(Some(12));
+        let mut tokenizer = parser.tokenizer();
+        assert_eq!(tokenizer.feed("a b").unwrap().len(), 1);
+        assert_eq!(tokenizer.feed(" c").unwrap().len(), 1);
+        let error = tokenizer.feed(" d").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::LimitsExceeded(Limit::TokenCount));
+        assert_eq!(error.span(), 6..7);
+
+        let mut tokenizer = parser.tokenizer();
+        assert_eq!(tokenizer.feed("'0123456789").unwrap().len(), 0);
+        let error = tokenizer.feed("é'").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::LimitsExceeded(Limit::InputLength));
+        assert_eq!(error.span(), 11..14);
+        assert_eq!(parser.try_parse("'0123456789é'"), Err(error));
+    }
+}
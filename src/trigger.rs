@@ -0,0 +1,52 @@
+//! Recognition of command prefixes, such as the `!` in chat-bot commands.
+
+use crate::prelude::*;
+use crate::Parser;
+
+/// Strips the longest of `triggers` that `input` begins with, returning the trigger and the
+/// remaining input. Returns None if `input` does not begin with any trigger. Empty triggers are
+/// never matched.
+///
+/// ```
+/// use comma::strip_trigger;
+///
+/// assert_eq!(strip_trigger("!!ban joe", &["!", "!!"]), Some(("!!", "ban joe")));
+/// assert_eq!(strip_trigger("hello", &["!", "!!"]), None);
+/// ```
+pub fn strip_trigger<'a, 't>(input: &'a str, triggers: &[&'t str]) -> Option<(&'t str, &'a str)> {
+    let trigger = triggers
+        .iter()
+        .filter(|trigger| !trigger.is_empty() && input.starts_with(**trigger))
+        .max_by_key(|trigger| trigger.len())?;
+    Some((trigger, &input[trigger.len()..]))
+}
+
+/// Strips a trigger from `input` with [`strip_trigger`], then parses the remainder like
+/// [`parse_command`](crate::parse_command). Returns None if `input` does not begin with any
+/// trigger or the remainder is malformed.
+///
+/// ```
+/// use comma::parse_triggered;
+///
+/// let (trigger, args) = parse_triggered(".cmd kick 'Bad Bot'", &["!", "/", ".cmd "]).unwrap();
+/// assert_eq!(trigger, ".cmd ");
+/// assert_eq!(args, vec!["kick", "Bad Bot"]);
+/// ```
+pub fn parse_triggered<'t>(input: &str, triggers: &[&'t str]) -> Option<(&'t str, Vec<String>)> {
+    Parser::new().parse_triggered(input, triggers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_triggered;
+
+    #[test]
+    fn triggers() {
+        assert_eq!(parse_triggered("/me waves", &[""]), None);
+        assert_eq!(parse_triggered("/say 'oops", &["/"]), None);
+
+        let (trigger, args) = parse_triggered("/", &["/"]).unwrap();
+        assert_eq!(trigger, "/");
+        assert!(args.is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+//! Variable references within command strings.
+
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use core::hash::BuildHasher;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// A source of variable values, used when expanding variable references.
+///
+/// Implementations are provided for maps from names to values, for closures, and for [`Env`].
+pub trait Variables {
+    /// The value of the named variable, or None if it is undefined.
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+#[cfg(feature = "std")]
+impl<S: BuildHasher> Variables for HashMap<String, String, S> {
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+impl Variables for BTreeMap<String, String> {
+    fn get(&self, name: &str) -> Option<String> {
+        BTreeMap::get(self, name).cloned()
+    }
+}
+
+impl<F: Fn(&str) -> Option<String>> Variables for F {
+    fn get(&self, name: &str) -> Option<String> {
+        self(name)
+    }
+}
+
+/// Resolves variables from the process environment.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Env;
+
+#[cfg(feature = "std")]
+impl Variables for Env {
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// Expands Windows-style `%NAME%` references in `input` using `vars`, as `cmd.exe` does before
+/// splitting a command line. References to undefined variables, and any `%` without a closing
+/// counterpart, are left in place literally. Because expansion happens before parsing, a value
+/// containing whitespace will be split into several tokens unless the reference is quoted.
+///
+/// ```
+/// use comma::{expand_percent_vars, parse_command};
+/// use std::collections::BTreeMap;
+///
+/// let mut vars = BTreeMap::new();
+/// vars.insert(String::from("USERPROFILE"), String::from("C:/Users/me"));
+///
+/// let expanded = expand_percent_vars("dir \"%USERPROFILE%/My Files\" %MISSING% 50%", &vars);
+/// assert_eq!(expanded, "dir \"C:/Users/me/My Files\" %MISSING% 50%");
+/// assert_eq!(parse_command(&expanded).unwrap()[1], "C:/Users/me/My Files");
+/// ```
+pub fn expand_percent_vars(input: &str, vars: &impl Variables) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(open) = rest.find('%') {
+        output.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+
+        let value = after
+            .find('%')
+            .filter(|&close| close > 0)
+            .and_then(|close| Some((close, vars.get(&after[..close])?)));
+
+        match value {
+            Some((close, value)) => {
+                output.push_str(&value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                output.push('%');
+                rest = after;
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expand_percent_vars;
+
+    #[test]
+    fn percent_vars() {
+        let vars = |name: &str| match name {
+            "A" => Some(String::from("1")),
+            "B" => Some(String::from("%A%")),
+            _ => None,
+        };
+        assert_eq!(
+            expand_percent_vars("%X%A% %B% %% %A", &vars),
+            "%X1 %A% %% %A"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Non-fatal diagnostics about well-formed input.
+
+use core::fmt;
+use core::ops::Range;
+
+/// The reason well-formed input may still not mean what its author intended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// An unquoted glob character, which a shell would expand, was kept literally.
+    UnquotedGlob(char),
+    /// A token combines single- and double-quoted sections.
+    MixedQuotes,
+    /// A backslash escaped a character that has no special meaning, so it could be omitted.
+    UnnecessaryEscape(char),
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WarningKind::UnquotedGlob(ch) => write!(f, "unquoted glob character {:?}", ch),
+            WarningKind::MixedQuotes => f.write_str("single and double quotes mixed in one token"),
+            WarningKind::UnnecessaryEscape(ch) => write!(f, "unnecessary escape sequence \\{}", ch),
+        }
+    }
+}
+
+/// A warning describing a questionable, but well-formed, part of the input. See
+/// [`Parser::parse_with_warnings`](crate::Parser::parse_with_warnings).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Warning {
+    kind: WarningKind,
+    span: Range<usize>,
+}
+
+impl Warning {
+    pub(crate) fn new(kind: WarningKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    /// The kind of problem found.
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// The byte range of the input the warning concerns.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.span.start)
+    }
+}